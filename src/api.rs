@@ -1,10 +1,13 @@
 // DPLang 公共 API - 供其他程序调用
 
 use crate::lexer::Lexer;
-use crate::parser::Parser;
-use crate::executor::DataStreamExecutor;
+use crate::parser::{Parser, BinaryOp, DivMode, Parameter, TypeAnnotation};
+use crate::executor::{DataStreamExecutor, StreamingExecutor};
 use crate::runtime::Value;
+use std::borrow::Cow;
 use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// DPLang 解释器
 pub struct DPLangInterpreter {
@@ -124,8 +127,8 @@ fn parse_json_object(json_str: &str) -> Result<HashMap<String, Value>, String> {
         
         let value = if value_str.starts_with('"') && value_str.ends_with('"') {
             Value::String(value_str.trim_matches('"').to_string())
-        } else if let Ok(n) = value_str.parse::<f64>() {
-            Value::Number(n)
+        } else if let Some(n) = Value::parse_number(value_str) {
+            n
         } else if value_str == "true" {
             Value::Bool(true)
         } else if value_str == "false" {
@@ -142,42 +145,448 @@ fn parse_json_object(json_str: &str) -> Result<HashMap<String, Value>, String> {
     Ok(result)
 }
 
+/// 解析单行 JSON 记录（JSON-lines 流式输入）
+/// {"code": "600000", "close": 10.5} => tick 数据
+pub fn parse_json_record(line: &str) -> Result<HashMap<String, Value>, String> {
+    let json: serde_json::Value = serde_json::from_str(line.trim())
+        .map_err(|e| format!("JSON 解析失败: {}", e))?;
+    
+    match json {
+        serde_json::Value::Object(fields) => fields
+            .into_iter()
+            .map(|(key, value)| Ok((key, json_to_value(value)?)))
+            .collect(),
+        _ => Err("JSON 记录必须是对象".to_string()),
+    }
+}
+
+/// 解析 JSON-lines 文本，跳过格式错误的行
+/// 返回 (记录列表, 跳过的行数)
+pub fn parse_json_lines(input: &str) -> (Vec<HashMap<String, Value>>, usize) {
+    let mut records = Vec::new();
+    let mut skipped = 0;
+    
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        match parse_json_record(line) {
+            Ok(record) => records.push(record),
+            Err(_) => skipped += 1,
+        }
+    }
+    
+    (records, skipped)
+}
+
+/// 将 serde_json 值转换为 DPLang 值
+fn json_to_value(json: serde_json::Value) -> Result<Value, String> {
+    Value::from_json(json).map_err(|e| e.message)
+}
+
+/// 命令行对脚本执行设置的覆盖（--precision N / --div-mode strict|null / --param name=value）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecutionOverrides {
+    pub precision: Option<u32>,
+    pub div_mode: Option<DivMode>,
+    /// 注入脚本的只读参数（可重复指定）
+    pub params: Vec<(String, Value)>,
+}
+
+impl ExecutionOverrides {
+    /// 从命令行参数中解析覆盖选项，未出现的选项保持脚本中的设置
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let value_of = |name: &str| -> Result<Option<&str>, String> {
+            match args.iter().position(|arg| arg == name) {
+                Some(i) => args.get(i + 1)
+                    .map(|v| Some(v.as_str()))
+                    .ok_or_else(|| format!("{} 缺少参数值", name)),
+                None => Ok(None),
+            }
+        };
+        
+        let precision = value_of("--precision")?
+            .map(|v| v.parse::<u32>().map_err(|_| format!("无效的 --precision 值: {}", v)))
+            .transpose()?;
+        let div_mode = value_of("--div-mode")?
+            .map(|v| DivMode::from_name(v).ok_or_else(|| format!("无效的 --div-mode 值: {}（可选 strict 或 null）", v)))
+            .transpose()?;
+        let params = args.iter()
+            .enumerate()
+            .filter(|(_, arg)| *arg == "--param")
+            .map(|(i, _)| {
+                let item = args.get(i + 1).ok_or_else(|| "--param 缺少参数值".to_string())?;
+                let (name, value) = item.split_once('=')
+                    .ok_or_else(|| format!("无效的 --param 值: {}（格式为 name=value）", item))?;
+                Ok((name.trim().to_string(), parse_csv_value(value.trim())))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        
+        Ok(ExecutionOverrides { precision, div_mode, params })
+    }
+    
+    /// 应用到数据流执行器（覆盖脚本中的 PRECISION / CONFIG，注入参数）
+    /// 参数与 INPUT 声明的列同名时报错
+    pub fn apply(&self, executor: &mut DataStreamExecutor) -> Result<(), String> {
+        if let Some(scale) = self.precision {
+            executor.set_precision_scale(scale);
+        }
+        if let Some(div_mode) = self.div_mode {
+            executor.set_div_mode(div_mode);
+        }
+        for (name, value) in &self.params {
+            executor.set_param(name, value.clone()).map_err(|e| e.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// 输出截取（--offset / --limit）：跳过前 offset 行后最多保留 limit 行
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OutputWindow {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+impl OutputWindow {
+    /// 从命令行参数中解析 --offset / --limit（须为非负整数）
+    pub fn from_args(args: &[String]) -> Result<Self, String> {
+        let value_of = |name: &str| -> Result<Option<usize>, String> {
+            match args.iter().position(|arg| arg == name) {
+                Some(i) => {
+                    let value = args.get(i + 1).ok_or_else(|| format!("{} 缺少参数值", name))?;
+                    value.parse::<usize>()
+                        .map(Some)
+                        .map_err(|_| format!("无效的 {} 值: {}（须为非负整数）", name, value))
+                }
+                None => Ok(None),
+            }
+        };
+        
+        Ok(OutputWindow {
+            offset: value_of("--offset")?.unwrap_or(0),
+            limit: value_of("--limit")?,
+        })
+    }
+    
+    /// 截取输出行
+    pub fn apply<T>(&self, rows: Vec<T>) -> Vec<T> {
+        rows.into_iter()
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// 选股筛选条件（screen --select），如 "selected" 或 "score > 0.8"
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectPredicate {
+    /// 输出列名
+    field: String,
+    /// 比较运算和阈值（None 时按列的布尔值筛选）
+    condition: Option<(BinaryOp, Value)>,
+    /// 列不存在或为 null 时是否保留该行
+    keep_missing: bool,
+}
+
+impl SelectPredicate {
+    /// 解析 --select 参数：单个列名，或 "列名 运算符 阈值"（运算符为 > >= < <= == !=）
+    /// 显式指定时，缺少该列的行不保留
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        const OPS: [(&str, BinaryOp); 6] = [
+            (">=", BinaryOp::GtEq),
+            ("<=", BinaryOp::LtEq),
+            ("==", BinaryOp::Eq),
+            ("!=", BinaryOp::NotEq),
+            (">", BinaryOp::Gt),
+            ("<", BinaryOp::Lt),
+        ];
+        
+        let (field, condition) = match OPS.iter().find_map(|(text, op)| expr.split_once(text).map(|parts| (parts, op))) {
+            Some(((field, threshold), op)) => {
+                let threshold = threshold.trim();
+                if threshold.is_empty() {
+                    return Err(format!("筛选条件缺少阈值: {}", expr));
+                }
+                (field.trim(), Some((*op, parse_csv_value(threshold))))
+            }
+            None => (expr.trim(), None),
+        };
+        
+        if field.is_empty() || !field.chars().all(|c| c.is_alphanumeric() || c == '_') {
+            return Err(format!("无效的筛选列名: {}", expr));
+        }
+        
+        Ok(SelectPredicate { field: field.to_string(), condition, keep_missing: false })
+    }
+    
+    /// 行是否入选
+    pub fn matches(&self, row: &HashMap<String, Value>) -> bool {
+        let value = match row.get(&self.field) {
+            Some(value) if !value.is_null() => value,
+            _ => return self.keep_missing,
+        };
+        
+        let (op, threshold) = match &self.condition {
+            Some(condition) => condition,
+            None => return match value {
+                Value::Bool(b) => *b,
+                other => self.keep_missing || other.to_bool(),
+            },
+        };
+        
+        match (value.to_number(), threshold.to_number()) {
+            (Ok(a), Ok(b)) => match op {
+                BinaryOp::Gt => a > b,
+                BinaryOp::GtEq => a >= b,
+                BinaryOp::Lt => a < b,
+                BinaryOp::LtEq => a <= b,
+                BinaryOp::Eq => a == b,
+                BinaryOp::NotEq => a != b,
+                _ => false,
+            },
+            // 非数值只支持相等比较
+            _ => match op {
+                BinaryOp::Eq => value == threshold,
+                BinaryOp::NotEq => value != threshold,
+                _ => false,
+            },
+        }
+    }
+}
+
+impl Default for SelectPredicate {
+    /// 默认按 selected 列筛选，没有该列（或不是布尔值）时全部保留
+    fn default() -> Self {
+        SelectPredicate { field: "selected".to_string(), condition: None, keep_missing: true }
+    }
+}
+
+/// 流式输入格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamInputFormat {
+    /// 首行为表头，之后每行逗号分隔
+    Csv,
+    /// 每行一个 JSON 对象
+    JsonLines,
+}
+
+/// 流式处理统计
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StreamStats {
+    /// 成功执行的 tick 数
+    pub processed: usize,
+    /// 格式错误被跳过的行数
+    pub skipped: usize,
+    /// 执行出错的 tick 数
+    pub failed: usize,
+    /// 是否因停止标志提前退出
+    pub stopped: bool,
+}
+
+/// 逐行读取 tick 并推送给流式执行器
+/// 每个 tick 前检查 stop 标志（如 Ctrl-C 设置），置位后立即退出，由调用方负责刷新输出
+pub fn run_tick_stream<I, F>(
+    lines: I,
+    format: StreamInputFormat,
+    executor: &mut StreamingExecutor,
+    stop: &AtomicBool,
+    mut on_output: F,
+) -> StreamStats
+where
+    I: IntoIterator<Item = String>,
+    F: FnMut(&HashMap<String, Value>),
+{
+    let mut stats = StreamStats::default();
+    let mut headers: Option<Vec<String>> = None;
+    
+    for line in lines {
+        if stop.load(Ordering::SeqCst) {
+            stats.stopped = true;
+            break;
+        }
+        
+        if line.trim().is_empty() {
+            continue;
+        }
+        
+        // 解析 tick 数据（格式错误的行计数后跳过）
+        let tick_data = match format {
+            StreamInputFormat::JsonLines => match parse_json_record(&line) {
+                Ok(record) => record,
+                Err(_) => {
+                    stats.skipped += 1;
+                    continue;
+                }
+            },
+            StreamInputFormat::Csv => {
+                let values = split_csv_line(&line);
+                match &headers {
+                    None => {
+                        headers = Some(values);
+                        continue;
+                    }
+                    Some(h) if h.len() != values.len() => {
+                        stats.skipped += 1;
+                        continue;
+                    }
+                    Some(h) => h.iter()
+                        .cloned()
+                        .zip(values.iter().map(|v| parse_csv_value(v)))
+                        .collect(),
+                }
+            }
+        };
+        
+        match executor.push_tick(tick_data) {
+            Ok(output) => {
+                if let Some(row) = output {
+                    on_output(&row);
+                }
+                stats.processed += 1;
+            }
+            Err(_) => stats.failed += 1,
+        }
+    }
+    
+    // 输入迭代器因停止标志提前结束（如定时检查标志的读取线程）时同样记为停止
+    if stop.load(Ordering::SeqCst) {
+        stats.stopped = true;
+    }
+    
+    // 流结束（EOF 或停止）时按配置输出未完成的 bar
+    match executor.finish() {
+        Ok(Some(row)) => on_output(&row),
+        Ok(None) => {}
+        Err(_) => stats.failed += 1,
+    }
+    
+    stats
+}
+
+/// 管道模式：从 input 读取 CSV（首行为表头），逐行流式计算，输出 CSV 写入 output
+/// columns 为输出列顺序（通常取 OUTPUT 声明），为空时按首个输出行的列名排序；
+/// 只向 output 写数据，写入失败（如下游关闭管道）时停止读取并返回错误
+pub fn run_csv_pipe<R: BufRead, W: Write>(
+    input: R,
+    output: &mut W,
+    executor: &mut StreamingExecutor,
+    columns: &[String],
+) -> io::Result<StreamStats> {
+    let mut columns = columns.to_vec();
+    if !columns.is_empty() {
+        writeln!(output, "{}", columns.join(","))?;
+    }
+    
+    let stop = AtomicBool::new(false);
+    let mut write_error = None;
+    let mut read_error = None;
+    let lines = input.lines().map_while(|line| line.map_err(|e| read_error = Some(e)).ok());
+    let stats = run_tick_stream(lines, StreamInputFormat::Csv, executor, &stop, |row| {
+        if columns.is_empty() {
+            columns = row.keys().cloned().collect();
+            columns.sort();
+            if let Err(e) = writeln!(output, "{}", columns.join(",")) {
+                write_error = Some(e);
+                stop.store(true, Ordering::SeqCst);
+                return;
+            }
+        }
+        if let Err(e) = writeln!(output, "{}", format_row_csv(row, &columns)) {
+            write_error = Some(e);
+            stop.store(true, Ordering::SeqCst);
+        }
+    });
+    
+    match write_error.or(read_error) {
+        Some(e) => Err(e),
+        None => output.flush().map(|_| stats),
+    }
+}
+
+/// CSV 读写选项
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvOptions {
+    /// 解析时视为 null 的字段文本（空字段总是视为 null），如 "NA"、"-"、"NaN"
+    pub null_tokens: Vec<String>,
+    /// 格式化时 null 的输出文本
+    pub null_output: String,
+    /// 数字分组格式（默认不处理分组符）
+    pub number_locale: NumberLocale,
+}
+
+/// CSV 数字分组格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NumberLocale {
+    /// 原样解析，如 1234.56
+    #[default]
+    Plain,
+    /// 逗号分组、点号小数，如 1,234.56
+    Grouped,
+    /// 点号分组、逗号小数，如 1.234,56
+    European,
+}
+
+impl NumberLocale {
+    /// 去掉分组符并统一小数点，返回可供 parse::<f64>() 的文本
+    fn normalize(self, text: &str) -> Cow<'_, str> {
+        match self {
+            NumberLocale::Plain => Cow::Borrowed(text),
+            NumberLocale::Grouped => Cow::Owned(text.replace(',', "")),
+            NumberLocale::European => Cow::Owned(text.replace('.', "").replace(',', ".")),
+        }
+    }
+}
+
+impl Default for CsvOptions {
+    fn default() -> Self {
+        CsvOptions {
+            null_tokens: vec!["null".to_string()],
+            null_output: String::new(),
+            number_locale: NumberLocale::Plain,
+        }
+    }
+}
+
+impl CsvOptions {
+    /// 使用同一个 null 文本读写（保证往返一致），如 CsvOptions::with_null_token("NA")
+    pub fn with_null_token(token: &str) -> Self {
+        CsvOptions {
+            null_tokens: vec![token.to_string()],
+            null_output: token.to_string(),
+            ..CsvOptions::default()
+        }
+    }
+}
+
 /// 解析 CSV 输入
 pub fn parse_csv(csv_str: &str) -> Result<Vec<HashMap<String, Value>>, String> {
+    parse_csv_with(csv_str, &CsvOptions::default())
+}
+
+/// 按指定选项解析 CSV 输入
+pub fn parse_csv_with(csv_str: &str, options: &CsvOptions) -> Result<Vec<HashMap<String, Value>>, String> {
     let lines: Vec<&str> = csv_str.trim().lines().collect();
     if lines.is_empty() {
         return Ok(vec![HashMap::new()]);
     }
     
     // 第一行是表头
-    let headers: Vec<&str> = lines[0].split(',').map(|s| s.trim()).collect();
+    let headers = split_csv_line(lines[0]);
+    for name in duplicate_headers(&headers) {
+        eprintln!("警告: CSV 表头中的列 {} 重复，后出现的列将覆盖前面的值", name);
+    }
     
     let mut result = Vec::new();
     
     // 解析数据行
     for line in &lines[1..] {
-        let values: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+        let values = split_csv_line(line);
         let mut row = HashMap::new();
         
-        for (i, &header) in headers.iter().enumerate() {
+        for (i, header) in headers.iter().enumerate() {
             if i >= values.len() {
                 break;
             }
             
-            let value_str = values[i];
-            let value = if let Ok(n) = value_str.parse::<f64>() {
-                Value::Number(n)
-            } else if value_str == "true" {
-                Value::Bool(true)
-            } else if value_str == "false" {
-                Value::Bool(false)
-            } else if value_str.is_empty() || value_str == "null" {
-                Value::Null
-            } else {
-                Value::String(value_str.to_string())
-            };
-            
-            row.insert(header.to_string(), value);
+            row.insert(header.clone(), parse_csv_value_with(&values[i], options));
         }
         
         result.push(row);
@@ -190,6 +599,70 @@ pub fn parse_csv(csv_str: &str) -> Result<Vec<HashMap<String, Value>>, String> {
     Ok(result)
 }
 
+/// 检查 CSV 表头中重复的列名（按首次重复出现的顺序）
+pub fn csv_duplicate_headers(csv_str: &str) -> Vec<String> {
+    match csv_str.trim().lines().next() {
+        Some(header) => duplicate_headers(&split_csv_line(header)),
+        None => Vec::new(),
+    }
+}
+
+fn duplicate_headers(headers: &[String]) -> Vec<String> {
+    let mut duplicates = Vec::new();
+    for (i, name) in headers.iter().enumerate() {
+        if headers[..i].contains(name) && !duplicates.contains(name) {
+            duplicates.push(name.clone());
+        }
+    }
+    duplicates
+}
+
+/// 按逗号切分一行 CSV，双引号内的逗号不切分（"" 表示转义的引号）
+pub(crate) fn split_csv_line(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+    
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                current.push('"');
+                chars.next();
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => {
+                fields.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(ch),
+        }
+    }
+    fields.push(current.trim().to_string());
+    
+    fields
+}
+
+/// 解析单个 CSV 字段（数字 / 布尔 / null / 字符串）
+pub fn parse_csv_value(value_str: &str) -> Value {
+    parse_csv_value_with(value_str, &CsvOptions::default())
+}
+
+/// 按指定选项解析单个 CSV 字段（null 文本优先于数字判断，如 "NaN"）
+pub fn parse_csv_value_with(value_str: &str, options: &CsvOptions) -> Value {
+    if value_str.is_empty() || options.null_tokens.iter().any(|t| t == value_str) {
+        Value::Null
+    } else if let Some(n) = Value::parse_number(&options.number_locale.normalize(value_str)) {
+        n
+    } else if value_str == "true" {
+        Value::Bool(true)
+    } else if value_str == "false" {
+        Value::Bool(false)
+    } else {
+        Value::String(value_str.to_string())
+    }
+}
+
 /// 格式化输出为 JSON
 fn format_output_json(output: &[HashMap<String, Value>]) -> String {
     let mut result = String::from("[\n");
@@ -223,6 +696,7 @@ fn format_value_json(value: &Value) -> String {
         Value::Null => "null".to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Number(n) => n.to_string(),
+        Value::Integer(i) => i.to_string(),
         Value::Decimal(d) => d.to_string(),
         Value::String(s) => format!("\"{}\"", s),
         Value::Array(arr) => {
@@ -241,7 +715,7 @@ fn format_value_json(value: &Value) -> String {
     }
 }
 
-/// 格式化输出为 CSV
+/// 格式化输出为 CSV（列按名称排序）
 pub fn format_output_csv(output: &[HashMap<String, Value>]) -> String {
     if output.is_empty() {
         return String::new();
@@ -258,21 +732,26 @@ pub fn format_output_csv(output: &[HashMap<String, Value>]) -> String {
     }
     headers.sort();
     
+    format_output_csv_ordered(output, &headers, &CsvOptions::default())
+}
+
+/// 按指定列顺序和选项格式化输出为 CSV
+pub fn format_output_csv_ordered(
+    output: &[HashMap<String, Value>],
+    columns: &[String],
+    options: &CsvOptions,
+) -> String {
     let mut result = String::new();
     
     // 写入表头
-    result.push_str(&headers.join(","));
+    result.push_str(&columns.join(","));
     result.push('\n');
     
-    // 写入数据行
+    // 写入数据行（缺失的列按 null 输出）
     for row in output {
-        let values: Vec<String> = headers
+        let values: Vec<String> = columns
             .iter()
-            .map(|h| {
-                row.get(h)
-                    .map(format_value_csv)
-                    .unwrap_or_else(|| String::new())
-            })
+            .map(|h| format_value_csv(row.get(h).unwrap_or(&Value::Null), options))
             .collect();
         
         result.push_str(&values.join(","));
@@ -282,12 +761,127 @@ pub fn format_output_csv(output: &[HashMap<String, Value>]) -> String {
     result
 }
 
+/// 按 OUTPUT 声明格式化输出为 CSV，表头写成 name:type（无类型标注的列只写列名）
+pub fn format_output_csv_typed(
+    output: &[HashMap<String, Value>],
+    schema: &[Parameter],
+    options: &CsvOptions,
+) -> String {
+    let columns: Vec<String> = schema.iter().map(|p| p.name.clone()).collect();
+    let body = format_output_csv_ordered(output, &columns, options);
+    
+    let header: Vec<String> = schema
+        .iter()
+        .map(|p| match &p.type_annotation {
+            Some(t) => format!("{}:{}", p.name, t),
+            None => p.name.clone(),
+        })
+        .collect();
+    
+    // 替换第一行表头
+    let data = body.split_once('\n').map(|(_, rest)| rest).unwrap_or("");
+    format!("{}\n{}", header.join(","), data)
+}
+
+/// 解析带 name:type 表头的 CSV（format_output_csv_typed 的逆操作）
+pub fn parse_csv_typed(csv_str: &str, options: &CsvOptions) -> Result<Vec<HashMap<String, Value>>, String> {
+    let lines: Vec<&str> = csv_str.trim().lines().collect();
+    if lines.is_empty() {
+        return Ok(vec![HashMap::new()]);
+    }
+    
+    // 解析表头中的类型标注
+    let mut headers = Vec::new();
+    for field in split_csv_line(lines[0]) {
+        let (name, type_annotation) = match field.split_once(':') {
+            Some((name, type_str)) => (name.trim().to_string(), Some(parse_type_name(type_str.trim())?)),
+            None => (field, None),
+        };
+        headers.push((name, type_annotation));
+    }
+    
+    let mut result = Vec::new();
+    for line in &lines[1..] {
+        let values = split_csv_line(line);
+        let mut row = HashMap::new();
+        
+        for (i, (name, type_annotation)) in headers.iter().enumerate() {
+            if i >= values.len() {
+                break;
+            }
+            row.insert(name.clone(), parse_typed_csv_value(&values[i], type_annotation.as_ref(), options)?);
+        }
+        
+        result.push(row);
+    }
+    
+    if result.is_empty() {
+        result.push(HashMap::new());
+    }
+    
+    Ok(result)
+}
+
+/// 解析表头中的类型名
+fn parse_type_name(type_str: &str) -> Result<TypeAnnotation, String> {
+    match type_str {
+        "number" => Ok(TypeAnnotation::Number),
+        "decimal" => Ok(TypeAnnotation::Decimal),
+        "string" => Ok(TypeAnnotation::String),
+        "bool" => Ok(TypeAnnotation::Bool),
+        "array" => Ok(TypeAnnotation::Array),
+        "null" => Ok(TypeAnnotation::Null),
+        _ => Err(format!("未知的列类型: {}", type_str)),
+    }
+}
+
+/// 按列类型解析单个 CSV 字段
+fn parse_typed_csv_value(
+    value_str: &str,
+    type_annotation: Option<&TypeAnnotation>,
+    options: &CsvOptions,
+) -> Result<Value, String> {
+    if value_str.is_empty() || options.null_tokens.iter().any(|t| t == value_str) {
+        return Ok(Value::Null);
+    }
+    
+    match type_annotation {
+        Some(TypeAnnotation::String) => Ok(Value::String(value_str.to_string())),
+        Some(TypeAnnotation::Decimal) => {
+            let text = options.number_locale.normalize(value_str);
+            rust_decimal::Decimal::from_str_exact(&text)
+                .map(Value::Decimal)
+                .map_err(|_| format!("无法解析为 decimal: {}", value_str))
+        }
+        Some(TypeAnnotation::Number) => options.number_locale.normalize(value_str)
+            .parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| format!("无法解析为 number: {}", value_str)),
+        Some(TypeAnnotation::Bool) => match value_str {
+            "true" => Ok(Value::Bool(true)),
+            "false" => Ok(Value::Bool(false)),
+            _ => Err(format!("无法解析为 bool: {}", value_str)),
+        },
+        _ => Ok(parse_csv_value_with(value_str, options)),
+    }
+}
+
+/// 按给定列顺序把一行格式化为 CSV（缺失列输出 null）
+pub fn format_row_csv(row: &HashMap<String, Value>, columns: &[String]) -> String {
+    let options = CsvOptions::default();
+    columns.iter()
+        .map(|c| format_value_csv(row.get(c).unwrap_or(&Value::Null), &options))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
 /// 格式化单个值为 CSV
-fn format_value_csv(value: &Value) -> String {
+fn format_value_csv(value: &Value, options: &CsvOptions) -> String {
     match value {
-        Value::Null => String::new(),
+        Value::Null => options.null_output.clone(),
         Value::Bool(b) => b.to_string(),
         Value::Number(n) => n.to_string(),
+        Value::Integer(i) => i.to_string(),
         Value::Decimal(d) => d.to_string(),
         Value::String(s) => {
             // 如果包含逗号或引号，需要转义
@@ -298,13 +892,13 @@ fn format_value_csv(value: &Value) -> String {
             }
         }
         Value::Array(arr) => {
-            let items: Vec<String> = arr.iter().map(format_value_csv).collect();
+            let items: Vec<String> = arr.iter().map(|v| format_value_csv(v, options)).collect();
             format!("\"[{}]\"", items.join("; "))
         }
         Value::ArraySlice { column_data, start, len } => {
             let items: Vec<String> = (0..*len)
                 .filter_map(|i| column_data.get(*start + i))
-                .map(format_value_csv)
+                .map(|v| format_value_csv(v, options))
                 .collect();
             format!("\"[{}]\"", items.join("; "))
         }
@@ -327,6 +921,260 @@ mod tests {
         assert_eq!(result[0].get("age"), Some(&Value::Number(30.0)));
     }
 
+    #[test]
+    fn test_parse_json_lines_skips_malformed() {
+        let input = r#"{"code": "600000", "close": 10.5}
+not json
+{"code": "600519", "close": 1700, "volume": null}
+[1, 2, 3]
+{"code": "000001", "close": }
+"#;
+        let (records, skipped) = parse_json_lines(input);
+        
+        assert_eq!(records.len(), 2);
+        assert_eq!(skipped, 3);
+        assert_eq!(records[0].get("code"), Some(&Value::String("600000".to_string())));
+        assert_eq!(records[1].get("close"), Some(&Value::Number(1700.0)));
+        assert_eq!(records[1].get("volume"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_run_tick_stream_stops_on_flag() {
+        let source = r#"
+-- INPUT close:number --
+-- OUTPUT double:number --
+
+double = close * 2
+return [double]
+"#;
+        let mut lexer = crate::lexer::Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut executor = StreamingExecutor::new(script, 10);
+        
+        let lines = (1..=100).map(|i| format!("{{\"close\": {}}}", i));
+        let stop = AtomicBool::new(false);
+        let mut flushed = Vec::new();
+        
+        // 第 3 个输出后模拟 Ctrl-C
+        let stats = run_tick_stream(lines, StreamInputFormat::JsonLines, &mut executor, &stop, |row| {
+            flushed.push(row.clone());
+            if flushed.len() == 3 {
+                stop.store(true, Ordering::SeqCst);
+            }
+        });
+        
+        assert!(stats.stopped);
+        assert_eq!(stats.processed, 3);
+        assert_eq!(flushed.len(), 3);
+        assert_eq!(flushed[2].get("double"), Some(&Value::Number(6.0)));
+    }
+
+    #[test]
+    fn test_run_csv_pipe_writes_csv() {
+        let source = r#"
+-- INPUT code:string, close:number --
+-- OUTPUT code:string, double:number --
+
+return [code, close * 2]
+"#;
+        let mut lexer = crate::lexer::Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut executor = StreamingExecutor::new(script, 10);
+        
+        let input = "code,close\nA,1.5\nB\nC,3\n";
+        let mut output = Vec::new();
+        let columns = vec!["code".to_string(), "double".to_string()];
+        let stats = run_csv_pipe(input.as_bytes(), &mut output, &mut executor, &columns).unwrap();
+        
+        // 列数不符的行被跳过，不写入输出
+        assert_eq!(String::from_utf8(output).unwrap(), "code,double\nA,3\nC,6\n");
+        assert_eq!(stats.processed, 2);
+        assert_eq!(stats.skipped, 1);
+    }
+    
+    #[test]
+    fn test_run_csv_pipe_quoted_fields() {
+        let source = r#"
+-- INPUT name:string, x:number --
+-- OUTPUT name:string, y:number --
+
+return [name, x + 1]
+"#;
+        let mut lexer = crate::lexer::Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let mut executor = StreamingExecutor::new(script, 10);
+        
+        // 引号内的逗号不切分列，输出时重新加引号
+        let input = "name,x\n\"Acme, Inc\",3\nBeta,4\n";
+        let mut output = Vec::new();
+        let columns = vec!["name".to_string(), "y".to_string()];
+        let stats = run_csv_pipe(input.as_bytes(), &mut output, &mut executor, &columns).unwrap();
+        
+        assert_eq!(String::from_utf8(output).unwrap(), "name,y\n\"Acme, Inc\",4\nBeta,5\n");
+        assert_eq!(stats.processed, 2);
+        assert_eq!(stats.skipped, 0);
+    }
+    
+    #[test]
+    fn test_execution_overrides_from_args() {
+        let args: Vec<String> = ["dplang", "run", "a.dp", "data.csv", "--precision", "2", "--div-mode", "null"]
+            .iter().map(|s| s.to_string()).collect();
+        let overrides = ExecutionOverrides::from_args(&args).unwrap();
+        assert_eq!(overrides.precision, Some(2));
+        assert_eq!(overrides.div_mode, Some(DivMode::Null));
+        
+        let with_params: Vec<String> = ["--param", "threshold=0.8", "--param", "tag=core"]
+            .iter().map(|s| s.to_string()).collect();
+        let overrides = ExecutionOverrides::from_args(&with_params).unwrap();
+        assert_eq!(overrides.params, vec![
+            ("threshold".to_string(), Value::Number(0.8)),
+            ("tag".to_string(), Value::String("core".to_string())),
+        ]);
+        let bad_param: Vec<String> = ["--param", "threshold"].iter().map(|s| s.to_string()).collect();
+        assert!(ExecutionOverrides::from_args(&bad_param).is_err());
+        
+        let none = ExecutionOverrides::from_args(&args[..4]).unwrap();
+        assert_eq!(none, ExecutionOverrides::default());
+        
+        let bad: Vec<String> = ["--div-mode", "lenient"].iter().map(|s| s.to_string()).collect();
+        assert!(ExecutionOverrides::from_args(&bad).is_err());
+        let missing: Vec<String> = vec!["--precision".to_string()];
+        assert!(ExecutionOverrides::from_args(&missing).is_err());
+    }
+    
+    #[test]
+    fn test_output_window_limit_and_offset() {
+        let rows: Vec<HashMap<String, Value>> = (0..100)
+            .map(|i| vec![("i".to_string(), Value::Number(i as f64))].into_iter().collect())
+            .collect();
+        
+        let args: Vec<String> = ["dplang", "run", "a.dp", "--offset", "95", "--limit", "3"]
+            .iter().map(|s| s.to_string()).collect();
+        let window = OutputWindow::from_args(&args).unwrap();
+        assert_eq!(window, OutputWindow { offset: 95, limit: Some(3) });
+        let trimmed = window.apply(rows.clone());
+        assert_eq!(trimmed.len(), 3);
+        assert_eq!(trimmed[0]["i"], Value::Number(95.0));
+        
+        // 超出末尾时只保留剩余的行
+        let tail = OutputWindow { offset: 98, limit: Some(10) }.apply(rows.clone());
+        assert_eq!(tail.len(), 2);
+        assert_eq!(OutputWindow::default().apply(rows).len(), 100);
+        
+        let negative: Vec<String> = ["--limit", "-1"].iter().map(|s| s.to_string()).collect();
+        assert!(OutputWindow::from_args(&negative).is_err());
+        let missing: Vec<String> = vec!["--offset".to_string()];
+        assert!(OutputWindow::from_args(&missing).is_err());
+    }
+    
+    #[test]
+    fn test_csv_null_tokens_round_trip() {
+        let options = CsvOptions::with_null_token("NA");
+        
+        let rows = parse_csv_with("code,close\nA,NA\nB,10", &options).unwrap();
+        assert_eq!(rows[0].get("close"), Some(&Value::Null));
+        assert_eq!(rows[1].get("close"), Some(&Value::Number(10.0)));
+        
+        // "NaN" 作为 null 文本时优先于数字解析
+        let nan_options = CsvOptions { null_tokens: vec!["NaN".to_string()], ..CsvOptions::default() };
+        assert_eq!(parse_csv_value_with("NaN", &nan_options), Value::Null);
+        
+        let columns = vec!["code".to_string(), "close".to_string()];
+        let csv = format_output_csv_ordered(&rows, &columns, &options);
+        assert_eq!(csv, "code,close\nA,NA\nB,10\n");
+        
+        // 同一配置往返后 null 保持不变
+        let reparsed = parse_csv_with(&csv, &options).unwrap();
+        assert_eq!(reparsed, rows);
+    }
+    
+    #[test]
+    fn test_csv_typed_header_round_trip() {
+        let source = r#"
+-- INPUT code:string, close:decimal --
+-- OUTPUT code:string, close:decimal, up:bool, ratio:number, note:string --
+
+return [code, close, code == "600000", 0.5, "a,b"]
+"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let schema = match &script {
+            crate::parser::Script::DataScript { output, .. } => output.clone(),
+            _ => unreachable!(),
+        };
+        
+        let input: Vec<HashMap<String, Value>> = vec![vec![
+            ("code".to_string(), Value::String("600000".to_string())),
+            ("close".to_string(), Value::Decimal(rust_decimal::Decimal::new(1050, 2))),
+        ].into_iter().collect()];
+        let output = DataStreamExecutor::new(script, input).execute_all().unwrap();
+        
+        let options = CsvOptions::default();
+        let csv = format_output_csv_typed(&output, &schema, &options);
+        assert_eq!(csv.lines().next(), Some("code:string,close:decimal,up:bool,ratio:number,note:string"));
+        
+        // 带类型表头时可以无损读回（数字样式的字符串保持为字符串）
+        let reparsed = parse_csv_typed(&csv, &options).unwrap();
+        assert_eq!(reparsed, output);
+    }
+    
+    #[test]
+    fn test_select_predicate_threshold() {
+        let row = |score: Value| -> HashMap<String, Value> {
+            vec![("score".to_string(), score)].into_iter().collect()
+        };
+        
+        let predicate = SelectPredicate::parse("score > 0.8").unwrap();
+        assert!(predicate.matches(&row(Value::Number(0.9))));
+        assert!(!predicate.matches(&row(Value::Number(0.8))));
+        assert!(!predicate.matches(&row(Value::Null)));
+        assert!(!predicate.matches(&HashMap::new()));
+        
+        let flag = SelectPredicate::parse("score").unwrap();
+        assert!(flag.matches(&row(Value::Bool(true))));
+        assert!(!flag.matches(&row(Value::Bool(false))));
+        assert!(!flag.matches(&HashMap::new()));
+        
+        // 默认按 selected 列筛选，没有该列时全部保留
+        let default = SelectPredicate::default();
+        assert!(default.matches(&row(Value::Number(0.1))));
+        assert!(!default.matches(&vec![("selected".to_string(), Value::Bool(false))].into_iter().collect()));
+        
+        assert!(SelectPredicate::parse("score >").is_err());
+        assert!(SelectPredicate::parse("a + b > 1").is_err());
+    }
+    
+    #[test]
+    fn test_csv_duplicate_headers() {
+        let csv = "code,close,close,code,open\nA,1,2,B,3";
+        assert_eq!(csv_duplicate_headers(csv), vec!["close".to_string(), "code".to_string()]);
+        assert!(csv_duplicate_headers("code,close\nA,1").is_empty());
+        
+        // 重复列仍可解析，后出现的列覆盖前面的值
+        let rows = parse_csv(csv).unwrap();
+        assert_eq!(rows[0].get("close"), Some(&Value::Integer(2)));
+    }
+    
+    #[test]
+    fn test_csv_number_locale() {
+        // 带引号的字段先按引号切分，再去掉分组符
+        let grouped = CsvOptions { number_locale: NumberLocale::Grouped, ..CsvOptions::default() };
+        let rows = parse_csv_with("code,close\nA,\"1,234.56\"", &grouped).unwrap();
+        assert_eq!(rows[0].get("close"), Some(&Value::Number(1234.56)));
+        assert_eq!(rows[0].get("code"), Some(&Value::String("A".to_string())));
+        
+        let european = CsvOptions { number_locale: NumberLocale::European, ..CsvOptions::default() };
+        assert_eq!(parse_csv_value_with("1.234,56", &european), Value::Number(1234.56));
+        
+        // 默认不处理分组符
+        let rows = parse_csv("code,close\nA,\"1,234.56\"").unwrap();
+        assert_eq!(rows[0].get("close"), Some(&Value::String("1,234.56".to_string())));
+    }
+
     #[test]
     fn test_format_output_csv() {
         let mut row1 = HashMap::new();
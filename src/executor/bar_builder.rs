@@ -0,0 +1,137 @@
+// K 线合成 - 流式执行前把 tick 按固定时间间隔聚合为 OHLCV bar
+
+use crate::runtime::{Value, RuntimeError};
+use std::collections::HashMap;
+
+/// 正在累积的 bar
+#[derive(Debug, Clone, PartialEq)]
+struct PartialBar {
+    /// bar 起始时间（间隔的整数倍）
+    start: i64,
+    open: f64,
+    high: f64,
+    low: f64,
+    close: f64,
+    volume: f64,
+}
+
+/// K 线合成器：按时间戳列把 tick 分到 `interval_secs` 秒的区间，
+/// 区间结束（收到下一区间的 tick）时产出一行 OHLCV
+///
+/// 产出的行包含时间戳列（区间起始时间）和 open/high/low/close/volume 列。
+/// 时间戳为 Unix 秒；早于当前区间的乱序 tick 视为错误。
+#[derive(Debug, Clone, PartialEq)]
+pub struct BarBuilder {
+    /// 区间长度（秒）
+    interval_secs: i64,
+    /// 时间戳列名
+    timestamp_col: String,
+    /// 成交价列名
+    price_col: String,
+    /// 成交量列名（tick 中缺失时按 0 计）
+    volume_col: String,
+    /// 停止时是否输出未完成的最后一根 bar
+    emit_partial: bool,
+    /// 当前区间
+    current: Option<PartialBar>,
+}
+
+impl BarBuilder {
+    /// 创建合成器（价格列默认 price，成交量列默认 volume）
+    pub fn new(interval_secs: u64, timestamp_col: &str) -> Self {
+        BarBuilder {
+            interval_secs: interval_secs.max(1) as i64,
+            timestamp_col: timestamp_col.to_string(),
+            price_col: "price".to_string(),
+            volume_col: "volume".to_string(),
+            emit_partial: false,
+            current: None,
+        }
+    }
+
+    /// 设置成交价列名
+    pub fn with_price_col(mut self, name: &str) -> Self {
+        self.price_col = name.to_string();
+        self
+    }
+
+    /// 设置成交量列名
+    pub fn with_volume_col(mut self, name: &str) -> Self {
+        self.volume_col = name.to_string();
+        self
+    }
+
+    /// 停止时是否输出未完成的最后一根 bar
+    pub fn with_emit_partial(mut self, enabled: bool) -> Self {
+        self.emit_partial = enabled;
+        self
+    }
+
+    /// 停止时是否输出未完成的 bar
+    pub fn emits_partial(&self) -> bool {
+        self.emit_partial
+    }
+
+    /// 推入一个 tick；tick 进入新区间时返回上一根已完成的 bar
+    pub fn push(&mut self, tick: &HashMap<String, Value>) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+        let timestamp = self.number_field(tick, &self.timestamp_col)?;
+        let price = self.number_field(tick, &self.price_col)?;
+        let volume = match tick.get(&self.volume_col) {
+            None | Some(Value::Null) => 0.0,
+            Some(_) => self.number_field(tick, &self.volume_col)?,
+        };
+        let start = (timestamp.floor() as i64).div_euclid(self.interval_secs) * self.interval_secs;
+
+        match &mut self.current {
+            Some(bar) if bar.start == start => {
+                bar.high = bar.high.max(price);
+                bar.low = bar.low.min(price);
+                bar.close = price;
+                bar.volume += volume;
+                Ok(None)
+            }
+            Some(bar) if start < bar.start => Err(RuntimeError::type_error(&format!(
+                "tick 时间 {} 早于当前 bar 起始时间 {}",
+                timestamp, bar.start
+            ))),
+            _ => {
+                let finished = self.current.replace(PartialBar {
+                    start,
+                    open: price,
+                    high: price,
+                    low: price,
+                    close: price,
+                    volume,
+                });
+                Ok(finished.map(|bar| self.bar_row(&bar)))
+            }
+        }
+    }
+
+    /// 取出未完成的 bar（流结束时调用），之后重新从空区间开始
+    pub fn flush(&mut self) -> Option<HashMap<String, Value>> {
+        self.current.take().map(|bar| self.bar_row(&bar))
+    }
+
+    /// 读取 tick 中的数值列
+    fn number_field(&self, tick: &HashMap<String, Value>, name: &str) -> Result<f64, RuntimeError> {
+        match tick.get(name) {
+            Some(value) => value.to_number().map_err(|_| {
+                RuntimeError::type_error(&format!("tick 列 {} 不是数字: {:?}", name, value))
+            }),
+            None => Err(RuntimeError::type_error(&format!("tick 缺少列: {}", name))),
+        }
+    }
+
+    /// 组装 bar 输出行
+    fn bar_row(&self, bar: &PartialBar) -> HashMap<String, Value> {
+        let mut row = HashMap::new();
+        row.insert(self.timestamp_col.clone(), Value::Integer(bar.start));
+        row.insert("open".to_string(), Value::Number(bar.open));
+        row.insert("high".to_string(), Value::Number(bar.high));
+        row.insert("low".to_string(), Value::Number(bar.low));
+        row.insert("close".to_string(), Value::Number(bar.close));
+        row.insert("volume".to_string(), Value::Number(bar.volume));
+        row
+    }
+}
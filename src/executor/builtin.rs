@@ -2,17 +2,34 @@
 
 use super::Executor;
 use crate::runtime::{Value, RuntimeError};
+use crate::api::{parse_csv_value, split_csv_line};
+use rust_decimal::{Decimal, RoundingStrategy};
 
 impl Executor {
     /// 执行内置函数
     pub(crate) fn execute_builtin(&mut self, name: &str, args: &[Value]) -> Result<Value, RuntimeError> {
-        match name {
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.check(name)?;
+        }
+        
+        // tap 原样返回值，不经过下面的 NaN 转换
+        if name == "tap" {
+            return self.builtin_tap(args);
+        }
+        
+        let result = match name {
             // 基础数据操作
             "sum" => self.builtin_sum(args),
             "max" => self.builtin_max(args),
             "min" => self.builtin_min(args),
             "length" => self.builtin_length(args),
             "concat" => self.builtin_concat(args),
+            "sqrt" => self.builtin_sqrt(args),
+            "sign" => self.builtin_sign(args),
+            "clamp" => self.builtin_clamp(args),
+            "lerp" => self.builtin_lerp(args),
+            "round_to_tick" => self.builtin_round_to_tick(args),
+            "approx_eq" => self.builtin_approx_eq(args),
             
             // 高阶函数
             "map" => self.builtin_map(args),
@@ -30,20 +47,62 @@ impl Executor {
             "sort" => self.builtin_sort(args),
             "unique" => self.builtin_unique(args),
             "reverse" => self.builtin_reverse(args),
+            "mask" => self.builtin_mask(args),
+            "chunk" => self.builtin_chunk(args),
+            "windows" => self.builtin_windows(args),
+            "head" => self.builtin_head(args),
+            "tail" => self.builtin_tail(args),
+            "cummax" => self.builtin_running_extreme("cummax", args, |new, best| new > best),
+            "cummin" => self.builtin_running_extreme("cummin", args, |new, best| new < best),
+            
+            // 缺失值处理函数
+            "fill_null" => self.builtin_fill_null(args),
+            "ffill" => self.builtin_ffill(args),
+            "interpolate" => self.builtin_interpolate(args),
             
             // 安全函数
             "safe_div" => self.builtin_safe_div(args),
             "safe_get" => self.builtin_safe_get(args),
             "safe_number" => self.builtin_safe_number(args),
             
+            // 数据校验函数
+            "validate_ohlc" => self.builtin_validate_ohlc(args),
+            "clean_ohlc" => self.builtin_clean_ohlc(args),
+            "in_range" => self.builtin_in_range(args),
+            
+            // 收益率函数
+            "pct_change" => self.builtin_pct_change(args),
+            "returns" => self.builtin_returns(args),
+            "roc" => self.builtin_change("roc", args, rate_of_change),
+            "mom" => self.builtin_change("mom", args, momentum),
+            "drawdown" => self.builtin_drawdown(args),
+            "forward" => self.builtin_forward(args),
+            
+            // 分位数函数
+            "percentile" => self.builtin_percentile(args),
+            "quantile" => self.builtin_quantile(args),
+            "histogram" => self.builtin_histogram(args),
+            
+            // 外部数据函数
+            "load_csv" => self.builtin_load_csv(args),
+            "lookup" => self.builtin_lookup(args),
+            
+            // JSON 函数
+            "to_json" => self.builtin_to_json(args),
+            "from_json" => self.builtin_from_json(args),
+            
             // 工具函数
             "print" => self.builtin_print(args),
+            "format_number" => self.builtin_format_number(args),
             
             // Null 处理函数
             "is_null" => self.builtin_is_null(args),
             
             _ => Err(RuntimeError::undefined_function(name)),
-        }
+        }?;
+        
+        // NaN/Infinity 结果按 NaN 处理模式转换
+        self.check_finite(result)
     }
     
     /// sum 函数 - 求和
@@ -53,6 +112,14 @@ impl Executor {
             return Ok(Value::Number(0.0));
         }
         
+        // 全部为 Decimal 时精确求和
+        if let Some(decimals) = all_decimals(aggregate_values(args)) {
+            return decimals.into_iter()
+                .try_fold(Decimal::ZERO, |total, d| total.checked_add(d))
+                .map(Value::Decimal)
+                .ok_or_else(|| RuntimeError::type_error("sum 的 Decimal 结果溢出"));
+        }
+        
         if let Value::Array(arr) = &args[0] {
             let mut total = 0.0;
             for v in arr {
@@ -77,7 +144,11 @@ impl Executor {
     /// max 函数 - 最大值
     fn builtin_max(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.is_empty() {
-            return Err(RuntimeError::type_error("max 需要至少一个参数"));
+            return Err(RuntimeError::argument_mismatch("max", "至少 1", args.len()));
+        }
+        
+        if let Some(decimals) = all_decimals(aggregate_values(args)) {
+            return Ok(decimals.into_iter().max().map_or(Value::Null, Value::Decimal));
         }
         
         let values: Vec<f64> = if let Value::Array(arr) = &args[0] {
@@ -103,7 +174,11 @@ impl Executor {
     /// min 函数 - 最小值
     fn builtin_min(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.is_empty() {
-            return Err(RuntimeError::type_error("min 需要至少一个参数"));
+            return Err(RuntimeError::argument_mismatch("min", "至少 1", args.len()));
+        }
+        
+        if let Some(decimals) = all_decimals(aggregate_values(args)) {
+            return Ok(decimals.into_iter().min().map_or(Value::Null, Value::Decimal));
         }
         
         let values: Vec<f64> = if let Value::Array(arr) = &args[0] {
@@ -129,12 +204,12 @@ impl Executor {
     /// length 函数 - 数组长度
     fn builtin_length(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("length 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("length", "1", args.len()));
         }
         
         match &args[0] {
-            Value::Array(arr) => Ok(Value::Number(arr.len() as f64)),
-            Value::String(s) => Ok(Value::Number(s.len() as f64)),
+            Value::Array(arr) => Ok(Value::Integer(arr.len() as i64)),
+            Value::String(s) => Ok(Value::Integer(s.len() as i64)),
             _ => Err(RuntimeError::type_error("length 参数必须是数组或字符串")),
         }
     }
@@ -157,11 +232,100 @@ impl Executor {
         Ok(Value::Array(result))
     }
     
+    /// sqrt 函数 - 平方根（支持数组逐元素计算）
+    /// sqrt(-1) 的结果为 NaN，按 NaN 处理模式返回 null 或报错
+    fn builtin_sqrt(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("sqrt", "1", args.len()));
+        }
+        
+        match &args[0] {
+            Value::Array(arr) => {
+                let result = arr.iter()
+                    .map(|v| self.builtin_sqrt(std::slice::from_ref(v)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(result))
+            }
+            Value::Null => Ok(Value::Null),
+            other => Ok(Value::Number(other.to_number()?.sqrt())),
+        }
+    }
+    
+    /// sign 函数 - 符号（-1 / 0 / 1），支持数组逐元素计算
+    fn builtin_sign(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("sign", "1", args.len()));
+        }
+        
+        broadcast_numeric("sign", args, |x| {
+            let sign = if x[0] > 0.0 { 1.0 } else if x[0] < 0.0 { -1.0 } else { 0.0 };
+            Ok(sign)
+        })
+    }
+    
+    /// clamp 函数 - 将值限制在 [lo, hi] 区间，支持数组逐元素计算
+    /// clamp(150, 0, 100) => 100
+    fn builtin_clamp(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::argument_mismatch("clamp", "3", args.len()));
+        }
+        
+        broadcast_numeric("clamp", args, |x| {
+            let (value, lo, hi) = (x[0], x[1], x[2]);
+            if lo > hi {
+                return Err(RuntimeError::type_error(&format!("clamp 的下界 {} 大于上界 {}", lo, hi)));
+            }
+            Ok(value.clamp(lo, hi))
+        })
+    }
+    
+    /// lerp 函数 - 线性插值 a + (b - a) * t，支持数组逐元素计算
+    /// lerp(0, 10, 0.25) => 2.5
+    fn builtin_lerp(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::argument_mismatch("lerp", "3", args.len()));
+        }
+        
+        broadcast_numeric("lerp", args, |x| Ok(x[0] + (x[1] - x[0]) * x[2]))
+    }
+    
+    /// round_to_tick 函数 - 按最小变动单位取整，使用配置的舍入方式（默认 half_even）
+    /// round_to_tick(10.017, 0.01) => 10.02；tick 必须为正，price 为 null 时返回 null
+    fn builtin_round_to_tick(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("round_to_tick", "2", args.len()));
+        }
+        if args[0].is_null() {
+            return Ok(Value::Null);
+        }
+        
+        let rounding = self.precision.as_ref().and_then(|p| p.rounding).unwrap_or_default();
+        args[0].round_to_tick(&args[1], rounding)
+    }
+    
+    /// approx_eq 函数 - 带容差的数值比较 |a - b| <= eps（默认 1e-9），支持数组逐元素计算
+    /// approx_eq(0.1 + 0.2, 0.3) => true；== 仍为精确比较
+    fn builtin_approx_eq(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() < 2 || args.len() > 3 {
+            return Err(RuntimeError::argument_mismatch("approx_eq", "2-3", args.len()));
+        }
+        
+        let eps = match args.get(2) {
+            Some(eps) => eps.to_number()?,
+            None => 1e-9,
+        };
+        if eps < 0.0 {
+            return Err(RuntimeError::type_error("approx_eq 的容差不能为负数"));
+        }
+        
+        broadcast_with("approx_eq", &args[..2], |x| Ok(Value::Bool((x[0] - x[1]).abs() <= eps)))
+    }
+    
     /// map 函数 - 数组映射
     fn builtin_map(&mut self, args: &[Value]) -> Result<Value, RuntimeError> {
         // map([1,2,3], x -> x * 2)
         if args.len() != 2 {
-            return Err(RuntimeError::type_error("map 需要 2 个参数"));
+            return Err(RuntimeError::argument_mismatch("map", "2", args.len()));
         }
         
         let arr = match &args[0] {
@@ -176,6 +340,7 @@ impl Executor {
         
         let mut result = Vec::new();
         for item in arr {
+            self.check_cancelled()?;
             let mapped = self.execute_lambda(
                 lambda.0.clone(),
                 lambda.1.clone(),
@@ -192,7 +357,7 @@ impl Executor {
     fn builtin_filter(&mut self, args: &[Value]) -> Result<Value, RuntimeError> {
         // filter([1,2,3,4], x -> x > 2)
         if args.len() != 2 {
-            return Err(RuntimeError::type_error("filter 需要 2 个参数"));
+            return Err(RuntimeError::argument_mismatch("filter", "2", args.len()));
         }
         
         let arr = match &args[0] {
@@ -207,6 +372,7 @@ impl Executor {
         
         let mut result = Vec::new();
         for item in arr {
+            self.check_cancelled()?;
             let keep = self.execute_lambda(
                 lambda.0.clone(),
                 lambda.1.clone(),
@@ -225,7 +391,7 @@ impl Executor {
     fn builtin_reduce(&mut self, args: &[Value]) -> Result<Value, RuntimeError> {
         // reduce([1,2,3,4], (acc, x) -> acc + x, 0)
         if args.len() < 2 || args.len() > 3 {
-            return Err(RuntimeError::type_error("reduce 需要 2-3 个参数"));
+            return Err(RuntimeError::argument_mismatch("reduce", "2-3", args.len()));
         }
         
         let arr = match &args[0] {
@@ -259,6 +425,7 @@ impl Executor {
         let start_idx = if args.len() == 3 { 0 } else { 1 };
         
         for item in &arr[start_idx..] {
+            self.check_cancelled()?;
             accumulator = self.execute_lambda(
                 lambda.0.clone(),
                 lambda.1.clone(),
@@ -270,6 +437,31 @@ impl Executor {
         Ok(accumulator)
     }
     
+    /// to_json 函数 - 序列化为 JSON 字符串
+    /// to_json([1, "a", null]) => "[1,\"a\",null]"
+    fn builtin_to_json(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("to_json", "1", args.len()));
+        }
+        
+        Ok(Value::String(args[0].to_json()?.to_string()))
+    }
+    
+    /// from_json 函数 - 解析 JSON 字符串
+    fn builtin_from_json(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("from_json", "1", args.len()));
+        }
+        
+        let text = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("from_json 的参数必须是字符串")),
+        };
+        let json = serde_json::from_str(text)
+            .map_err(|e| RuntimeError::type_error(&format!("JSON 解析错误: {}", e)))?;
+        Value::from_json(json)
+    }
+    
     /// print 函数 - 输出调试
     fn builtin_print(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         let output = args
@@ -277,14 +469,55 @@ impl Executor {
             .map(|v| format!("{:?}", v))
             .collect::<Vec<_>>()
             .join(" ");
-        println!("{}", output);
+        self.emit_print(&output);
         Ok(Value::Null)
     }
     
+    /// tap 函数 - 输出带标签的值并原样返回，用于查看管道的中间结果
+    /// [1, 2] |> map(x -> x * 2) |> tap("doubled") |> sum()  输出 "doubled: [2, 4]"
+    fn builtin_tap(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (value, label) = match args {
+            [value] => (value, None),
+            [value, label] => (value, Some(label)),
+            _ => return Err(RuntimeError::argument_mismatch("tap", "1 或 2", args.len())),
+        };
+        
+        match label {
+            Some(Value::String(label)) => self.emit_print(&format!("{}: {}", label, value)),
+            Some(label) => self.emit_print(&format!("{}: {}", label, value)),
+            None => self.emit_print(&value.to_string()),
+        }
+        Ok(value.clone())
+    }
+    
+    /// format_number 函数 - 按模式格式化数字为字符串
+    /// format_number(1234.5, "#,##0.00") => "1,234.50"；format_number(0.123, "0.0%") => "12.3%"
+    /// 模式中 , 表示千分位分组，小数点后 0 为固定位数、# 为可选位数，含 % 时先乘以 100，
+    /// 数字部分前后的其他字符作为前缀/后缀原样输出（如 "¥#,##0"）；null 返回 null
+    fn builtin_format_number(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("format_number", "2", args.len()));
+        }
+        if args[0].is_null() {
+            return Ok(Value::Null);
+        }
+        
+        let pattern = match &args[1] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("format_number 的格式必须是字符串")),
+        };
+        let value = match &args[0] {
+            Value::Number(_) | Value::Integer(_) | Value::Decimal(_) => args[0].to_decimal()?,
+            other => return Err(RuntimeError::type_error(&format!("format_number 需要数字，实际为: {}", other))),
+        };
+        
+        Ok(Value::String(format_with_pattern(value, pattern)?))
+    }
+    
     /// is_null 函数 - 检查值是否为null
     fn builtin_is_null(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("is_null 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("is_null", "1", args.len()));
         }
         
         Ok(Value::Bool(matches!(args[0], Value::Null)))
@@ -297,7 +530,7 @@ impl Executor {
     /// Range(0, 10, 2) => [0,2,4,6,8,10]
     fn builtin_range(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() < 2 || args.len() > 3 {
-            return Err(RuntimeError::type_error("Range 需要 2-3 个参数"));
+            return Err(RuntimeError::argument_mismatch("Range", "2-3", args.len()));
         }
         
         let start = args[0].to_number()?;
@@ -312,17 +545,25 @@ impl Executor {
             return Err(RuntimeError::type_error("Range 的步长不能为 0"));
         }
         
+        // 先按元素个数检查上限，避免生成过大的数组
+        let count = ((end - start) / step).floor() + 1.0;
+        self.limits.check_array_len("Range", if count > 0.0 { count as usize } else { 0 })?;
+        
+        // 参数都是整数时生成整数序列
+        let integral = args.iter().all(|a| matches!(a, Value::Integer(_)));
+        let make = |n: f64| if integral { Value::Integer(n as i64) } else { Value::Number(n) };
+        
         let mut result = Vec::new();
         let mut current = start;
         
         if step > 0.0 {
             while current <= end {
-                result.push(Value::Number(current));
+                result.push(make(current));
                 current += step;
             }
         } else {
             while current >= end {
-                result.push(Value::Number(current));
+                result.push(make(current));
                 current += step;
             }
         }
@@ -335,10 +576,11 @@ impl Executor {
     /// Array(10, i -> i * 2) => [0,2,4,6,8,10,12,14,16,18]
     fn builtin_array(&mut self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 2 {
-            return Err(RuntimeError::type_error("Array 需要 2 个参数"));
+            return Err(RuntimeError::argument_mismatch("Array", "2", args.len()));
         }
         
         let size = args[0].to_number()? as usize;
+        self.limits.check_array_len("Array", size)?;
         
         match &args[1] {
             Value::Lambda { params, body, captures } => {
@@ -353,7 +595,7 @@ impl Executor {
                         params.clone(),
                         body.clone(),
                         captures.clone(),
-                        &[Value::Number(i as f64)],
+                        &[Value::Integer(i as i64)],
                     )?;
                     result.push(value);
                 }
@@ -371,7 +613,16 @@ impl Executor {
     /// mean 函数 - 计算平均值
     fn builtin_mean(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.is_empty() {
-            return Err(RuntimeError::type_error("mean 需要至少一个参数"));
+            return Err(RuntimeError::argument_mismatch("mean", "至少 1", args.len()));
+        }
+        
+        if let Some(decimals) = all_decimals(aggregate_values(args)) {
+            let count = Decimal::from(decimals.len());
+            return decimals.into_iter()
+                .try_fold(Decimal::ZERO, |total, d| total.checked_add(d))
+                .and_then(|total| total.checked_div(count))
+                .map(Value::Decimal)
+                .ok_or_else(|| RuntimeError::type_error("mean 的 Decimal 结果溢出"));
         }
         
         let values: Vec<f64> = if let Value::Array(arr) = &args[0] {
@@ -397,7 +648,7 @@ impl Executor {
     /// first 函数 - 获取数组第一个元素
     fn builtin_first(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("first 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("first", "1", args.len()));
         }
         
         match &args[0] {
@@ -415,7 +666,7 @@ impl Executor {
     /// last 函数 - 获取数组最后一个元素
     fn builtin_last(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("last 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("last", "1", args.len()));
         }
         
         match &args[0] {
@@ -433,7 +684,7 @@ impl Executor {
     /// sort 函数 - 对数组排序
     fn builtin_sort(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("sort 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("sort", "1", args.len()));
         }
         
         match &args[0] {
@@ -441,8 +692,11 @@ impl Executor {
                 let mut sorted = arr.clone();
                 sorted.sort_by(|a, b| {
                     match (a, b) {
-                        (Value::Number(x), Value::Number(y)) => {
-                            x.partial_cmp(y).unwrap_or(std::cmp::Ordering::Equal)
+                        (Value::Integer(x), Value::Integer(y)) => x.cmp(y),
+                        (Value::Number(_) | Value::Integer(_), Value::Number(_) | Value::Integer(_)) => {
+                            let x = a.to_number().unwrap_or(f64::NAN);
+                            let y = b.to_number().unwrap_or(f64::NAN);
+                            x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
                         }
                         (Value::String(x), Value::String(y)) => x.cmp(y),
                         (Value::Null, Value::Null) => std::cmp::Ordering::Equal,
@@ -460,7 +714,7 @@ impl Executor {
     /// unique 函数 - 数组去重
     fn builtin_unique(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("unique 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("unique", "1", args.len()));
         }
         
         match &args[0] {
@@ -480,7 +734,7 @@ impl Executor {
     /// reverse 函数 - 反转数组
     fn builtin_reverse(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() != 1 {
-            return Err(RuntimeError::type_error("reverse 需要 1 个参数"));
+            return Err(RuntimeError::argument_mismatch("reverse", "1", args.len()));
         }
         
         match &args[0] {
@@ -493,13 +747,591 @@ impl Executor {
         }
     }
     
+    /// mask 函数 - 按布尔掩码选取元素
+    /// mask(prices, prices > 100) => prices 中大于 100 的元素
+    fn builtin_mask(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("mask", "2", args.len()));
+        }
+        
+        let (arr, flags) = match (&args[0], &args[1]) {
+            (Value::Array(a), Value::Array(m)) => (a, m),
+            _ => return Err(RuntimeError::type_error("mask 的参数必须是数组")),
+        };
+        
+        if arr.len() != flags.len() {
+            return Err(RuntimeError::type_error(&format!(
+                "mask 数组长度不匹配: {} 与 {}",
+                arr.len(),
+                flags.len()
+            )));
+        }
+        
+        let result = arr.iter()
+            .zip(flags.iter())
+            .filter(|(_, flag)| flag.to_bool())
+            .map(|(item, _)| item.clone())
+            .collect();
+        
+        Ok(Value::Array(result))
+    }
+    
+    /// chunk 函数 - 按固定大小分组（不重叠，最后一组可能不足）
+    /// chunk([1, 2, 3, 4, 5], 2) => [[1, 2], [3, 4], [5]]
+    fn builtin_chunk(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (arr, n) = self.array_and_size("chunk", args)?;
+        let result = arr.chunks(n)
+            .map(|c| Value::Array(c.to_vec()))
+            .collect();
+        Ok(Value::Array(result))
+    }
+    
+    /// windows 函数 - 所有长度为 n 的连续切片
+    /// windows([1, 2, 3], 2) => [[1, 2], [2, 3]]
+    fn builtin_windows(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (arr, n) = self.array_and_size("windows", args)?;
+        let total = arr.len().saturating_sub(n - 1).saturating_mul(n);
+        self.limits.check_array_len("windows", total)?;
+        let result = arr.windows(n)
+            .map(|w| Value::Array(w.to_vec()))
+            .collect();
+        Ok(Value::Array(result))
+    }
+    
+    /// head 函数 - 前 n 个元素（不足 n 个时返回全部）
+    /// head([1, 2, 3], 2) => [1, 2]
+    fn builtin_head(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (arr, n) = self.array_and_count("head", args)?;
+        Ok(Value::Array(arr.iter().take(n).cloned().collect()))
+    }
+    
+    /// tail 函数 - 后 n 个元素（不足 n 个时返回全部）
+    /// tail([1, 2, 3], 2) => [2, 3]
+    fn builtin_tail(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let (arr, n) = self.array_and_count("tail", args)?;
+        Ok(Value::Array(arr[arr.len().saturating_sub(n)..].to_vec()))
+    }
+    
+    /// 解析 (数组, 非负整数个数) 参数
+    fn array_and_count<'a>(&self, name: &str, args: &'a [Value]) -> Result<(&'a [Value], usize), RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch(name, "2", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error(&format!("{} 的第一个参数必须是数组", name))),
+        };
+        
+        let n = args[1].to_number()?;
+        if n < 0.0 || n.fract() != 0.0 {
+            return Err(RuntimeError::type_error(&format!("{} 的个数必须为非负整数", name)));
+        }
+        
+        Ok((arr, n as usize))
+    }
+    
+    /// 解析 (数组, 正整数大小) 参数
+    fn array_and_size<'a>(&self, name: &str, args: &'a [Value]) -> Result<(&'a [Value], usize), RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch(name, "2", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error(&format!("{} 的第一个参数必须是数组", name))),
+        };
+        
+        let n = args[1].to_number()?;
+        if n < 1.0 {
+            return Err(RuntimeError::type_error(&format!("{} 的大小必须为正整数", name)));
+        }
+        
+        Ok((arr, n as usize))
+    }
+    
+    // ==================== 缺失值处理函数 ====================
+    
+    /// fill_null 函数 - 用常量替换 null
+    /// fill_null([1, null, 3], 0) => [1, 0, 3]
+    fn builtin_fill_null(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("fill_null", "2", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error("fill_null 的第一个参数必须是数组")),
+        };
+        
+        let result = arr.iter()
+            .map(|v| if v.is_null() { args[1].clone() } else { v.clone() })
+            .collect();
+        
+        Ok(Value::Array(result))
+    }
+    
+    /// ffill 函数 - 用前一个非 null 值向前填充
+    /// ffill([null, 1, null, 4]) => [null, 1, 1, 4]（开头没有前值的 null 保持不变）
+    fn builtin_ffill(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("ffill", "1", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error("ffill 的参数必须是数组")),
+        };
+        
+        let mut last = Value::Null;
+        let result = arr.iter()
+            .map(|v| {
+                if !v.is_null() {
+                    last = v.clone();
+                }
+                last.clone()
+            })
+            .collect();
+        
+        Ok(Value::Array(result))
+    }
+    
+    /// cummax / cummin 函数 - 累计最大值 / 最小值
+    /// cummax([1, 3, 2, 5]) => [1, 3, 3, 5]；null 保持为 null 且不参与比较
+    fn builtin_running_extreme(
+        &self,
+        name: &str,
+        args: &[Value],
+        replaces: fn(f64, f64) -> bool,
+    ) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch(name, "1", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error(&format!("{} 的参数必须是数组", name))),
+        };
+        
+        let mut best: Option<(f64, &Value)> = None;
+        let mut result = Vec::with_capacity(arr.len());
+        for v in arr {
+            if v.is_null() {
+                result.push(Value::Null);
+                continue;
+            }
+            let n = v.to_number()?;
+            match best {
+                Some((current, _)) if !replaces(n, current) => {}
+                _ => best = Some((n, v)),
+            }
+            result.push(best.map_or(Value::Null, |(_, value)| value.clone()));
+        }
+        
+        Ok(Value::Array(result))
+    }
+    
+    /// interpolate 函数 - 在前后数值之间线性插值
+    /// interpolate([1, null, null, 4]) => [1, 2, 3, 4]（首尾缺少邻值的 null 保持不变）
+    fn builtin_interpolate(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("interpolate", "1", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(a) => a,
+            _ => return Err(RuntimeError::type_error("interpolate 的参数必须是数组")),
+        };
+        
+        let mut result = arr.clone();
+        let mut prev: Option<(usize, f64)> = None;
+        
+        for (i, v) in arr.iter().enumerate() {
+            if v.is_null() {
+                continue;
+            }
+            
+            let current = v.to_number()?;
+            if let Some((prev_idx, prev_val)) = prev {
+                let gap = (i - prev_idx) as f64;
+                for (j, slot) in result.iter_mut().enumerate().take(i).skip(prev_idx + 1) {
+                    let ratio = (j - prev_idx) as f64 / gap;
+                    *slot = Value::Number(prev_val + (current - prev_val) * ratio);
+                }
+            }
+            prev = Some((i, current));
+        }
+        
+        Ok(Value::Array(result))
+    }
+    
+    // ==================== 收益率函数 ====================
+    
+    /// pct_change 函数 - 数据流中相对 n 行前的变化率（默认 n = 1）
+    /// pct_change("close") => (close - close[-1]) / close[-1]，历史不足或前值为 0 时返回 null
+    fn builtin_pct_change(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(RuntimeError::argument_mismatch("pct_change", "1-2", args.len()));
+        }
+        
+        let name = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("pct_change 的第一个参数必须是列名字符串")),
+        };
+        let periods = if args.len() == 2 {
+            let n = args[1].to_number()?;
+            if n < 1.0 || n.fract() != 0.0 {
+                return Err(RuntimeError::type_error("pct_change 的周期必须是正整数"));
+            }
+            n as usize
+        } else {
+            1
+        };
+        
+        let current = self.get_time_series_value(name, 0);
+        let previous = self.get_time_series_value(name, periods);
+        match (current, previous) {
+            (Some(cur), Some(prev)) => simple_return(&prev, &cur),
+            _ => Ok(Value::Null),
+        }
+    }
+    
+    /// forward 函数 - 数据流中 n 行之后的输入值（默认 n = 1），超出末尾时返回 null
+    /// 非因果：会读取未来数据，只用于在历史数据上生成标签，如 forward("close") / close - 1
+    /// 仅批量执行支持，流式执行中报错
+    fn builtin_forward(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.is_empty() || args.len() > 2 {
+            return Err(RuntimeError::argument_mismatch("forward", "1-2", args.len()));
+        }
+        
+        let name = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("forward 的第一个参数必须是列名字符串")),
+        };
+        let periods = if args.len() == 2 {
+            let n = args[1].to_number()?;
+            if n < 1.0 || n.fract() != 0.0 {
+                return Err(RuntimeError::type_error("forward 的行数必须为正整数"));
+            }
+            n as usize
+        } else {
+            1
+        };
+        
+        self.get_forward_value(name, periods)
+    }
+    
+    /// returns 函数 - 价格数组的简单收益率数组
+    /// returns([10, 11, 0, 5]) => [null, 0.1, -1, null]（首个元素及前值为 0 时为 null）
+    fn builtin_returns(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("returns", "1", args.len()));
+        }
+        
+        let prices = match &args[0] {
+            Value::Array(arr) => arr,
+            _ => return Err(RuntimeError::type_error("returns 的参数必须是数组")),
+        };
+        
+        let mut result = Vec::with_capacity(prices.len());
+        if !prices.is_empty() {
+            result.push(Value::Null);
+        }
+        for pair in prices.windows(2) {
+            result.push(simple_return(&pair[0], &pair[1])?);
+        }
+        
+        Ok(Value::Array(result))
+    }
+    
+    /// roc / mom 函数 - 相对 n 行前的变化率（百分比）/ 绝对变化
+    /// roc("close", 10) => (close - close[-10]) / close[-10] * 100；mom("close", 10) => close - close[-10]
+    /// 第一个参数为数组时逐元素计算：mom([1, 3, 6], 1) => [null, 2, 3]
+    /// 历史不足 n 行时为 null，roc 的基准为 0 时也为 null
+    fn builtin_change(
+        &self,
+        name: &str,
+        args: &[Value],
+        change: fn(f64, f64) -> Option<f64>,
+    ) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch(name, "2", args.len()));
+        }
+        
+        let periods = args[1].to_number()?;
+        if periods < 1.0 || periods.fract() != 0.0 {
+            return Err(RuntimeError::type_error(&format!("{} 的周期必须是正整数", name)));
+        }
+        let periods = periods as usize;
+        
+        let apply = |prev: &Value, cur: &Value| -> Result<Value, RuntimeError> {
+            if prev.is_null() || cur.is_null() {
+                return Ok(Value::Null);
+            }
+            Ok(change(prev.to_number()?, cur.to_number()?).map_or(Value::Null, Value::Number))
+        };
+        
+        match &args[0] {
+            Value::String(column) => {
+                let current = self.get_time_series_value(column, 0);
+                let previous = self.get_time_series_value(column, periods);
+                match (current, previous) {
+                    (Some(cur), Some(prev)) => apply(&prev, &cur),
+                    _ => Ok(Value::Null),
+                }
+            }
+            Value::Array(arr) => {
+                let result = (0..arr.len())
+                    .map(|i| if i < periods { Ok(Value::Null) } else { apply(&arr[i - periods], &arr[i]) })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(result))
+            }
+            _ => Err(RuntimeError::type_error(&format!("{} 的第一个参数必须是列名字符串或数组", name))),
+        }
+    }
+    
+    /// drawdown 函数 - 净值序列相对历史最高点的回撤 value / peak - 1（不大于 0）
+    /// drawdown([1, 2, 1.5, 3]) => [0, 0, -0.25, 0]；null 保持为 null 且不更新最高点，最高点不为正时为 null
+    fn builtin_drawdown(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("drawdown", "1", args.len()));
+        }
+        
+        let equity = match &args[0] {
+            Value::Array(arr) => arr,
+            _ => return Err(RuntimeError::type_error("drawdown 的参数必须是数组")),
+        };
+        
+        let mut peak = f64::NEG_INFINITY;
+        let mut result = Vec::with_capacity(equity.len());
+        for v in equity {
+            if v.is_null() {
+                result.push(Value::Null);
+                continue;
+            }
+            let value = v.to_number()?;
+            peak = peak.max(value);
+            result.push(if peak > 0.0 { Value::Number(value / peak - 1.0) } else { Value::Null });
+        }
+        
+        Ok(Value::Array(result))
+    }
+    
+    // ==================== 分位数函数 ====================
+    
+    /// percentile 函数 - 数组的百分位数（线性插值，跳过 null）
+    /// percentile([1, 2, 3, 4, 5], 95) => 4.8
+    fn builtin_percentile(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("percentile", "2", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(arr) => arr,
+            _ => return Err(RuntimeError::type_error("percentile 的第一个参数必须是数组")),
+        };
+        let p = args[1].to_number()?;
+        if !(0.0..=100.0).contains(&p) {
+            return Err(RuntimeError::type_error("percentile 的百分位必须在 0 到 100 之间"));
+        }
+        
+        let mut values = numeric_values(arr)?;
+        if values.is_empty() {
+            return Ok(Value::Null);
+        }
+        Ok(Value::Number(quantile_of(&mut values, p / 100.0)))
+    }
+    
+    /// quantile 函数 - 时间序列最近 window 行（含当前行）的分位数，q 取 0 到 1
+    /// quantile("close", 20, 0.95)；有效值少于 2 个时返回 null
+    /// 每行用快速选择求顺序统计量，复杂度 O(window)
+    fn builtin_quantile(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::argument_mismatch("quantile", "3", args.len()));
+        }
+        
+        let name = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("quantile 的第一个参数必须是列名字符串")),
+        };
+        let window = args[1].to_number()?;
+        if window < 1.0 {
+            return Err(RuntimeError::type_error("quantile 的窗口长度必须至少为 1"));
+        }
+        let q = args[2].to_number()?;
+        if !(0.0..=1.0).contains(&q) {
+            return Err(RuntimeError::type_error("quantile 的分位必须在 0 到 1 之间"));
+        }
+        
+        let start = -(window as isize - 1);
+        let history = match self.get_time_series_slice(name, start, 0)? {
+            Value::Array(arr) => arr,
+            _ => return Ok(Value::Null),
+        };
+        
+        let mut values = numeric_values(&history)?;
+        if values.len() < 2 {
+            return Ok(Value::Null);
+        }
+        Ok(Value::Number(quantile_of(&mut values, q)))
+    }
+    
+    /// histogram 函数 - 分桶统计，返回 [[下界, 上界, 个数], ...]（跳过 null）
+    /// histogram(arr, 4) 在 min..max 上等宽分 4 桶；histogram(arr, [0, 10, 50]) 使用自定义边界
+    /// 区间为左闭右开，最后一个桶包含上界；自定义边界之外的值不计数
+    fn builtin_histogram(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 2 {
+            return Err(RuntimeError::argument_mismatch("histogram", "2", args.len()));
+        }
+        
+        let arr = match &args[0] {
+            Value::Array(arr) => arr,
+            _ => return Err(RuntimeError::type_error("histogram 的第一个参数必须是数组")),
+        };
+        let values = numeric_values(arr)?;
+        
+        let edges = match &args[1] {
+            Value::Array(edges) => {
+                let edges = numeric_values(edges)?;
+                if edges.len() < 2 {
+                    return Err(RuntimeError::type_error("histogram 的边界至少需要 2 个"));
+                }
+                if edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+                    return Err(RuntimeError::type_error("histogram 的边界必须严格递增"));
+                }
+                edges
+            }
+            bins => {
+                let bins = bins.to_number()?;
+                if bins < 1.0 || bins.fract() != 0.0 {
+                    return Err(RuntimeError::type_error("histogram 的桶数必须是正整数"));
+                }
+                if values.is_empty() {
+                    return Ok(Value::Array(vec![]));
+                }
+                let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+                let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let width = (max - min) / bins;
+                (0..=bins as usize)
+                    .map(|i| if i == bins as usize { max } else { min + width * i as f64 })
+                    .collect()
+            }
+        };
+        if values.is_empty() {
+            return Ok(Value::Array(vec![]));
+        }
+        
+        let last = edges.len() - 2;
+        let mut counts = vec![0i64; last + 1];
+        for v in values {
+            if v < edges[0] || v > edges[last + 1] {
+                continue;
+            }
+            // 第一个上界大于 v 的桶；等于最大边界时归入最后一个桶
+            let bin = edges[1..].partition_point(|&hi| hi <= v).min(last);
+            counts[bin] += 1;
+        }
+        
+        let result = counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| Value::Array(vec![
+                Value::Number(edges[i]),
+                Value::Number(edges[i + 1]),
+                Value::Integer(count),
+            ]))
+            .collect();
+        Ok(Value::Array(result))
+    }
+    
+    // ==================== 外部数据函数 ====================
+    
+    /// load_csv 函数 - 读取外部 CSV 参考表
+    /// load_csv("sectors.csv") => [[表头...], [行1...], [行2...], ...]
+    /// 同一路径在执行器生命周期内只读取一次
+    fn builtin_load_csv(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 1 {
+            return Err(RuntimeError::argument_mismatch("load_csv", "1", args.len()));
+        }
+        
+        let path = match &args[0] {
+            Value::String(s) => s,
+            _ => return Err(RuntimeError::type_error("load_csv 的参数必须是文件路径字符串")),
+        };
+        
+        if !self.file_access {
+            return Err(RuntimeError {
+                message: "load_csv 不可用：当前执行器禁止访问文件".to_string(),
+                ..RuntimeError::permission_denied("load_csv")
+            });
+        }
+        
+        if let Some(table) = self.table_cache.borrow().get(path) {
+            return Ok(table.clone());
+        }
+        
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| RuntimeError::type_error(&format!("无法读取 CSV 文件 {}: {}", path, e)))?;
+        
+        let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+        let header: Vec<Value> = match lines.next() {
+            Some(line) => split_csv_line(line).into_iter().map(Value::String).collect(),
+            None => return Err(RuntimeError::type_error(&format!("CSV 文件为空: {}", path))),
+        };
+        
+        let mut table = vec![Value::Array(header)];
+        for line in lines {
+            let row = split_csv_line(line).iter().map(|s| parse_csv_value(s)).collect();
+            table.push(Value::Array(row));
+        }
+        
+        let table = Value::Array(table);
+        self.table_cache.borrow_mut().insert(path.clone(), table.clone());
+        Ok(table)
+    }
+    
+    /// lookup 函数 - 在参考表中按键列查找值
+    /// lookup(table, "code", 600000, "sector") => 第一个 code == 600000 的行的 sector，找不到返回 null
+    fn builtin_lookup(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 4 {
+            return Err(RuntimeError::argument_mismatch("lookup", "4", args.len()));
+        }
+        
+        let (header, rows) = match &args[0] {
+            Value::Array(table) => match table.split_first() {
+                Some((Value::Array(header), rows)) => (header, rows),
+                _ => return Err(RuntimeError::type_error("lookup 的第一个参数必须是 load_csv 返回的表")),
+            },
+            _ => return Err(RuntimeError::type_error("lookup 的第一个参数必须是 load_csv 返回的表")),
+        };
+        
+        let column_index = |column: &Value| -> Result<usize, RuntimeError> {
+            header.iter()
+                .position(|h| h == column)
+                .ok_or_else(|| RuntimeError::type_error(&format!("lookup 找不到列: {:?}", column)))
+        };
+        let key_index = column_index(&args[1])?;
+        let value_index = column_index(&args[3])?;
+        let key = &args[2];
+        
+        for row in rows {
+            if let Value::Array(cells) = row {
+                if cells.get(key_index) == Some(key) {
+                    return Ok(cells.get(value_index).cloned().unwrap_or(Value::Null));
+                }
+            }
+        }
+        
+        Ok(Value::Null)
+    }
+    
     // ==================== 安全函数 ====================
     
     /// safe_div 函数 - 安全除法（避免除零错误）
     /// safe_div(a, b, default=0.0)
     fn builtin_safe_div(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() < 2 || args.len() > 3 {
-            return Err(RuntimeError::type_error("safe_div 需要 2-3 个参数"));
+            return Err(RuntimeError::argument_mismatch("safe_div", "2-3", args.len()));
         }
         
         let a = args[0].to_number()?;
@@ -521,7 +1353,7 @@ impl Executor {
     /// safe_get(array, index, default=null)
     fn builtin_safe_get(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() < 2 || args.len() > 3 {
-            return Err(RuntimeError::type_error("safe_get 需要 2-3 个参数"));
+            return Err(RuntimeError::argument_mismatch("safe_get", "2-3", args.len()));
         }
         
         let arr = match &args[0] {
@@ -558,7 +1390,7 @@ impl Executor {
     /// safe_number(value, default=0)
     fn builtin_safe_number(&self, args: &[Value]) -> Result<Value, RuntimeError> {
         if args.len() < 1 || args.len() > 2 {
-            return Err(RuntimeError::type_error("safe_number 需要 1-2 个参数"));
+            return Err(RuntimeError::argument_mismatch("safe_number", "1-2", args.len()));
         }
         
         let default = if args.len() == 2 {
@@ -573,10 +1405,68 @@ impl Executor {
         }
     }
     
+    // ==================== 数据校验函数 ====================
+    
+    /// validate_ohlc 函数 - 检查 K 线是否合理，支持数组逐元素计算
+    /// validate_ohlc(open, high, low, close[, volume])：价格为正、high >= low、
+    /// open/close 落在 [low, high] 内、成交量非负时为 true；任一值为 null 时为 false
+    fn builtin_validate_ohlc(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() < 4 || args.len() > 5 {
+            return Err(RuntimeError::argument_mismatch("validate_ohlc", "4-5", args.len()));
+        }
+        
+        let valid = broadcast_with("validate_ohlc", args, |x| Ok(Value::Bool(is_valid_ohlc(x))))?;
+        // 缺失数据视为不合理
+        let null_to_false = |v: Value| if v.is_null() { Value::Bool(false) } else { v };
+        Ok(match valid {
+            Value::Array(arr) => Value::Array(arr.into_iter().map(null_to_false).collect()),
+            scalar => null_to_false(scalar),
+        })
+    }
+    
+    /// clean_ohlc 函数 - 不合理的 K 线置为 null（判断规则同 validate_ohlc）
+    /// [o, h, l, c] = clean_ohlc(open, high, low, close)，传入数组时逐元素清洗
+    fn builtin_clean_ohlc(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        let valid = self.builtin_validate_ohlc(args)?;
+        
+        let cleaned = args.iter().map(|arg| match (&valid, arg) {
+            (Value::Array(mask), Value::Array(values)) => Value::Array(mask.iter()
+                .zip(values)
+                .map(|(ok, v)| if matches!(ok, Value::Bool(true)) { v.clone() } else { Value::Null })
+                .collect()),
+            // 标量与数组混合时标量逐元素复用，清洗后展开为数组
+            (Value::Array(mask), scalar) => Value::Array(mask.iter()
+                .map(|ok| if matches!(ok, Value::Bool(true)) { scalar.clone() } else { Value::Null })
+                .collect()),
+            (Value::Bool(true), v) => v.clone(),
+            _ => Value::Null,
+        }).collect();
+        
+        Ok(Value::Array(cleaned))
+    }
+    
+    /// in_range 函数 - 判断 lo <= x <= hi，支持数组逐元素计算
+    /// in_range(5, 0, 10) => true；x 为 null 时返回 null
+    fn builtin_in_range(&self, args: &[Value]) -> Result<Value, RuntimeError> {
+        if args.len() != 3 {
+            return Err(RuntimeError::argument_mismatch("in_range", "3", args.len()));
+        }
+        
+        broadcast_with("in_range", args, |x| {
+            let (value, lo, hi) = (x[0], x[1], x[2]);
+            if lo > hi {
+                return Err(RuntimeError::type_error(&format!("in_range 的下界 {} 大于上界 {}", lo, hi)));
+            }
+            Ok(Value::Bool(lo <= value && value <= hi))
+        })
+    }
+    
     /// 辅助函数 - 判断两个值是否相等
     fn values_equal(&self, a: &Value, b: &Value) -> bool {
         match (a, b) {
             (Value::Number(x), Value::Number(y)) => (x - y).abs() < f64::EPSILON,
+            (Value::Integer(x), Value::Integer(y)) => x == y,
+            (Value::Integer(x), Value::Number(y)) | (Value::Number(y), Value::Integer(x)) => (*x as f64 - y).abs() < f64::EPSILON,
             (Value::String(x), Value::String(y)) => x == y,
             (Value::Bool(x), Value::Bool(y)) => x == y,
             (Value::Null, Value::Null) => true,
@@ -587,3 +1477,188 @@ impl Executor {
         }
     }
 }
+
+/// 简单收益率 (cur - prev) / prev，任一值为 null 或前值为 0 时返回 null
+fn simple_return(prev: &Value, cur: &Value) -> Result<Value, RuntimeError> {
+    if prev.is_null() || cur.is_null() {
+        return Ok(Value::Null);
+    }
+    
+    let prev = prev.to_number()?;
+    if prev == 0.0 {
+        return Ok(Value::Null);
+    }
+    
+    Ok(Value::Number((cur.to_number()? - prev) / prev))
+}
+
+/// 变化率（百分比），基准为 0 时无意义
+fn rate_of_change(prev: f64, cur: f64) -> Option<f64> {
+    if prev == 0.0 {
+        return None;
+    }
+    Some((cur - prev) / prev * 100.0)
+}
+
+/// 绝对变化
+fn momentum(prev: f64, cur: f64) -> Option<f64> {
+    Some(cur - prev)
+}
+
+/// K 线合理性检查：[open, high, low, close] 或附带 volume
+fn is_valid_ohlc(x: &[f64]) -> bool {
+    let (open, high, low, close) = (x[0], x[1], x[2], x[3]);
+    let volume_ok = x.get(4).is_none_or(|&volume| volume >= 0.0);
+    [open, high, low, close].iter().all(|&p| p > 0.0)
+        && high >= low
+        && (low..=high).contains(&open)
+        && (low..=high).contains(&close)
+        && volume_ok
+}
+
+/// 数值函数的数组广播：任一参数为数组时逐元素计算（数组长度必须一致），
+/// 标量参数复用到每个元素；任一元素为 null 时结果为 null
+fn broadcast_numeric<F>(name: &str, args: &[Value], f: F) -> Result<Value, RuntimeError>
+where
+    F: Fn(&[f64]) -> Result<f64, RuntimeError>,
+{
+    broadcast_with(name, args, |x| f(x).map(Value::Number))
+}
+
+/// 同 broadcast_numeric，但逐元素结果可以是任意值（如 bool）
+fn broadcast_with<F>(name: &str, args: &[Value], f: F) -> Result<Value, RuntimeError>
+where
+    F: Fn(&[f64]) -> Result<Value, RuntimeError>,
+{
+    let mut len = None;
+    for arg in args {
+        if let Value::Array(arr) = arg {
+            match len {
+                Some(n) if n != arr.len() => {
+                    return Err(RuntimeError::type_error(&format!(
+                        "{} 数组长度不匹配: {} 与 {}",
+                        name,
+                        n,
+                        arr.len()
+                    )));
+                }
+                _ => len = Some(arr.len()),
+            }
+        }
+    }
+    
+    let apply = |values: Vec<&Value>| -> Result<Value, RuntimeError> {
+        if values.iter().any(|v| v.is_null()) {
+            return Ok(Value::Null);
+        }
+        let numbers = values.iter().map(|v| v.to_number()).collect::<Result<Vec<_>, _>>()?;
+        f(&numbers)
+    };
+    
+    match len {
+        None => apply(args.iter().collect()),
+        Some(n) => {
+            let result = (0..n)
+                .map(|i| apply(args.iter()
+                    .map(|arg| match arg {
+                        Value::Array(arr) => &arr[i],
+                        scalar => scalar,
+                    })
+                    .collect()))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Value::Array(result))
+        }
+    }
+}
+
+/// 聚合函数的参数：传入单个数组时取数组元素，否则为可变参数本身
+fn aggregate_values(args: &[Value]) -> &[Value] {
+    match args {
+        [Value::Array(arr), ..] => arr,
+        _ => args,
+    }
+}
+
+/// 非 null 元素全部为 Decimal 时返回它们（至少一个），混合类型时返回 None 以回退到 f64
+fn all_decimals(values: &[Value]) -> Option<Vec<Decimal>> {
+    let decimals = values.iter()
+        .filter(|v| !v.is_null())
+        .map(|v| match v {
+            Value::Decimal(d) => Some(*d),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (!decimals.is_empty()).then_some(decimals)
+}
+
+/// 按 format_number 的模式格式化，舍入方式为四舍五入（远离零）
+fn format_with_pattern(value: Decimal, pattern: &str) -> Result<String, RuntimeError> {
+    let start = pattern.find(['#', '0', '.']);
+    let end = pattern.rfind(['#', '0']);
+    let (prefix, spec, suffix) = match (start, end) {
+        (Some(start), Some(end)) if start <= end => (&pattern[..start], &pattern[start..=end], &pattern[end + 1..]),
+        _ => return Err(RuntimeError::type_error(&format!("format_number 的格式中缺少数字占位符: {}", pattern))),
+    };
+    
+    let (int_spec, frac_spec) = spec.split_once('.').unwrap_or((spec, ""));
+    let grouping = int_spec.contains(',');
+    let min_int = int_spec.chars().filter(|&c| c == '0').count();
+    let min_frac = frac_spec.chars().filter(|&c| c == '0').count();
+    let max_frac = frac_spec.chars().filter(|&c| c == '0' || c == '#').count();
+    
+    let percent = prefix.contains('%') || suffix.contains('%');
+    let scaled = if percent { value * Decimal::ONE_HUNDRED } else { value };
+    let rounded = scaled.round_dp_with_strategy(max_frac as u32, RoundingStrategy::MidpointAwayFromZero);
+    
+    let digits = format!("{:.*}", max_frac, rounded.abs());
+    let (int_digits, frac_digits) = digits.split_once('.').unwrap_or((&digits, ""));
+    let int_digits = int_digits.trim_start_matches('0');
+    let int_digits = format!("{:0>width$}", int_digits, width = min_int);
+    let mut frac_digits = frac_digits.to_string();
+    while frac_digits.len() > min_frac && frac_digits.ends_with('0') {
+        frac_digits.pop();
+    }
+    
+    let mut formatted = String::new();
+    if rounded.is_sign_negative() && !rounded.is_zero() {
+        formatted.push('-');
+    }
+    formatted.push_str(prefix);
+    for (i, c) in int_digits.chars().enumerate() {
+        if grouping && i > 0 && (int_digits.len() - i) % 3 == 0 {
+            formatted.push(',');
+        }
+        formatted.push(c);
+    }
+    if !frac_digits.is_empty() {
+        formatted.push('.');
+        formatted.push_str(&frac_digits);
+    }
+    formatted.push_str(suffix);
+    Ok(formatted)
+}
+
+/// 提取数组中的数值，跳过 null
+fn numeric_values(arr: &[Value]) -> Result<Vec<f64>, RuntimeError> {
+    arr.iter()
+        .filter(|v| !v.is_null())
+        .map(|v| v.to_number())
+        .collect()
+}
+
+/// 分位数（线性插值），q 取 0 到 1，values 不能为空
+/// 用快速选择代替排序：先选出下侧顺序统计量，上侧取其后分区的最小值
+fn quantile_of(values: &mut [f64], q: f64) -> f64 {
+    let pos = q * (values.len() - 1) as f64;
+    let lo = pos.floor() as usize;
+    let frac = pos - lo as f64;
+    
+    let (_, lower, upper) = values.select_nth_unstable_by(lo, |a, b| a.total_cmp(b));
+    let lower = *lower;
+    if frac == 0.0 || upper.is_empty() {
+        return lower;
+    }
+    
+    let next = upper.iter().copied().fold(f64::INFINITY, f64::min);
+    lower + frac * (next - lower)
+}
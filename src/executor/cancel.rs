@@ -0,0 +1,69 @@
+// 取消令牌 - 嵌入场景下中止失控脚本（死循环、深递归等）
+
+use crate::runtime::RuntimeError;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// 取消令牌：外部置位标志或超过截止时间后，执行在下一个检查点中止
+///
+/// 检查点为语句边界和 map/filter/reduce 的每次迭代。
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    /// 取消标志（可由其他线程置位）
+    flag: Arc<AtomicBool>,
+    /// 截止时间
+    deadline: Option<Instant>,
+}
+
+impl CancelToken {
+    /// 创建令牌
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    /// 使用已有的取消标志创建令牌
+    pub fn from_flag(flag: Arc<AtomicBool>) -> Self {
+        CancelToken { flag, deadline: None }
+    }
+
+    /// 设置超时时间（从现在开始计算）
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(Instant::now() + timeout);
+        self
+    }
+
+    /// 设置截止时间
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// 请求取消
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// 取消标志（可交给其他线程）
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.flag)
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// 检查点：已取消或已超时则返回错误
+    pub fn check(&self) -> Result<(), RuntimeError> {
+        if self.is_cancelled() {
+            return Err(RuntimeError::cancelled("执行已取消"));
+        }
+        if let Some(deadline) = self.deadline {
+            if Instant::now() >= deadline {
+                return Err(RuntimeError::cancelled("执行超时"));
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 // 列式数据存储 - 优化HashMap查找性能
 
-use crate::runtime::Value;
+use crate::runtime::{Value, RuntimeError};
 use std::collections::HashMap;
 use std::rc::Rc;
 
@@ -64,6 +64,23 @@ impl ColumnarStorage {
         }
     }
 
+    /// 从按列累积的数据创建列式存储（各列长度应相同）
+    pub fn from_columns(columns: Vec<(String, Vec<Value>)>) -> Self {
+        let row_count = columns.first().map_or(0, |(_, values)| values.len());
+        let mut column_index = HashMap::new();
+        let mut rc_columns = Vec::with_capacity(columns.len());
+        for (name, values) in columns {
+            column_index.insert(name, rc_columns.len());
+            rc_columns.push(Rc::new(values));
+        }
+
+        ColumnarStorage {
+            column_index,
+            columns: rc_columns,
+            row_count,
+        }
+    }
+
     /// 获取指定列的值（单个值）
     pub fn get_value(&self, col_name: &str, row_idx: usize) -> Option<Value> {
         let col_idx = self.column_index.get(col_name)?;
@@ -126,6 +143,41 @@ impl ColumnarStorage {
         names.into_iter().map(|(name, _)| name).collect()
     }
 
+    /// 对数值列逐元素计算，返回新列（不经过解释器，用于执行脚本前的预计算）
+    /// 列中含 null、字符串等非数值时报错
+    pub fn map_column(&self, name: &str, f: impl Fn(f64) -> f64) -> Result<Vec<f64>, RuntimeError> {
+        Ok(self.number_column(name)?.into_iter().map(f).collect())
+    }
+
+    /// 对两个数值列逐行计算，返回新列，如 combine_columns("high", "low", |h, l| h - l)
+    pub fn combine_columns(
+        &self,
+        a: &str,
+        b: &str,
+        op: impl Fn(f64, f64) -> f64,
+    ) -> Result<Vec<f64>, RuntimeError> {
+        let left = self.number_column(a)?;
+        let right = self.number_column(b)?;
+        Ok(left.into_iter().zip(right).map(|(x, y)| op(x, y)).collect())
+    }
+
+    /// 读取整列为 f64（只接受 Number / Integer）
+    fn number_column(&self, name: &str) -> Result<Vec<f64>, RuntimeError> {
+        let column = self.get_column(name)
+            .ok_or_else(|| RuntimeError::type_error(&format!("列不存在: {}", name)))?;
+        column.iter()
+            .enumerate()
+            .map(|(row, value)| match value {
+                Value::Number(n) => Ok(*n),
+                Value::Integer(i) => Ok(*i as f64),
+                other => Err(RuntimeError::type_error(&format!(
+                    "列 {} 第 {} 行不是数值: {}",
+                    name, row, other
+                ))),
+            })
+            .collect()
+    }
+
     /// 判断是否应该使用列式存储
     /// 根据列数和数据特征判断
     pub fn should_use_columnar(column_count: usize, row_count: usize) -> bool {
@@ -259,6 +311,36 @@ mod tests {
         assert!(!ColumnarStorage::should_use_columnar(5, 50));
     }
 
+    #[test]
+    fn test_column_arithmetic() {
+        let rows: Vec<HashMap<String, Value>> = (0..1000)
+            .map(|i| {
+                let mut row = HashMap::new();
+                row.insert("high".to_string(), Value::Number(100.0 + (i % 7) as f64 * 1.5));
+                row.insert("low".to_string(), Value::Integer(95 + (i % 5)));
+                row.insert("code".to_string(), Value::String("000001".to_string()));
+                row
+            })
+            .collect();
+        let storage = ColumnarStorage::from_rows(&rows);
+
+        let range = storage.combine_columns("high", "low", |h, l| h - l).unwrap();
+        let mut expected = Vec::new();
+        for row in &rows {
+            let high = row["high"].to_number().unwrap();
+            let low = row["low"].to_number().unwrap();
+            expected.push(high - low);
+        }
+        assert_eq!(range, expected);
+
+        let doubled = storage.map_column("low", |x| x * 2.0).unwrap();
+        assert_eq!(doubled[3], 196.0);
+
+        // 非数值列和缺失列报错
+        assert!(storage.map_column("code", |x| x).is_err());
+        assert!(storage.combine_columns("high", "missing", |a, b| a + b).is_err());
+    }
+
     #[test]
     fn test_empty_storage() {
         let storage = ColumnarStorage::from_rows(&[]);
@@ -1,16 +1,99 @@
 // 数据流执行器 - 用于处理时间序列数据的行级执行
 
-use super::{Executor, ContextPool};
-use crate::parser::{Script, PrecisionSetting};
+use super::{Executor, MemoCache, CancelToken, SandboxConfig, ResourceLimits, PrintSink, TraceEntry, ContextPool, ColumnarStorage, map_return_to_output, return_values, check_param_name, check_passthrough};
+use crate::parser::{Script, PrecisionSetting, DivMode, NanMode, IndexMode, TypeAnnotation, is_exported};
 use crate::runtime::{Value, RuntimeError};
 use crate::package_loader::PackageLoader;
 use std::collections::HashMap;
 use std::rc::Rc;
 use std::cell::RefCell;
 
-// 线程局部变量：当前数据流执行器的引用（用于 ref 函数访问历史数据）
+// 线程局部变量：当前时间序列数据源的引用（用于 ref 函数访问历史数据）
 thread_local! {
-    pub(crate) static CURRENT_DATA_STREAM: RefCell<Option<*const DataStreamExecutor>> = RefCell::new(None);
+    pub(crate) static CURRENT_DATA_STREAM: RefCell<Option<*const dyn TimeSeriesSource>> = RefCell::new(None);
+}
+
+/// 时间序列数据源：批量数据流执行器与实时流式执行器共用的历史访问接口
+/// offset 为相对当前行的偏移（0 表示当前行）
+pub(crate) trait TimeSeriesSource {
+    fn get_input_history(&self, name: &str, offset: usize) -> Option<Value>;
+    fn get_output_history(&self, name: &str, offset: usize) -> Option<Value>;
+    fn get_input_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError>;
+    fn get_output_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError>;
+    fn get_current_index(&self) -> usize;
+    fn get_total_rows(&self) -> usize;
+    fn get_current_row(&self) -> Option<&HashMap<String, Value>>;
+    /// 预读 offset 行之后的输入（非因果，仅批量执行支持；超出末尾为 None）
+    fn get_input_forward(&self, name: &str, offset: usize) -> Result<Option<Value>, RuntimeError>;
+    /// 当前行在全部输入中的索引（分组执行时 get_current_index 为组内索引）
+    fn get_global_index(&self) -> usize {
+        self.get_current_index()
+    }
+    /// 全部输入的行数（分组执行时 get_total_rows 为本组行数）
+    fn get_global_total(&self) -> usize {
+        self.get_total_rows()
+    }
+}
+
+impl TimeSeriesSource for DataStreamExecutor {
+    fn get_input_history(&self, name: &str, offset: usize) -> Option<Value> {
+        DataStreamExecutor::get_input_history(self, name, offset)
+    }
+    
+    fn get_output_history(&self, name: &str, offset: usize) -> Option<Value> {
+        DataStreamExecutor::get_output_history(self, name, offset)
+    }
+    
+    fn get_input_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
+        DataStreamExecutor::get_input_slice(self, name, start_offset, end_offset)
+    }
+    
+    fn get_output_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
+        DataStreamExecutor::get_output_slice(self, name, start_offset, end_offset)
+    }
+    
+    fn get_current_index(&self) -> usize {
+        DataStreamExecutor::get_current_index(self)
+    }
+    
+    fn get_total_rows(&self) -> usize {
+        DataStreamExecutor::get_total_rows(self)
+    }
+    
+    fn get_current_row(&self) -> Option<&HashMap<String, Value>> {
+        DataStreamExecutor::get_current_row(self)
+    }
+    
+    fn get_input_forward(&self, name: &str, offset: usize) -> Result<Option<Value>, RuntimeError> {
+        Ok(self.input_matrix
+            .get(self.current_index + offset)
+            .and_then(|row| row.get(name))
+            .cloned())
+    }
+    
+    fn get_global_index(&self) -> usize {
+        match &self.global_rows {
+            Some((indices, _)) => indices.get(self.current_index).copied().unwrap_or(self.current_index),
+            None => self.current_index,
+        }
+    }
+    
+    fn get_global_total(&self) -> usize {
+        match &self.global_rows {
+            Some((_, total)) => *total,
+            None => self.input_matrix.len(),
+        }
+    }
+}
+
+/// 行执行出错时的处理策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// 遇到第一个出错的行即终止整个执行（默认）
+    #[default]
+    FailFast,
+    /// 记录出错行的索引和错误，输出空行后继续执行后续行
+    SkipRow,
 }
 
 /// 数据流执行器
@@ -24,17 +107,74 @@ pub struct DataStreamExecutor {
     /// 输出矩阵（累积结果）
     output_matrix: Vec<HashMap<String, Value>>,
     
+    /// 按 OUTPUT 列累积的结果（execute_all_columnar 期间代替 output_matrix）
+    output_columns: Option<Vec<(String, Vec<Value>)>>,
+    
     /// 当前处理的行索引
     current_index: usize,
     
     /// 精度设置
     precision: Option<PrecisionSetting>,
     
+    /// 除法模式
+    div_mode: DivMode,
+    
+    /// NaN/Infinity 处理模式
+    nan_mode: NanMode,
+    
+    /// 数组越界访问模式
+    index_mode: IndexMode,
+    
+    /// 是否允许脚本访问文件（load_csv 等）
+    file_access: bool,
+    
+    /// 外部参考表缓存（所有行共享）
+    table_cache: Rc<RefCell<HashMap<String, Value>>>,
+    
+    /// @memo 函数结果缓存（所有行共享）
+    memo_cache: Rc<RefCell<MemoCache>>,
+    
+    /// 取消令牌（所有行共享）
+    cancel_token: Option<CancelToken>,
+    
+    /// 沙箱配置（所有行共享）
+    sandbox: Option<Rc<SandboxConfig>>,
+    
+    /// print / tap 的输出目标（所有行共享）
+    print_sink: Option<PrintSink>,
+    
+    /// 资源限制
+    limits: ResourceLimits,
+    
+    /// 需要跟踪的行索引
+    trace_row: Option<usize>,
+    
+    /// 跟踪行的赋值记录
+    trace: Vec<TraceEntry>,
+    
     /// 导入的包（包名 -> 包的变量和函数）
     packages: HashMap<String, HashMap<String, Value>>,
     
+    /// 运行时注入的只读参数（所有行共享）
+    params: HashMap<String, Value>,
+    
     /// 上下文对象池（复用ExecutionContext）
     context_pool: ContextPool,
+    
+    /// 行错误处理策略
+    error_policy: ErrorPolicy,
+    
+    /// 执行前是否按 INPUT 声明检查输入列
+    schema_check: bool,
+    
+    /// SkipRow 模式下收集的行错误（行索引, 错误）
+    row_errors: Vec<(usize, RuntimeError)>,
+    
+    /// 分组执行时本组各行在原始输入中的索引，以及原始输入的总行数
+    global_rows: Option<(Vec<usize>, usize)>,
+    
+    /// 原样复制到输出行的输入列（如 name、sector）
+    passthrough: Vec<String>,
 }
 
 impl DataStreamExecutor {
@@ -47,22 +187,148 @@ impl DataStreamExecutor {
             input_matrix
         };
         
-        // 提取精度设置
-        let precision = if let Script::DataScript { precision, .. } = &script {
-            precision.clone()
+        // 提取精度和各运算模式（PRECISION 声明优先于 CONFIG 中的 precision）
+        let (precision, div_mode, nan_mode, index_mode) = if let Script::DataScript { precision, config, .. } = &script {
+            let precision = config.effective_precision(precision);
+            (
+                precision,
+                config.div_mode.unwrap_or_default(),
+                config.nan_mode.unwrap_or_default(),
+                config.index_mode.unwrap_or_default(),
+            )
         } else {
-            None
+            (None, DivMode::default(), NanMode::default(), IndexMode::default())
         };
         
         DataStreamExecutor {
             script,
             input_matrix: Rc::new(normalized_input),
             output_matrix: Vec::new(),
+            output_columns: None,
             current_index: 0,
             precision,
+            div_mode,
+            nan_mode,
+            index_mode,
+            file_access: true,
+            table_cache: Rc::new(RefCell::new(HashMap::new())),
+            memo_cache: Rc::new(RefCell::new(MemoCache::default())),
+            cancel_token: None,
+            sandbox: None,
+            print_sink: None,
+            limits: ResourceLimits::default(),
+            trace_row: None,
+            trace: Vec::new(),
             packages: HashMap::new(),
+            params: HashMap::new(),
             context_pool: ContextPool::with_default(),
+            error_policy: ErrorPolicy::default(),
+            schema_check: true,
+            row_errors: Vec::new(),
+            global_rows: None,
+            passthrough: Vec::new(),
+        }
+    }
+    
+    /// 设置是否允许脚本访问文件（服务端可关闭）
+    pub fn set_file_access(&mut self, enabled: bool) {
+        self.file_access = enabled;
+    }
+    
+    /// 覆盖脚本中的精度设置（如命令行 --precision）
+    pub fn set_precision(&mut self, precision: Option<PrecisionSetting>) {
+        self.precision = precision;
+    }
+    
+    /// 只覆盖精度位数（如命令行 --precision），保留脚本 PRECISION / CONFIG 中的舍入方式
+    pub fn set_precision_scale(&mut self, scale: u32) {
+        let rounding = match (&self.precision, &self.script) {
+            (Some(precision), _) => precision.rounding,
+            (None, Script::DataScript { config, .. }) => config.rounding,
+            _ => None,
+        };
+        self.precision = Some(PrecisionSetting { scale, rounding });
+    }
+    
+    /// 覆盖脚本中的除法模式（如命令行 --div-mode）
+    pub fn set_div_mode(&mut self, div_mode: DivMode) {
+        self.div_mode = div_mode;
+    }
+    
+    /// 覆盖脚本中的数组越界访问模式
+    pub fn set_index_mode(&mut self, index_mode: IndexMode) {
+        self.index_mode = index_mode;
+    }
+    
+    /// 注入只读参数（如任务配置中的阈值），脚本中按变量名访问
+    /// 与 INPUT 声明的列同名时报错
+    pub fn set_param(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if let Script::DataScript { input, .. } = &self.script {
+            check_param_name(name, input)?;
         }
+        self.params.insert(name.to_string(), value);
+        Ok(())
+    }
+    
+    /// 分组执行时设置本组各行在原始输入中的索引和原始总行数（用于 _global_index / _global_total）
+    pub(crate) fn set_global_rows(&mut self, indices: Vec<usize>, total: usize) {
+        self.global_rows = Some((indices, total));
+    }
+    
+    /// 设置透传列：这些输入列原样复制到每个输出行，不必写进 OUTPUT 和 return
+    /// 与 OUTPUT 声明的列同名时报错
+    pub fn set_passthrough(&mut self, columns: Vec<String>) -> Result<(), RuntimeError> {
+        if let Script::DataScript { output, .. } = &self.script {
+            check_passthrough(&columns, output)?;
+        }
+        self.passthrough = columns;
+        Ok(())
+    }
+    
+    /// 设置行错误处理策略
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+    
+    /// 设置沙箱配置（所有行共享，禁用的内置函数被调用时报权限错误）
+    pub fn set_sandbox(&mut self, sandbox: SandboxConfig) {
+        self.sandbox = Some(Rc::new(sandbox));
+    }
+    
+    /// 设置资源限制（输入行数、内置函数生成的数组长度）
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+    
+    /// 设置 print / tap 的输出目标（所有行共享，默认打印到标准输出）
+    pub fn set_print_sink(&mut self, sink: impl Fn(&str) + 'static) {
+        self.print_sink = Some(Rc::new(sink));
+    }
+    
+    /// 设置取消令牌（每条语句和 map/filter/reduce 迭代时检查）
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel_token = Some(token);
+    }
+    
+    /// 设置执行前是否检查输入列（默认开启，关闭后缺少的列按 null 处理）
+    pub fn set_schema_check(&mut self, enabled: bool) {
+        self.schema_check = enabled;
+    }
+    
+    /// 跟踪指定行的每次赋值（None 关闭跟踪），结果通过 trace() 获取
+    pub fn set_trace(&mut self, row_index: Option<usize>) {
+        self.trace_row = row_index;
+        self.trace.clear();
+    }
+    
+    /// 获取跟踪行的赋值记录
+    pub fn trace(&self) -> &[TraceEntry] {
+        &self.trace
+    }
+    
+    /// 获取执行过程中跳过的行错误（仅 SkipRow 模式下收集）
+    pub fn row_errors(&self) -> &[(usize, RuntimeError)] {
+        &self.row_errors
     }
     
     /// 创建数据流执行器（带包加载）
@@ -114,24 +380,24 @@ impl DataStreamExecutor {
     
     /// 执行包脚本一次，返回包的所有变量和函数
     fn execute_package_once(&self, package_script: &Script) -> Result<HashMap<String, Value>, RuntimeError> {
-        if let Script::Package { variables, functions, .. } = package_script {
+        if let Script::Package { exports, variables, functions, .. } = package_script {
             let mut package_executor = Executor::new();
             
             // 执行包脚本
             package_executor.execute_package_script(package_script)?;
             
-            // 收集包的所有变量
+            // 收集包的公开成员（EXPORT 声明或下划线约定）
             let mut package_data = HashMap::new();
             
             // 添加包变量
-            for var_def in variables {
+            for var_def in variables.iter().filter(|v| is_exported(exports.as_deref(), &v.name)) {
                 if let Some(value) = package_executor.package_vars.get(&var_def.name) {
                     package_data.insert(var_def.name.clone(), value.clone());
                 }
             }
             
             // 添加包函数（封装为 Function 类型）
-            for func_def in functions {
+            for func_def in functions.iter().filter(|f| is_exported(exports.as_deref(), &f.name)) {
                 let func_value = Value::Function(Box::new(func_def.clone()));
                 package_data.insert(func_def.name.clone(), func_value);
             }
@@ -144,14 +410,122 @@ impl DataStreamExecutor {
     
     /// 执行整个数据流
     pub fn execute_all(&mut self) -> Result<Vec<HashMap<String, Value>>, RuntimeError> {
+        self.run_rows()?;
+        Ok(self.output_matrix.clone())
+    }
+    
+    /// 执行所有行，输出直接按列累积，省去每行一个 HashMap 的开销
+    ///
+    /// 结果与 `execute_all` 相同（列按 OUTPUT 声明顺序），但不写入 `get_output` 的输出矩阵。
+    pub fn execute_all_columnar(&mut self) -> Result<ColumnarStorage, RuntimeError> {
         let row_count = self.input_matrix.len();
+        self.output_columns = Some(match &self.script {
+            Script::DataScript { output, .. } => output.iter()
+                .map(|param| &param.name)
+                .chain(&self.passthrough)
+                .map(|name| (name.clone(), Vec::with_capacity(row_count)))
+                .collect(),
+            _ => Vec::new(),
+        });
+        let result = self.run_rows();
+        let columns = self.output_columns.take().unwrap_or_default();
+        result?;
+        Ok(ColumnarStorage::from_columns(columns))
+    }
+    
+    /// 逐行执行脚本，按错误策略处理出错的行
+    fn run_rows(&mut self) -> Result<(), RuntimeError> {
+        let row_count = self.input_matrix.len();
+        self.limits.check_input_rows(row_count)?;
+        self.row_errors.clear();
+        if self.schema_check {
+            self.check_schema()?;
+        }
+        if let Some(first_row) = self.input_matrix.first().filter(|row| !row.is_empty()) {
+            if let Some(name) = self.passthrough.iter().find(|name| !first_row.contains_key(*name)) {
+                return Err(RuntimeError::type_error(&format!("透传列 {} 不在输入数据中", name)));
+            }
+        }
         
         for row_idx in 0..row_count {
             self.current_index = row_idx;
-            self.execute_row()?;
+            let result = self.execute_row();
+            
+            // 清理线程局部变量（出错时也要清理）
+            CURRENT_DATA_STREAM.with(|cell| {
+                *cell.borrow_mut() = None;
+            });
+            
+            if let Err(e) = result {
+                match self.error_policy {
+                    ErrorPolicy::FailFast => return Err(e),
+                    // 取消/超时总是中止整个执行
+                    ErrorPolicy::SkipRow if e.is_cancelled() => return Err(e),
+                    ErrorPolicy::SkipRow => {
+                        // 输出空行，保持输出行与输入行对齐（ref 访问历史输出依赖该对齐）
+                        self.row_errors.push((row_idx, e));
+                        match &mut self.output_columns {
+                            Some(columns) => columns.iter_mut().for_each(|(_, column)| column.push(Value::Null)),
+                            None => self.output_matrix.push(HashMap::new()),
+                        }
+                    }
+                }
+            }
         }
         
-        Ok(self.output_matrix.clone())
+        Ok(())
+    }
+    
+    /// 按 INPUT 声明检查首行：列是否存在、类型是否兼容，一次报告所有问题
+    /// 没有输入数据（首行为空）时跳过
+    fn check_schema(&self) -> Result<(), RuntimeError> {
+        let (input, first_row) = match (&self.script, self.input_matrix.first()) {
+            (Script::DataScript { input, .. }, Some(row)) if !row.is_empty() => (input, row),
+            _ => return Ok(()),
+        };
+        
+        let mut problems = Vec::new();
+        for param in input {
+            match first_row.get(&param.name) {
+                None => problems.push(format!("缺少列 {}", param.name)),
+                Some(value) => {
+                    if let Some(expected) = &param.type_annotation {
+                        if !is_compatible(expected, value) {
+                            problems.push(format!("列 {} 期望 {}，实际值为 {}", param.name, expected, value));
+                        }
+                    }
+                }
+            }
+        }
+        
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(RuntimeError::type_error(&format!("输入数据与 INPUT 声明不符: {}", problems.join("；"))))
+        }
+    }
+    
+    /// 获取已计算的输出矩阵
+    pub fn get_output(&self) -> &[HashMap<String, Value>] {
+        &self.output_matrix
+    }
+    
+    /// 截面排名：在所有输出行之间对 name 列升序排名（最小值为 1，并列取最小名次），
+    /// 结果写入 `{name}_rank` 列；非数值行的排名为 null
+    pub fn rank_column(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let mut rows: Vec<_> = self.output_matrix.iter_mut().collect();
+        require_numeric_column(&rows, name)?;
+        rank_rows(&mut rows, name);
+        Ok(())
+    }
+    
+    /// 截面标准分：在所有输出行之间计算 name 列的 z-score（总体标准差），
+    /// 结果写入 `{name}_zscore` 列；非数值行或标准差为 0 时为 null
+    pub fn zscore_column(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let mut rows: Vec<_> = self.output_matrix.iter_mut().collect();
+        require_numeric_column(&rows, name)?;
+        zscore_rows(&mut rows, name);
+        Ok(())
     }
     
     /// 获取输入矩阵的历史值（通过 index 引用）
@@ -187,35 +561,11 @@ impl DataStreamExecutor {
     /// end_offset: 结束偏移量（相对于当前行，0表示当前行）
     /// 返回: [current_index - start_offset, ..., current_index - end_offset]
     pub fn get_input_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
-        // 构建列数据
-        let mut column_data = Vec::new();
-        
-        // 计算实际索引范围
-        let start_idx = if start_offset > self.current_index {
-            // 历史不足，填充 null
-            for _ in 0..(start_offset - self.current_index) {
-                column_data.push(Value::Null);
-            }
-            0
-        } else {
-            self.current_index - start_offset
-        };
-        
-        let end_idx = if end_offset > self.current_index {
-            0
-        } else {
-            self.current_index - end_offset
-        };
-        
-        // 提取数据
-        for i in start_idx..=end_idx {
-            let value = self.input_matrix
-                .get(i)
-                .and_then(|row| row.get(name))
-                .cloned()
-                .unwrap_or(Value::Null);
-            column_data.push(value);
-        }
+        // 第一行之前的位置填充 null
+        let column_data = (end_offset..=start_offset)
+            .rev()
+            .map(|offset| self.get_input_history(name, offset).unwrap_or(Value::Null))
+            .collect();
         
         // 返回普通数组（后期可优化为 ArraySlice）
         Ok(Value::Array(column_data))
@@ -228,6 +578,10 @@ impl DataStreamExecutor {
         }
         
         let target_index = self.current_index - offset;
+        if let Some(columns) = &self.output_columns {
+            let (_, column) = columns.iter().find(|(column_name, _)| column_name == name)?;
+            return column.get(target_index).cloned();
+        }
         self.output_matrix
             .get(target_index)
             .and_then(|row| row.get(name))
@@ -236,35 +590,15 @@ impl DataStreamExecutor {
     
     /// 获取输出矩阵的切片
     pub fn get_output_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
-        let mut column_data = Vec::new();
-        
-        let start_idx = if start_offset > self.current_index {
-            for _ in 0..(start_offset - self.current_index) {
-                column_data.push(Value::Null);
-            }
-            0
-        } else {
-            self.current_index - start_offset
-        };
-        
-        let end_idx = if end_offset > self.current_index || end_offset == 0 {
-            if end_offset == 0 {
-                return Err(RuntimeError::type_error("无法获取当前行的输出值"));
-            }
-            0
-        } else {
-            self.current_index - end_offset
-        };
-        
-        for i in start_idx..=end_idx {
-            let value = self.output_matrix
-                .get(i)
-                .and_then(|row| row.get(name))
-                .cloned()
-                .unwrap_or(Value::Null);
-            column_data.push(value);
+        if end_offset == 0 {
+            return Err(RuntimeError::type_error("无法获取当前行的输出值"));
         }
         
+        let column_data = (end_offset..=start_offset)
+            .rev()
+            .map(|offset| self.get_output_history(name, offset).unwrap_or(Value::Null))
+            .collect();
+        
         Ok(Value::Array(column_data))
     }
     
@@ -272,7 +606,7 @@ impl DataStreamExecutor {
     fn execute_row(&mut self) -> Result<(), RuntimeError> {
         // 设置线程局部变量,让 ref() 函数能访问历史数据
         CURRENT_DATA_STREAM.with(|cell| {
-            *cell.borrow_mut() = Some(self as *const DataStreamExecutor);
+            *cell.borrow_mut() = Some(self as &dyn TimeSeriesSource as *const dyn TimeSeriesSource);
         });
         
         // 1. 设置当前行的 INPUT 变量（可能为空）
@@ -296,7 +630,20 @@ impl DataStreamExecutor {
                 context,
                 functions: HashMap::new(),
                 package_vars: HashMap::new(),
+                current_package: None,
+                params: self.params.clone(),
                 precision: self.precision.clone(),
+                div_mode: self.div_mode,
+                nan_mode: self.nan_mode,
+                index_mode: self.index_mode,
+                file_access: self.file_access,
+                table_cache: Rc::clone(&self.table_cache),
+                memo_cache: Rc::clone(&self.memo_cache),
+                cancel_token: self.cancel_token.clone(),
+                sandbox: self.sandbox.clone(),
+                print_sink: self.print_sink.clone(),
+                limits: self.limits,
+                trace: if self.trace_row == Some(self.current_index) { Some(Vec::new()) } else { None },
             };
             
             // 将包数据注入到 package_vars（扩展为平面结构）
@@ -308,30 +655,112 @@ impl DataStreamExecutor {
                 }
             }
             
-            let result = executor.execute_body(body)?;
+            let body_result = executor.execute_body(body);
+            if let Some(entries) = executor.trace.take() {
+                self.trace = entries;
+            }
+            
+            // 应用精度到返回值
+            let result = match body_result? {
+                Some(val) => Some(executor.apply_precision_to_value(val)?),
+                None => None,
+            };
             
             // 归还上下文到对象池
             self.context_pool.release(executor.context);
             
-            // 3. 收集输出
-            if let Some(Value::Array(output_values)) = result {
-                let mut output_row = HashMap::new();
-                
-                for (i, param) in output.iter().enumerate() {
-                    if let Some(value) = output_values.get(i) {
-                        output_row.insert(param.name.clone(), value.clone());
+            // 3. 收集输出（按位置映射到 OUTPUT 列名）
+            if let Some(result) = result {
+                let input_row = &self.input_matrix[self.current_index];
+                let passthrough_value = |name: &String| input_row.get(name).cloned().unwrap_or(Value::Null);
+                match &mut self.output_columns {
+                    Some(columns) => {
+                        let (returned, passthrough) = columns.split_at_mut(output.len());
+                        for ((_, column), value) in returned.iter_mut().zip(return_values(output, result)?) {
+                            column.push(value);
+                        }
+                        for (name, column) in passthrough {
+                            column.push(passthrough_value(name));
+                        }
+                    }
+                    None => {
+                        let mut row = map_return_to_output(output, result)?;
+                        for name in &self.passthrough {
+                            row.insert(name.clone(), passthrough_value(name));
+                        }
+                        self.output_matrix.push(row);
                     }
                 }
-                
-                self.output_matrix.push(output_row);
             }
         }
         
-        // 清理线程局部变量
-        CURRENT_DATA_STREAM.with(|cell| {
-            *cell.borrow_mut() = None;
-        });
-        
         Ok(())
     }
 }
+
+/// 提取一组输出行中某列的数值（非数值为 None）
+fn numeric_column(rows: &[&mut HashMap<String, Value>], name: &str) -> Vec<Option<f64>> {
+    rows.iter()
+        .map(|row| match row.get(name) {
+            Some(v @ (Value::Number(_) | Value::Integer(_) | Value::Decimal(_))) => v.to_number().ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 整列都没有数值时报错
+pub(crate) fn require_numeric_column(rows: &[&mut HashMap<String, Value>], name: &str) -> Result<(), RuntimeError> {
+    if numeric_column(rows, name).iter().all(|v| v.is_none()) {
+        return Err(RuntimeError::type_error(&format!("输出列 {} 不存在或没有数值", name)));
+    }
+    Ok(())
+}
+
+/// 在一组输出行之间对 name 列升序排名，写入 `{name}_rank` 列
+pub(crate) fn rank_rows(rows: &mut [&mut HashMap<String, Value>], name: &str) {
+    let values = numeric_column(rows, name);
+    
+    let mut sorted: Vec<f64> = values.iter().flatten().copied().collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    
+    let rank_name = format!("{}_rank", name);
+    for (row, value) in rows.iter_mut().zip(values) {
+        let rank = match value {
+            Some(v) => Value::Number((sorted.partition_point(|x| *x < v) + 1) as f64),
+            None => Value::Null,
+        };
+        row.insert(rank_name.clone(), rank);
+    }
+}
+
+/// 在一组输出行之间计算 name 列的 z-score，写入 `{name}_zscore` 列
+pub(crate) fn zscore_rows(rows: &mut [&mut HashMap<String, Value>], name: &str) {
+    let values = numeric_column(rows, name);
+    
+    let present: Vec<f64> = values.iter().flatten().copied().collect();
+    let n = present.len() as f64;
+    let mean = present.iter().sum::<f64>() / n;
+    let std = (present.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    
+    let zscore_name = format!("{}_zscore", name);
+    for (row, value) in rows.iter_mut().zip(values) {
+        let zscore = match value {
+            Some(v) if std > 0.0 => Value::Number((v - mean) / std),
+            _ => Value::Null,
+        };
+        row.insert(zscore_name.clone(), zscore);
+    }
+}
+
+/// 输入值是否与声明的类型兼容（null 总是兼容；数值类型之间互相兼容，
+/// string 列接受 CSV 中数字形式的代码）
+fn is_compatible(expected: &TypeAnnotation, value: &Value) -> bool {
+    matches!(
+        (expected, value),
+        (_, Value::Null)
+            | (TypeAnnotation::Number | TypeAnnotation::Decimal, Value::Number(_) | Value::Integer(_) | Value::Decimal(_))
+            | (TypeAnnotation::String, Value::String(_) | Value::Number(_) | Value::Integer(_) | Value::Decimal(_))
+            | (TypeAnnotation::Bool, Value::Bool(_))
+            | (TypeAnnotation::Array, Value::Array(_) | Value::ArraySlice { .. })
+    )
+}
@@ -1,9 +1,8 @@
 // 表达式求值逻辑
 
-use super::Executor;
-use crate::parser::{Expr, BinaryOp, UnaryOp, FunctionDef};
-use crate::runtime::{Value, RuntimeError};
-use crate::lexer::{FStringPart, Lexer};
+use super::{Executor, MemoCache};
+use crate::parser::{Expr, BinaryOp, UnaryOp, FunctionDef, FStringSegment, DivMode, IndexMode, is_private_name};
+use crate::runtime::{Value, RuntimeError, ErrorType};
 use std::collections::HashMap;
 
 impl Executor {
@@ -11,42 +10,26 @@ impl Executor {
     pub(crate) fn execute_expr(&mut self, expr: &Expr) -> Result<Value, RuntimeError> {
         match expr {
             Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Integer(i) => Ok(Value::Integer(*i)),
             Expr::String(s) => Ok(Value::String(s.clone())),
             
-            Expr::FString(parts) => {
-                // 处理 f-string 字符串插值
+            Expr::FString(segments) => {
+                // 处理 f-string 字符串插值（嵌入表达式已在解析阶段编译）
                 let mut result = String::new();
-                for part in parts {
-                    match part {
-                        FStringPart::Text(text) => result.push_str(text),
-                        FStringPart::Expr(expr_str) => {
-                            // 解析表达式
-                            let mut lexer = Lexer::new(expr_str);
-                            let tokens = lexer.tokenize()
-                                .map_err(|e| RuntimeError::type_error(&format!("f-string 表达式解析错误: {}", e)))?;
-                            let mut parser = crate::parser::Parser::new(tokens);
-                            let script = parser.parse()
-                                .map_err(|e| RuntimeError::type_error(&format!("f-string 表达式解析错误: {}", e)))?;
-                            
-                            // 执行表达式（假设是单个表达式语句）
-                            if let crate::parser::Script::DataScript { body, .. } = script {
-                                if let Some(crate::parser::Stmt::Expression(expr)) = body.first() {
-                                    let value = self.execute_expr(expr)?;
-                                    // 使用自定义格式化，字符串不加引号
-                                    let formatted = match value {
-                                        Value::String(s) => s,
-                                        Value::Number(n) => n.to_string(),
-                                        Value::Bool(b) => b.to_string(),
-                                        Value::Null => "null".to_string(),
-                                        _ => value.to_string(),
-                                    };
-                                    result.push_str(&formatted);
-                                } else {
-                                    return Err(RuntimeError::type_error("f-string 中的表达式无效"));
-                                }
-                            } else {
-                                return Err(RuntimeError::type_error("f-string 中的表达式无效"));
-                            }
+                for segment in segments {
+                    match segment {
+                        FStringSegment::Text(text) => result.push_str(text),
+                        FStringSegment::Expr(inner) => {
+                            let value = self.execute_expr(inner)?;
+                            // 使用自定义格式化，字符串不加引号
+                            let formatted = match value {
+                                Value::String(s) => s,
+                                Value::Number(n) => n.to_string(),
+                                Value::Bool(b) => b.to_string(),
+                                Value::Null => "null".to_string(),
+                                _ => value.to_string(),
+                            };
+                            result.push_str(&formatted);
                         }
                     }
                 }
@@ -66,16 +49,13 @@ impl Executor {
                 }
                 
                 self.context.get(name)
+                    .or_else(|| self.params.get(name))
                     .cloned()
                     .ok_or_else(|| RuntimeError::undefined_variable(name))
             }
             
             Expr::Array(elements) => {
-                let mut arr = Vec::new();
-                for elem in elements {
-                    arr.push(self.execute_expr(elem)?);
-                }
-                Ok(Value::Array(arr))
+                Ok(Value::Array(self.execute_spread_list(elements)?))
             }
             
             Expr::Binary { left, op, right } => {
@@ -83,12 +63,18 @@ impl Executor {
                 let right_val = self.execute_expr(right)?;
                 
                 match op {
-                    BinaryOp::Add => left_val.add(&right_val),
-                    BinaryOp::Sub => left_val.sub(&right_val),
-                    BinaryOp::Mul => left_val.mul(&right_val),
-                    BinaryOp::Div => left_val.div(&right_val),
-                    BinaryOp::Mod => left_val.modulo(&right_val),
-                    BinaryOp::Pow => left_val.pow(&right_val),
+                    BinaryOp::Add => self.check_finite(left_val.add(&right_val)?),
+                    BinaryOp::Sub => self.check_finite(left_val.sub(&right_val)?),
+                    BinaryOp::Mul => self.check_finite(left_val.mul(&right_val)?),
+                    BinaryOp::Div => match left_val.div(&right_val) {
+                        // div=null 模式下除零返回 null
+                        Err(e) if e.error_type == ErrorType::ZeroDivision && self.div_mode == DivMode::Null => {
+                            Ok(Value::Null)
+                        }
+                        other => self.check_finite(other?),
+                    },
+                    BinaryOp::Mod => self.check_finite(left_val.modulo(&right_val)?),
+                    BinaryOp::Pow => self.check_finite(left_val.pow(&right_val)?),
                     BinaryOp::Gt => left_val.gt(&right_val),
                     BinaryOp::Lt => left_val.lt(&right_val),
                     BinaryOp::GtEq => left_val.gte(&right_val),
@@ -117,11 +103,21 @@ impl Executor {
                 }
             }
             
-            Expr::When { branches, else_expr } => {
+            Expr::When { subject, branches, else_expr } => {
+                // 带主体的 when：先求值主体，分支条件与之做相等比较
+                let subject_val = match subject {
+                    Some(subject_expr) => Some(self.execute_expr(subject_expr)?),
+                    None => None,
+                };
+                
                 // when 表达式：依次求值每个分支的条件
                 for branch in branches {
                     let cond = self.execute_expr(&branch.condition)?;
-                    if cond.to_bool() {
+                    let matched = match &subject_val {
+                        Some(val) => val.eq(&cond)?.to_bool(),
+                        None => cond.to_bool(),
+                    };
+                    if matched {
                         return self.execute_expr(&branch.result);
                     }
                 }
@@ -143,9 +139,9 @@ impl Executor {
                 let idx_val = self.execute_expr(index)?;
                 
                 // 获取索引值
-                let idx = match idx_val {
-                    Value::Number(n) => n as isize,
-                    _ => return Err(RuntimeError::type_error("索引必须为数字")),
+                let idx = match idx_val.to_index() {
+                    Some(i) => i,
+                    None => return Err(RuntimeError::type_error("索引必须为数字")),
                 };
                 
                 // 判断 base 是否为变量名（时间序列访问）
@@ -162,13 +158,7 @@ impl Executor {
                         // 如果没有在 DataStreamExecutor 中，则尝试从普通变量获取
                         let base_val = self.execute_expr(base)?;
                         if let Value::Array(arr) = base_val {
-                            let len = arr.len() as isize;
-                            let actual_idx = (len + idx) as usize;
-                            if actual_idx < arr.len() {
-                                return Ok(arr[actual_idx].clone());
-                            } else {
-                                return Ok(Value::Null);
-                            }
+                            return self.index_array(&arr, idx);
                         }
                         
                         return Ok(Value::Null);
@@ -183,22 +173,7 @@ impl Executor {
                 // 普通数组索引
                 let arr_val = self.execute_expr(base)?;
                 if let Value::Array(arr) = arr_val {
-                    if idx < 0 {
-                        let len = arr.len() as isize;
-                        let actual_idx = (len + idx) as usize;
-                        if actual_idx < arr.len() {
-                            Ok(arr[actual_idx].clone())
-                        } else {
-                            Ok(Value::Null)
-                        }
-                    } else {
-                        let i = idx as usize;
-                        if i < arr.len() {
-                            Ok(arr[i].clone())
-                        } else {
-                            Ok(Value::Null)
-                        }
-                    }
+                    self.index_array(&arr, idx)
                 } else {
                     Err(RuntimeError::type_error("索引操作需要数组类型"))
                 }
@@ -210,9 +185,9 @@ impl Executor {
                 // 解析 start 和 end 索引
                 let start_idx = if let Some(s) = start {
                     let s_val = self.execute_expr(s)?;
-                    match s_val {
-                        Value::Number(n) => Some(n as isize),
-                        _ => return Err(RuntimeError::type_error("切片索引必须为数字")),
+                    match s_val.to_index() {
+                        Some(i) => Some(i),
+                        None => return Err(RuntimeError::type_error("切片索引必须为数字")),
                     }
                 } else {
                     None
@@ -220,9 +195,9 @@ impl Executor {
                 
                 let end_idx = if let Some(e) = end {
                     let e_val = self.execute_expr(e)?;
-                    match e_val {
-                        Value::Number(n) => Some(n as isize),
-                        _ => return Err(RuntimeError::type_error("切片索引必须为数字")),
+                    match e_val.to_index() {
+                        Some(i) => Some(i),
+                        None => return Err(RuntimeError::type_error("切片索引必须为数字")),
                     }
                 } else {
                     Some(0)  // 默认到当前值
@@ -243,17 +218,13 @@ impl Executor {
                 // 普通数组切片
                 let arr_val = self.execute_expr(base)?;
                 if let Value::Array(arr) = arr_val {
-                    let len = arr.len() as isize;
-                    
                     let actual_start = match start_idx {
-                        Some(s) if s < 0 => ((len + s).max(0)) as usize,
-                        Some(s) => (s as usize).min(arr.len()),
+                        Some(s) => self.slice_bound(s, arr.len())?,
                         None => 0,
                     };
                     
                     let actual_end = match end_idx {
-                        Some(e) if e < 0 => ((len + e).max(0)) as usize,
-                        Some(e) => (e as usize).min(arr.len()),
+                        Some(e) => self.slice_bound(e, arr.len())?,
                         None => arr.len(),
                     };
                     
@@ -267,9 +238,9 @@ impl Executor {
                 }
             }
             
-            Expr::Spread(inner) => {
-                // 展开在特定上下文中处理,这里直接返回数组
-                self.execute_expr(inner)
+            Expr::Spread(_) => {
+                // 展开只在数组字面量和函数参数中有意义（见 execute_spread_list）
+                Err(RuntimeError::type_error("展开运算符 ... 只能用于数组字面量或函数参数"))
             }
             
             Expr::Lambda { params, body } => {
@@ -292,10 +263,13 @@ impl Executor {
                 for stage in stages {
                     // 管道: value |> func(arg) => func(value, arg)
                     if let Expr::Call { callee, args } = stage {
-                        let mut new_args = vec![result];
-                        for arg in args {
-                            new_args.push(self.execute_expr(arg)?);
+                        // coalesce / nvl / if_null 同样按需求值后备参数
+                        if matches!(callee.as_str(), "coalesce" | "nvl" | "if_null") {
+                            result = self.execute_coalesce(callee, Some(result), args)?;
+                            continue;
                         }
+                        let mut new_args = vec![result];
+                        new_args.extend(self.execute_spread_list(args)?);
                         result = self.execute_builtin(callee, &new_args)?;
                     } else {
                         return Err(RuntimeError::type_error("管道右侧必须是函数调用"));
@@ -307,6 +281,7 @@ impl Executor {
             Expr::MemberAccess { object, member } => {
                 // 包.成员 访问
                 // object 是包名，member 是变量/函数名
+                self.check_member_access(object, member)?;
                 let full_name = format!("{}.{}", object, member);
                 if let Some(value) = self.package_vars.get(&full_name) {
                     Ok(value.clone())
@@ -319,9 +294,18 @@ impl Executor {
     
     /// 执行函数调用
     pub(crate) fn execute_call(&mut self, callee: &str, args: &[Expr]) -> Result<Value, RuntimeError> {
+        // 包外不能调用包的私有函数（pkg._helper）
+        if let Some((package, member)) = callee.split_once('.') {
+            self.check_member_access(package, member)?;
+        }
+        
+        // coalesce / nvl / if_null 按需求值，需在参数统一求值之前处理（同名的用户函数优先）
+        if matches!(callee, "coalesce" | "nvl" | "if_null") && !self.is_user_callable(callee) {
+            return self.execute_coalesce(callee, None, args);
+        }
+        
         // 先尝试内置函数
-        let arg_values: Result<Vec<Value>, _> = args.iter().map(|a| self.execute_expr(a)).collect();
-        let arg_values = arg_values?;
+        let arg_values = self.execute_spread_list(args)?;
         
         // 检查是否是 Lambda 函数
         if let Some(lambda_val) = self.context.get(callee) {
@@ -330,22 +314,122 @@ impl Executor {
             }
         }
         
-        // 检查是否是包函数 (Function 类型)
-        if let Some(func_val) = self.package_vars.get(callee) {
-            if let Value::Function(func_def) = func_val {
-                let func_def_clone = (**func_def).clone();
-                return self.execute_user_function(&func_def_clone, &arg_values);
-            }
+        // 检查是否是包函数 (Function 类型)；包函数内可直接按名字调用同包的公开函数
+        let qualified = match &self.current_package {
+            Some(package) if !callee.contains('.') => format!("{}.{}", package, callee),
+            _ => callee.to_string(),
+        };
+        let func_def = [qualified.as_str(), callee].into_iter().find_map(|name| match self.package_vars.get(name) {
+            Some(Value::Function(func_def)) => Some((name.to_string(), (**func_def).clone())),
+            _ => None,
+        });
+        if let Some((name, func_def)) = func_def {
+            let package = name.split_once('.').map(|(package, _)| package.to_string());
+            let saved_package = std::mem::replace(&mut self.current_package, package);
+            let result = self.execute_user_function(&name, &func_def, &arg_values);
+            self.current_package = saved_package;
+            return result;
         }
         
         // 检查是否是包函数 (functions map)
         if let Some(func_def) = self.functions.get(callee).cloned() {
-            return self.execute_user_function(&func_def, &arg_values);
+            return self.execute_user_function(callee, &func_def, &arg_values);
         }
         
         self.execute_builtin(callee, &arg_values)
     }
     
+    /// 是否为 Lambda 变量或用户/包函数
+    fn is_user_callable(&self, name: &str) -> bool {
+        matches!(self.context.get(name), Some(Value::Lambda { .. }))
+            || matches!(self.package_vars.get(name), Some(Value::Function(_)))
+            || self.functions.contains_key(name)
+    }
+    
+    /// coalesce / nvl / if_null：从左到右求值，返回第一个非 null 的参数，之后的参数不再求值
+    /// 全部为 null 时返回 null；nvl / if_null 只接受 2 个参数
+    /// piped 为管道左侧已求出的值（x |> coalesce(0)），作为第一个参数
+    fn execute_coalesce(&mut self, name: &str, piped: Option<Value>, args: &[Expr]) -> Result<Value, RuntimeError> {
+        let count = args.len() + usize::from(piped.is_some());
+        if name == "coalesce" && count == 0 {
+            return Err(RuntimeError::argument_mismatch(name, "至少 1", 0));
+        }
+        if name != "coalesce" && count != 2 {
+            return Err(RuntimeError::argument_mismatch(name, "2", count));
+        }
+        if let Some(sandbox) = &self.sandbox {
+            sandbox.check(name)?;
+        }
+        
+        if let Some(value) = piped.filter(|v| !v.is_null()) {
+            return Ok(value);
+        }
+        for arg in args {
+            let values = self.execute_spread_list(std::slice::from_ref(arg))?;
+            if let Some(value) = values.into_iter().find(|v| !v.is_null()) {
+                return Ok(value);
+            }
+        }
+        Ok(Value::Null)
+    }
+    
+    /// 包成员访问检查：包外访问私有成员（以下划线开头）报错
+    /// 导入时只收集公开成员，EXPORT 显式列出的下划线成员可以访问
+    fn check_member_access(&self, package: &str, member: &str) -> Result<(), RuntimeError> {
+        if is_private_name(member) && !self.package_vars.contains_key(&format!("{}.{}", package, member)) {
+            return Err(RuntimeError::type_error(&format!(
+                "无法访问包 {} 的私有成员: {}（以 _ 开头的成员只能在包内使用）", package, member
+            )));
+        }
+        Ok(())
+    }
+    
+    /// 数组索引读取（负数从末尾计数），越界时按索引模式返回 null 或报错
+    fn index_array(&self, arr: &[Value], idx: isize) -> Result<Value, RuntimeError> {
+        let actual = if idx < 0 { arr.len() as isize + idx } else { idx };
+        if (0..arr.len() as isize).contains(&actual) {
+            return Ok(arr[actual as usize].clone());
+        }
+        match self.index_mode {
+            IndexMode::Lenient => Ok(Value::Null),
+            IndexMode::Strict => Err(RuntimeError::index_out_of_bounds(idx, arr.len())),
+        }
+    }
+    
+    /// 切片边界（负数从末尾计数），超出数组范围时按索引模式截断或报错
+    fn slice_bound(&self, bound: isize, len: usize) -> Result<usize, RuntimeError> {
+        let actual = if bound < 0 { len as isize + bound } else { bound };
+        if (0..=len as isize).contains(&actual) {
+            return Ok(actual as usize);
+        }
+        match self.index_mode {
+            IndexMode::Lenient => Ok(actual.clamp(0, len as isize) as usize),
+            IndexMode::Strict => Err(RuntimeError::index_out_of_bounds(bound, len)),
+        }
+    }
+    
+    /// 依次求值表达式列表，...expr 元素将数组展开为多个值
+    /// [1, ...rest, 5] / f(...args)
+    pub(crate) fn execute_spread_list(&mut self, exprs: &[Expr]) -> Result<Vec<Value>, RuntimeError> {
+        let mut values = Vec::with_capacity(exprs.len());
+        for expr in exprs {
+            if let Expr::Spread(inner) = expr {
+                match self.execute_expr(inner)? {
+                    Value::Array(arr) => values.extend(arr),
+                    Value::ArraySlice { column_data, start, len } => {
+                        values.extend(column_data.iter().skip(start).take(len).cloned());
+                    }
+                    other => {
+                        return Err(RuntimeError::type_error(&format!("只能展开数组，实际为: {}", other)));
+                    }
+                }
+            } else {
+                values.push(self.execute_expr(expr)?);
+            }
+        }
+        Ok(values)
+    }
+    
     /// 执行 Lambda 函数
     pub(crate) fn execute_lambda(
         &mut self,
@@ -355,11 +439,7 @@ impl Executor {
         args: &[Value],
     ) -> Result<Value, RuntimeError> {
         if params.len() != args.len() {
-            return Err(RuntimeError::type_error(&format!(
-                "Lambda 参数数量不匹配: 期望 {} 个，实际 {} 个",
-                params.len(),
-                args.len()
-            )));
+            return Err(RuntimeError::argument_mismatch("Lambda", &params.len().to_string(), args.len()));
         }
         
         // 保存当前上下文
@@ -384,9 +464,10 @@ impl Executor {
         result
     }
     
-    /// 执行用户定义函数
+    /// 执行用户定义函数（name 为调用时的名字，包函数带包名前缀，如 pa.f）
     pub(crate) fn execute_user_function(
         &mut self,
+        name: &str,
         func_def: &FunctionDef,
         args: &[Value],
     ) -> Result<Value, RuntimeError> {
@@ -398,13 +479,24 @@ impl Executor {
         
         // 检查参数数量
         if args.len() < required_params || args.len() > total_params {
-            return Err(RuntimeError::type_error(&format!(
-                "函数 {} 参数数量不匹配: 期望 {}-{} 个，实际 {} 个",
-                func_def.name,
-                required_params,
-                total_params,
-                args.len()
-            )));
+            let expected = if required_params == total_params {
+                total_params.to_string()
+            } else {
+                format!("{}-{}", required_params, total_params)
+            };
+            return Err(RuntimeError::argument_mismatch(&func_def.name, &expected, args.len()));
+        }
+        
+        // @memo 函数：相同参数直接返回缓存结果（按带包名的调用名区分不同包的同名函数）
+        let memo_key = if func_def.memoize() {
+            MemoCache::key(name, args)
+        } else {
+            None
+        };
+        if let Some(key) = &memo_key {
+            if let Some(cached) = self.memo_cache.borrow_mut().get(key) {
+                return Ok(cached);
+            }
         }
         
         // 保存当前上下文
@@ -443,6 +535,10 @@ impl Executor {
         // 恢复上下文
         self.context.variables = saved_vars;
         
+        if let Some(key) = memo_key {
+            self.memo_cache.borrow_mut().insert(key, result.clone());
+        }
+        
         Ok(result)
     }
 }
@@ -0,0 +1,229 @@
+// 分组执行器 - 多标的数据的拆分-计算-合并
+
+use super::{DataStreamExecutor, ErrorPolicy, check_passthrough};
+use super::data_stream::{rank_rows, require_numeric_column, zscore_rows};
+use crate::parser::Script;
+use crate::runtime::{Value, RuntimeError};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::thread;
+
+/// 分组执行器
+///
+/// 第一阶段按分组列（如 code）拆分输入，每组用独立的 DataStreamExecutor 执行，
+/// 时间序列访问（close[-1]、pct_change 等）只看到本组的历史，
+/// `_index` / `_total` 为组内位置和本组行数，`_global_index` / `_global_total` 为在全部输入中的位置；
+/// 第二阶段按时间列（如 date）对各组同一时刻的输出做截面计算（rank / zscore）。
+/// 设置并行度后各组在工作线程上执行，输出顺序与串行执行一致（按原始输入行顺序）。
+pub struct GroupedExecutor {
+    /// 脚本定义
+    script: Script,
+    /// 输入矩阵（保持原始行顺序）
+    input_matrix: Vec<HashMap<String, Value>>,
+    /// 分组列名
+    group_by: String,
+    /// 时间列名（截面计算按该列分批；None 时在全部输出行之间计算）
+    time_key: Option<String>,
+    /// 输出矩阵（与输入行一一对应）
+    output_matrix: Vec<HashMap<String, Value>>,
+    /// 行错误处理策略
+    error_policy: ErrorPolicy,
+    /// 并行执行的工作线程数（1 为串行）
+    parallelism: usize,
+    /// 出错的行（原始行索引，错误）
+    row_errors: Vec<(usize, RuntimeError)>,
+    /// 原样复制到输出行的输入列
+    passthrough: Vec<String>,
+}
+
+impl GroupedExecutor {
+    /// 创建分组执行器
+    pub fn new(script: Script, input_matrix: Vec<HashMap<String, Value>>, group_by: &str) -> Self {
+        GroupedExecutor {
+            script,
+            input_matrix,
+            group_by: group_by.to_string(),
+            time_key: None,
+            output_matrix: Vec::new(),
+            error_policy: ErrorPolicy::default(),
+            parallelism: 1,
+            row_errors: Vec::new(),
+            passthrough: Vec::new(),
+        }
+    }
+
+    /// 设置截面计算使用的时间列
+    pub fn set_time_key(&mut self, time_key: &str) {
+        self.time_key = Some(time_key.to_string());
+    }
+
+    /// 设置透传列（对每个分组生效），与 OUTPUT 声明的列同名时报错
+    pub fn set_passthrough(&mut self, columns: Vec<String>) -> Result<(), RuntimeError> {
+        if let Script::DataScript { output, .. } = &self.script {
+            check_passthrough(&columns, output)?;
+        }
+        self.passthrough = columns;
+        Ok(())
+    }
+
+    /// 设置行执行出错时的处理策略（对每个分组生效）
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = policy;
+    }
+
+    /// 设置并行执行的工作线程数（0 视为 1）
+    /// 脚本只访问本行和本组历史时，并行与串行的结果相同
+    pub fn set_parallelism(&mut self, threads: usize) {
+        self.parallelism = threads.max(1);
+    }
+
+    /// 出错的行（原始行索引，错误），仅在 SkipRow 策略下记录
+    pub fn row_errors(&self) -> &[(usize, RuntimeError)] {
+        &self.row_errors
+    }
+
+    /// 获取已计算的输出矩阵
+    pub fn get_output(&self) -> &[HashMap<String, Value>] {
+        &self.output_matrix
+    }
+
+    /// 按分组执行脚本，输出按原始输入行顺序合并
+    pub fn execute_all(&mut self) -> Result<Vec<HashMap<String, Value>>, RuntimeError> {
+        self.row_errors.clear();
+
+        let groups = group_indices(&self.input_matrix, &self.group_by);
+        let results = if self.parallelism > 1 && groups.len() > 1 {
+            self.run_parallel(&groups)
+        } else {
+            groups.iter()
+                .map(|indices| run_group(
+                    self.script.clone(),
+                    self.group_rows(indices),
+                    indices,
+                    self.input_matrix.len(),
+                    &self.passthrough,
+                    self.error_policy,
+                ))
+                .collect()
+        };
+
+        let mut output = vec![HashMap::new(); self.input_matrix.len()];
+        for (indices, result) in groups.iter().zip(results) {
+            let (group_output, group_errors) = result?;
+            for (&i, row) in indices.iter().zip(group_output) {
+                output[i] = row;
+            }
+            for (row_idx, e) in group_errors {
+                self.row_errors.push((indices[row_idx], e));
+            }
+        }
+        self.row_errors.sort_by_key(|(row_idx, _)| *row_idx);
+
+        self.output_matrix = output;
+        Ok(self.output_matrix.clone())
+    }
+
+    /// 各组在工作线程上执行，结果按组的顺序返回
+    fn run_parallel(&self, groups: &[Vec<usize>]) -> Vec<GroupResult> {
+        let jobs: Vec<_> = groups.iter()
+            .enumerate()
+            .map(|(g, indices)| (g, self.group_rows(indices)))
+            .collect();
+        let jobs = &Mutex::new(jobs);
+        let results = &Mutex::new(Vec::with_capacity(groups.len()));
+        let error_policy = self.error_policy;
+        let total = self.input_matrix.len();
+        let passthrough = &self.passthrough;
+
+        thread::scope(|scope| {
+            for _ in 0..self.parallelism.min(groups.len()) {
+                let script = self.script.clone();
+                scope.spawn(move || loop {
+                    let job = jobs.lock().map(|mut jobs| jobs.pop()).unwrap_or(None);
+                    let Some((g, rows)) = job else { break };
+                    let result = run_group(script.clone(), rows, &groups[g], total, passthrough, error_policy);
+                    if let Ok(mut results) = results.lock() {
+                        results.push((g, result));
+                    }
+                });
+            }
+        });
+
+        let mut results = std::mem::take(&mut *results.lock().unwrap_or_else(|e| e.into_inner()));
+        results.sort_by_key(|(g, _)| *g);
+        results.into_iter().map(|(_, result)| result).collect()
+    }
+
+    /// 取出一组的输入行
+    fn group_rows(&self, indices: &[usize]) -> Vec<HashMap<String, Value>> {
+        indices.iter().map(|&i| self.input_matrix[i].clone()).collect()
+    }
+
+    /// 截面排名：同一时刻的各组输出之间对 name 列升序排名，写入 `{name}_rank` 列
+    pub fn rank_column(&mut self, name: &str) -> Result<(), RuntimeError> {
+        self.apply_cross_section(name, rank_rows)
+    }
+
+    /// 截面标准分：同一时刻的各组输出之间计算 name 列的 z-score，写入 `{name}_zscore` 列
+    pub fn zscore_column(&mut self, name: &str) -> Result<(), RuntimeError> {
+        self.apply_cross_section(name, zscore_rows)
+    }
+
+    /// 按时间列把输出行分批，逐批应用截面计算
+    fn apply_cross_section(
+        &mut self,
+        name: &str,
+        apply: fn(&mut [&mut HashMap<String, Value>], &str),
+    ) -> Result<(), RuntimeError> {
+        require_numeric_column(&self.output_matrix.iter_mut().collect::<Vec<_>>(), name)?;
+
+        let batches = match &self.time_key {
+            Some(time_key) => group_indices(&self.input_matrix, time_key),
+            None => vec![(0..self.output_matrix.len()).collect()],
+        };
+
+        let mut rows: Vec<Option<&mut HashMap<String, Value>>> = self.output_matrix.iter_mut().map(Some).collect();
+        for indices in batches {
+            let mut batch: Vec<_> = indices.iter().filter_map(|&i| rows[i].take()).collect();
+            apply(&mut batch, name);
+        }
+        Ok(())
+    }
+}
+
+/// 一组的执行结果：输出行与出错的行（组内行索引）
+type GroupResult = Result<(Vec<HashMap<String, Value>>, Vec<(usize, RuntimeError)>), RuntimeError>;
+
+/// 用独立的数据流执行器执行一组（indices 为本组各行在原始输入中的索引）
+fn run_group(
+    script: Script,
+    rows: Vec<HashMap<String, Value>>,
+    indices: &[usize],
+    total: usize,
+    passthrough: &[String],
+    error_policy: ErrorPolicy,
+) -> GroupResult {
+    let mut executor = DataStreamExecutor::new(script, rows);
+    executor.set_error_policy(error_policy);
+    executor.set_global_rows(indices.to_vec(), total);
+    executor.set_passthrough(passthrough.to_vec())?;
+    let output = executor.execute_all()?;
+    Ok((output, executor.row_errors().to_vec()))
+}
+
+/// 按列值对行分组，返回各组的行索引（组按首次出现的顺序，组内保持原始顺序）
+fn group_indices(rows: &[HashMap<String, Value>], key: &str) -> Vec<Vec<usize>> {
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+
+    for (i, row) in rows.iter().enumerate() {
+        let group = row.get(key).map_or_else(|| "null".to_string(), |v| v.to_string());
+        let pos = *positions.entry(group).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[pos].push(i);
+    }
+
+    groups
+}
@@ -0,0 +1,47 @@
+// 资源限制 - 防止异常输入或脚本分配过多内存
+
+use crate::runtime::RuntimeError;
+
+/// 资源限制：超出时返回运行时错误，而不是耗尽内存
+///
+/// 默认值足够宽松，服务端可按租户设置更严格的限制。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceLimits {
+    /// 内置函数（Range、Array 等）生成的数组最大长度
+    pub max_array_len: usize,
+    /// DataStreamExecutor 的最大输入行数
+    pub max_input_rows: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        ResourceLimits {
+            max_array_len: 10_000_000,
+            max_input_rows: 10_000_000,
+        }
+    }
+}
+
+impl ResourceLimits {
+    /// 检查内置函数将要生成的数组长度
+    pub fn check_array_len(&self, name: &str, len: usize) -> Result<(), RuntimeError> {
+        if len > self.max_array_len {
+            return Err(RuntimeError::limit_exceeded(&format!(
+                "{} 生成的数组长度 {} 超过上限 {}",
+                name, len, self.max_array_len
+            )));
+        }
+        Ok(())
+    }
+
+    /// 检查输入行数
+    pub fn check_input_rows(&self, rows: usize) -> Result<(), RuntimeError> {
+        if rows > self.max_input_rows {
+            return Err(RuntimeError::limit_exceeded(&format!(
+                "输入行数 {} 超过上限 {}",
+                rows, self.max_input_rows
+            )));
+        }
+        Ok(())
+    }
+}
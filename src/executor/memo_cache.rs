@@ -0,0 +1,122 @@
+// 纯函数结果缓存 - 用于 @memo 标记的包函数
+
+use crate::runtime::Value;
+use std::collections::HashMap;
+
+/// 有界 LRU 缓存，键为 (函数名, 参数值)
+pub struct MemoCache {
+    /// 最大条目数
+    capacity: usize,
+    /// 键 -> (结果, 最近使用时间)
+    entries: HashMap<String, (Value, u64)>,
+    /// 逻辑时钟（每次访问递增）
+    clock: u64,
+    /// 命中次数
+    hits: usize,
+    /// 未命中次数
+    misses: usize,
+}
+
+impl MemoCache {
+    /// 创建缓存
+    pub fn new(capacity: usize) -> Self {
+        MemoCache {
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// 生成缓存键；参数中含 Lambda/函数时不可缓存，返回 None
+    pub fn key(name: &str, args: &[Value]) -> Option<String> {
+        if args.iter().any(|arg| matches!(arg, Value::Lambda { .. } | Value::Function(_))) {
+            return None;
+        }
+        Some(format!("{}{:?}", name, args))
+    }
+
+    /// 查询缓存，命中时刷新最近使用时间
+    pub fn get(&mut self, key: &str) -> Option<Value> {
+        self.clock += 1;
+        match self.entries.get_mut(key) {
+            Some((value, used)) => {
+                *used = self.clock;
+                self.hits += 1;
+                Some(value.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// 写入缓存，超出容量时淘汰最久未使用的条目
+    pub fn insert(&mut self, key: String, value: Value) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.clock += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.capacity {
+            let oldest = self.entries
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(k, _)| k.clone());
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+        self.entries.insert(key, (value, self.clock));
+    }
+
+    /// 命中次数
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// 未命中次数
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// 当前条目数
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// 是否为空
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl Default for MemoCache {
+    fn default() -> Self {
+        MemoCache::new(1024)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut cache = MemoCache::new(2);
+        let key = |n: f64| MemoCache::key("f", &[Value::Number(n)]).unwrap();
+
+        cache.insert(key(1.0), Value::Number(10.0));
+        cache.insert(key(2.0), Value::Number(20.0));
+        // 访问 1 后，2 成为最久未使用的条目
+        assert_eq!(cache.get(&key(1.0)), Some(Value::Number(10.0)));
+        cache.insert(key(3.0), Value::Number(30.0));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&key(2.0)), None);
+        assert_eq!(cache.get(&key(3.0)), Some(Value::Number(30.0)));
+        assert_eq!(cache.hits(), 2);
+        assert_eq!(cache.misses(), 1);
+    }
+}
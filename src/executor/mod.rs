@@ -4,8 +4,14 @@ mod context;
 mod context_pool;
 mod columnar_storage;
 mod data_stream;
+mod grouped;
 mod streaming;
+mod bar_builder;
 mod output_manager;
+mod memo_cache;
+mod cancel;
+mod sandbox;
+mod limits;
 mod expression;
 mod statement;
 mod builtin;
@@ -13,14 +19,25 @@ mod builtin;
 pub use context::ExecutionContext;
 pub use context_pool::{ContextPool, PoolConfig};
 pub use columnar_storage::ColumnarStorage;
-pub use data_stream::DataStreamExecutor;
+pub use data_stream::{DataStreamExecutor, ErrorPolicy};
+pub use grouped::GroupedExecutor;
 pub use streaming::StreamingExecutor;
-pub use output_manager::{OutputManager, OutputManagerConfig, OutputMode, OutputRow};
+pub use bar_builder::BarBuilder;
+pub use memo_cache::MemoCache;
+pub use cancel::CancelToken;
+pub use sandbox::SandboxConfig;
+pub use limits::ResourceLimits;
+pub use output_manager::{
+    OutputManager, OutputManagerConfig, OutputMode, OutputRow,
+    OutputQueue, OutputQueueConfig, BackpressurePolicy,
+};
 
-use data_stream::CURRENT_DATA_STREAM;
-use crate::parser::{Stmt, Script, FunctionDef, PrecisionSetting};
+use data_stream::{CURRENT_DATA_STREAM, TimeSeriesSource};
+use crate::parser::{Stmt, Script, FunctionDef, Parameter, PrecisionSetting, DivMode, NanMode, IndexMode, TypeAnnotation};
 use crate::runtime::{Value, RuntimeError};
 use std::collections::HashMap;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 /// 执行器
 pub struct Executor {
@@ -29,8 +46,46 @@ pub struct Executor {
     pub(crate) functions: HashMap<String, FunctionDef>,
     /// 包级变量
     pub(crate) package_vars: HashMap<String, Value>,
+    /// 正在执行的包函数所属的包（包内按名字调用同包函数）
+    pub(crate) current_package: Option<String>,
+    /// 运行时注入的只读参数（脚本中按变量名访问）
+    pub(crate) params: HashMap<String, Value>,
     /// 精度设置
     pub(crate) precision: Option<PrecisionSetting>,
+    /// 除法模式
+    pub(crate) div_mode: DivMode,
+    /// NaN/Infinity 处理模式
+    pub(crate) nan_mode: NanMode,
+    /// 数组越界访问模式
+    pub(crate) index_mode: IndexMode,
+    /// 是否允许脚本访问文件（load_csv 等）
+    pub(crate) file_access: bool,
+    /// 外部参考表缓存（路径 -> 表）
+    pub(crate) table_cache: Rc<RefCell<HashMap<String, Value>>>,
+    /// @memo 函数结果缓存
+    pub(crate) memo_cache: Rc<RefCell<MemoCache>>,
+    /// 取消令牌（None 表示不检查）
+    pub(crate) cancel_token: Option<CancelToken>,
+    /// 沙箱配置（None 表示不限制内置函数）
+    pub(crate) sandbox: Option<Rc<SandboxConfig>>,
+    /// 执行跟踪（None 表示未开启）
+    pub(crate) trace: Option<Vec<TraceEntry>>,
+    /// print / tap 的输出目标（None 时打印到标准输出）
+    pub(crate) print_sink: Option<PrintSink>,
+    /// 资源限制
+    pub(crate) limits: ResourceLimits,
+}
+
+/// print / tap 的输出目标，每次调用传入一行文本
+pub type PrintSink = Rc<dyn Fn(&str)>;
+
+/// 执行跟踪记录：一次赋值对应一条
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceEntry {
+    /// 被赋值的变量名
+    pub name: String,
+    /// 赋值结果
+    pub value: Value,
 }
 
 impl Executor {
@@ -39,22 +94,115 @@ impl Executor {
             context: ExecutionContext::new(),
             functions: HashMap::new(),
             package_vars: HashMap::new(),
+            current_package: None,
+            params: HashMap::new(),
             precision: None,
+            div_mode: DivMode::default(),
+            nan_mode: NanMode::default(),
+            index_mode: IndexMode::default(),
+            file_access: true,
+            table_cache: Rc::new(RefCell::new(HashMap::new())),
+            memo_cache: Rc::new(RefCell::new(MemoCache::default())),
+            cancel_token: None,
+            sandbox: None,
+            trace: None,
+            print_sink: None,
+            limits: ResourceLimits::default(),
         }
     }
     
+    /// 设置是否允许脚本访问文件（服务端可关闭）
+    pub fn set_file_access(&mut self, enabled: bool) {
+        self.file_access = enabled;
+    }
+    
+    /// 注入只读参数（如任务配置中的阈值），脚本中按变量名访问
+    /// 与脚本 INPUT 声明的列同名时，执行数据脚本会报错
+    pub fn set_param(&mut self, name: &str, value: Value) {
+        self.params.insert(name.to_string(), value);
+    }
+    
+    /// 设置取消令牌（嵌入场景下由外部中止执行）
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel_token = Some(token);
+    }
+    
+    /// 设置沙箱配置（禁用的内置函数被调用时报权限错误）
+    pub fn set_sandbox(&mut self, sandbox: SandboxConfig) {
+        self.sandbox = Some(Rc::new(sandbox));
+    }
+    
+    /// 设置资源限制（内置函数生成的数组长度等）
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+    
+    /// 设置 print / tap 的输出目标（默认打印到标准输出）
+    pub fn set_print_sink(&mut self, sink: impl Fn(&str) + 'static) {
+        self.print_sink = Some(Rc::new(sink));
+    }
+    
+    /// 输出一行 print / tap 文本
+    pub(crate) fn emit_print(&self, line: &str) {
+        match &self.print_sink {
+            Some(sink) => sink(line),
+            None => println!("{}", line),
+        }
+    }
+    
+    /// 检查点：令牌已取消或超时则中止
+    pub(crate) fn check_cancelled(&self) -> Result<(), RuntimeError> {
+        match &self.cancel_token {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+    
+    /// 开启/关闭执行跟踪（记录每次赋值的变量名和值）
+    pub fn set_trace(&mut self, enabled: bool) {
+        self.trace = if enabled { Some(Vec::new()) } else { None };
+    }
+    
+    /// 获取跟踪记录（未开启时为空）
+    pub fn trace(&self) -> &[TraceEntry] {
+        self.trace.as_deref().unwrap_or(&[])
+    }
+    
+    /// 记录一次赋值；设置了 DPLANG_DEBUG 环境变量时同时打印到 stderr
+    pub(crate) fn record_trace(&mut self, name: &str, value: &Value) {
+        if let Some(trace) = &mut self.trace {
+            if std::env::var_os("DPLANG_DEBUG").is_some() {
+                eprintln!("[trace] {} = {}", name, value);
+            }
+            trace.push(TraceEntry { name: name.to_string(), value: value.clone() });
+        }
+    }
+    
+    /// @memo 函数缓存的 (命中次数, 未命中次数)
+    pub fn memo_stats(&self) -> (usize, usize) {
+        let cache = self.memo_cache.borrow();
+        (cache.hits(), cache.misses())
+    }
+    
     /// 执行数据脚本
     pub fn execute_data_script(&mut self, script: &Script) -> Result<Option<Value>, RuntimeError> {
-        if let Script::DataScript { body, error_block, precision, .. } = script {
-            // 设置精度
-            self.precision = precision.clone();
+        if let Script::DataScript { input, body, error_block, precision, config, .. } = script {
+            for name in self.params.keys() {
+                check_param_name(name, input)?;
+            }
+            
+            // 设置精度（PRECISION 声明优先于 CONFIG 中的 precision）
+            self.precision = config.effective_precision(precision);
+            self.div_mode = config.div_mode.unwrap_or_default();
+            self.nan_mode = config.nan_mode.unwrap_or_default();
+            self.index_mode = config.index_mode.unwrap_or_default();
             
             // 尝试执行主体
             let result = self.execute_body(body);
             
-            // 如果有错误且定义了 ERROR 块，执行 ERROR 块
+            // 如果有错误且定义了 ERROR 块，执行 ERROR 块（取消/超时直接中止）
             if let Err(ref error) = result {
-                if let Some(error_stmts) = error_block {
+                if let (Some(error_stmts), false) = (error_block, error.is_cancelled()) {
                     // 设置错误信息变量
                     self.context.set("__error__".to_string(), Value::String(error.message.clone()));
                     
@@ -83,7 +231,28 @@ impl Executor {
         }
     }
     
-    /// 执行语句体
+    /// 对单行数据执行脚本，按 OUTPUT 声明返回命名结果
+    /// 没有历史数据，时间序列访问（如 pct_change）返回 null 或报错
+    pub fn run_row(script: &Script, row: &HashMap<String, Value>) -> Result<HashMap<String, Value>, RuntimeError> {
+        let (input, output) = match script {
+            Script::DataScript { input, output, .. } => (input, output),
+            _ => return Err(RuntimeError::type_error("期望数据脚本")),
+        };
+        
+        let mut executor = Executor::new();
+        for param in input {
+            let value = row.get(&param.name).cloned().unwrap_or(Value::Null);
+            executor.set_input(param.name.clone(), value);
+        }
+        
+        match executor.execute_data_script(script)? {
+            Some(result) => map_return_to_output(output, result),
+            None => Ok(HashMap::new()),
+        }
+    }
+    
+    /// 执行语句体：实际执行到的第一个 return 即为本行输出，其后的语句不再执行
+    /// （if/else 分支内的 return 经 execute_stmt 向外传递；没有执行到 return 时返回 None）
     pub(crate) fn execute_body(&mut self, body: &[Stmt]) -> Result<Option<Value>, RuntimeError> {
         for stmt in body {
             if let Some(result) = self.execute_stmt(stmt)? {
@@ -120,9 +289,27 @@ impl Executor {
         self.context.set(name, value);
     }
     
+    /// 按 NaN 处理模式检查数值结果（NaN/Infinity 转为 null 或报错）
+    pub(crate) fn check_finite(&self, value: Value) -> Result<Value, RuntimeError> {
+        match value {
+            Value::Number(n) if !n.is_finite() => match self.nan_mode {
+                NanMode::Null => Ok(Value::Null),
+                NanMode::Strict => Err(RuntimeError::non_finite()),
+            },
+            Value::Array(arr) => {
+                let result = arr.into_iter()
+                    .map(|v| self.check_finite(v))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Array(result))
+            }
+            _ => Ok(value),
+        }
+    }
+    
     /// 应用精度到值
-    fn apply_precision_to_value(&self, value: Value) -> Result<Value, RuntimeError> {
+    pub(crate) fn apply_precision_to_value(&self, value: Value) -> Result<Value, RuntimeError> {
         if let Some(ref precision) = self.precision {
+            let rounding = precision.rounding.unwrap_or_default();
             match value {
                 Value::Array(arr) => {
                     // 对数组中的每个元素应用精度
@@ -130,7 +317,7 @@ impl Executor {
                     for v in arr {
                         // 对 Decimal 类型应用精度
                         if matches!(v, Value::Decimal(_)) {
-                            result.push(v.apply_precision(precision.scale)?);
+                            result.push(v.apply_precision_with_mode(precision.scale, rounding)?);
                         } else {
                             result.push(v);
                         }
@@ -139,7 +326,7 @@ impl Executor {
                 }
                 Value::Decimal(_) => {
                     // 对 Decimal 应用精度
-                    value.apply_precision(precision.scale)
+                    value.apply_precision_with_mode(precision.scale, rounding)
                 }
                 _ => Ok(value), // 其他类型不变
             }
@@ -149,7 +336,15 @@ impl Executor {
     }
     
     /// 从 DataStreamExecutor 获取时间序列单值
+    /// offset 0 始终是当前行正在计算的值（执行上下文，包含本行之前的赋值），
+    /// offset ≥ 1 是已提交的历史行
     pub(crate) fn get_time_series_value(&self, var_name: &str, offset: usize) -> Option<Value> {
+        if offset == 0 {
+            if let Some(val) = self.context.get(var_name) {
+                return Some(val.clone());
+            }
+        }
+        
         CURRENT_DATA_STREAM.with(|cell| {
             if let Some(executor_ptr) = *cell.borrow() {
                 unsafe {
@@ -167,6 +362,18 @@ impl Executor {
         })
     }
     
+    /// 预读当前行之后第 offset 行的输入值（非因果，仅批量数据流支持）
+    pub(crate) fn get_forward_value(&self, var_name: &str, offset: usize) -> Result<Value, RuntimeError> {
+        CURRENT_DATA_STREAM.with(|cell| {
+            if let Some(executor_ptr) = *cell.borrow() {
+                let executor = unsafe { &*executor_ptr };
+                Ok(executor.get_input_forward(var_name, offset)?.unwrap_or(Value::Null))
+            } else {
+                Err(RuntimeError::type_error("forward 只能在数据流脚本中使用"))
+            }
+        })
+    }
+    
     /// 从 DataStreamExecutor 获取时间序列切片
     pub(crate) fn get_time_series_slice(
         &self,
@@ -183,13 +390,21 @@ impl Executor {
                     let start_offset = if start_idx < 0 { (-start_idx) as usize } else { 0 };
                     let end_offset = if end_idx < 0 { (-end_idx) as usize } else { 0 };
                     
-                    // 先尝试从输入获取
-                    if let Ok(val) = executor.get_input_slice(var_name, start_offset, end_offset) {
-                        return Ok(val);
+                    // 包含当前行时：历史部分取已提交的行，当前值取执行上下文（与 var[0] 一致）
+                    if end_offset == 0 {
+                        let mut values = if start_offset == 0 {
+                            Vec::new()
+                        } else {
+                            match Self::history_slice(executor, var_name, start_offset, 1)? {
+                                Value::Array(values) => values,
+                                other => vec![other],
+                            }
+                        };
+                        values.push(self.get_time_series_value(var_name, 0).unwrap_or(Value::Null));
+                        return Ok(Value::Array(values));
                     }
                     
-                    // 再尝试从输出获取
-                    executor.get_output_slice(var_name, start_offset, end_offset)
+                    Self::history_slice(executor, var_name, start_offset, end_offset)
                 }
             } else {
                 Err(RuntimeError::type_error("时间序列访问只能在数据流脚本中使用"))
@@ -197,15 +412,32 @@ impl Executor {
         })
     }
     
-    /// 获取内置变量 (_index, _total, _args, _args_names)
+    /// 已提交历史行的切片：当前输入行含该列时取输入，否则取输出
+    fn history_slice(
+        executor: &dyn TimeSeriesSource,
+        var_name: &str,
+        start_offset: usize,
+        end_offset: usize,
+    ) -> Result<Value, RuntimeError> {
+        if executor.get_input_history(var_name, 0).is_some() {
+            executor.get_input_slice(var_name, start_offset, end_offset)
+        } else {
+            executor.get_output_slice(var_name, start_offset, end_offset)
+        }
+    }
+    
+    /// 获取内置变量 (_index, _total, _global_index, _global_total, _args, _args_names)
+    /// 分组执行时 _index / _total 为组内位置和本组行数，_global_* 为在全部输入中的位置
     pub(crate) fn get_builtin_variable(&self, name: &str) -> Option<Value> {
         CURRENT_DATA_STREAM.with(|cell| {
             if let Some(executor_ptr) = *cell.borrow() {
                 unsafe {
                     let executor = &*executor_ptr;
                     match name {
-                        "_index" => Some(Value::Number(executor.get_current_index() as f64)),
-                        "_total" => Some(Value::Number(executor.get_total_rows() as f64)),
+                        "_index" => Some(Value::Integer(executor.get_current_index() as i64)),
+                        "_total" => Some(Value::Integer(executor.get_total_rows() as i64)),
+                        "_global_index" => Some(Value::Integer(executor.get_global_index() as i64)),
+                        "_global_total" => Some(Value::Integer(executor.get_global_total() as i64)),
                         "_args" => {
                             // 返回当前输入行的所有值
                             if let Some(row) = executor.get_current_row() {
@@ -236,5 +468,61 @@ impl Executor {
     }
 }
 
+/// 将 return 的结果映射到 OUTPUT 声明的列名
+/// 数组按位置与 OUTPUT 参数一一对应，长度必须与 OUTPUT 数量一致；
+/// 单个非数组值（return x）仅在 OUTPUT 只声明一列时允许；
+/// OUTPUT 只有一列 array 类型时，未再包一层的数组直接作为该列的值
+pub(crate) fn map_return_to_output(
+    output: &[Parameter],
+    result: Value,
+) -> Result<HashMap<String, Value>, RuntimeError> {
+    Ok(output.iter()
+        .map(|param| param.name.clone())
+        .zip(return_values(output, result)?)
+        .collect())
+}
+
+/// 把返回值拆成与 OUTPUT 列一一对应的值（按声明顺序）
+pub(crate) fn return_values(output: &[Parameter], result: Value) -> Result<Vec<Value>, RuntimeError> {
+    let single_array_column = matches!(output, [param] if param.type_annotation == Some(TypeAnnotation::Array));
+    let values = match result {
+        Value::Array(values) if single_array_column && !matches!(values.as_slice(), [Value::Array(_)]) => {
+            vec![Value::Array(values)]
+        }
+        Value::Array(values) => values,
+        value if output.len() == 1 => vec![value],
+        _ => return Err(RuntimeError::type_error(&format!(
+            "返回值必须是与 OUTPUT 对应的数组（OUTPUT 声明了 {} 列）",
+            output.len()
+        ))),
+    };
+    
+    if values.len() != output.len() {
+        return Err(RuntimeError::type_error(&format!(
+            "返回值数量 {} 与 OUTPUT 声明数量 {} 不匹配",
+            values.len(),
+            output.len()
+        )));
+    }
+    
+    Ok(values)
+}
+
+/// 注入的参数不能与 INPUT 声明的列同名
+pub(crate) fn check_param_name(name: &str, input: &[Parameter]) -> Result<(), RuntimeError> {
+    if input.iter().any(|param| param.name == name) {
+        return Err(RuntimeError::type_error(&format!("参数 {} 与 INPUT 声明的列同名", name)));
+    }
+    Ok(())
+}
+
+/// 透传到输出的输入列不能与 OUTPUT 声明的列同名
+pub(crate) fn check_passthrough(columns: &[String], output: &[Parameter]) -> Result<(), RuntimeError> {
+    if let Some(name) = columns.iter().find(|name| output.iter().any(|param| &param.name == *name)) {
+        return Err(RuntimeError::type_error(&format!("透传列 {} 与 OUTPUT 声明的列同名", name)));
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests;
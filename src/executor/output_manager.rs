@@ -5,6 +5,9 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{BufWriter, Write};
 use std::path::PathBuf;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
 /// 输出行类型
 pub type OutputRow = HashMap<String, Value>;
@@ -21,17 +24,29 @@ pub enum OutputMode {
     },
     /// 回调模式：每行调用回调函数
     Callback,
+    /// 按列值分区写入文件：key_column 的每个取值写入 dir 下的一个文件，
+    /// 文件名由 template 中的 {key} 替换得到（如 "{key}.csv"）
+    Partitioned {
+        dir: PathBuf,
+        key_column: String,
+        template: String,
+    },
 }
 
 /// 输出管理器配置
+///
+/// StreamToFile 模式下 write_row 先缓冲，满足以下任一条件时写入文件：
+/// 缓冲行数达到 buffer_size，或距上次刷新已超过 flush_interval。
+/// Partitioned 模式的缓冲与刷新规则相同，刷新时按分区列把行路由到各自的文件。
+/// finalize 总会写入剩余缓冲。InMemory / Callback 模式不刷新，行全部保留在内存中。
 #[derive(Clone)]
 pub struct OutputManagerConfig {
     /// 输出模式
     pub mode: OutputMode,
     /// 缓冲区大小（行数）
     pub buffer_size: usize,
-    /// 刷新间隔（行数）
-    pub flush_interval: usize,
+    /// 刷新间隔（时间）
+    pub flush_interval: Duration,
 }
 
 impl Default for OutputManagerConfig {
@@ -39,7 +54,7 @@ impl Default for OutputManagerConfig {
         OutputManagerConfig {
             mode: OutputMode::InMemory,
             buffer_size: 1000,
-            flush_interval: 1000,
+            flush_interval: Duration::from_secs(1),
         }
     }
 }
@@ -52,10 +67,14 @@ pub struct OutputManager {
     buffer: Vec<OutputRow>,
     /// 文件写入器
     file_writer: Option<BufWriter<File>>,
+    /// 分区写入器（文件名 -> 写入器）；不同分区键清洗后同名时写入同一个文件
+    partition_writers: HashMap<String, BufWriter<File>>,
     /// 已写入行数
     written_count: usize,
     /// 列名顺序（用于CSV输出）
     column_order: Vec<String>,
+    /// 上次刷新时间
+    last_flush: Instant,
 }
 
 impl OutputManager {
@@ -67,6 +86,11 @@ impl OutputManager {
                     .map_err(|e| RuntimeError::type_error(&format!("无法创建输出文件: {}", e)))?;
                 Some(BufWriter::with_capacity(*buffer_size * 1024, file))
             }
+            OutputMode::Partitioned { dir, .. } => {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| RuntimeError::type_error(&format!("无法创建输出目录: {}", e)))?;
+                None
+            }
             _ => None,
         };
 
@@ -74,8 +98,10 @@ impl OutputManager {
             config,
             buffer: Vec::new(),
             file_writer,
+            partition_writers: HashMap::new(),
             written_count: 0,
             column_order: Vec::new(),
+            last_flush: Instant::now(),
         })
     }
 
@@ -85,8 +111,10 @@ impl OutputManager {
             config: OutputManagerConfig::default(),
             buffer: Vec::new(),
             file_writer: None,
+            partition_writers: HashMap::new(),
             written_count: 0,
             column_order: Vec::new(),
+            last_flush: Instant::now(),
         }
     }
 
@@ -102,11 +130,13 @@ impl OutputManager {
             OutputMode::InMemory => {
                 self.buffer.push(row);
             }
-            OutputMode::StreamToFile { .. } => {
-                // 先缓冲，达到阈值后批量写入
+            OutputMode::StreamToFile { .. } | OutputMode::Partitioned { .. } => {
+                // 先缓冲，达到行数阈值或刷新间隔后批量写入
                 self.buffer.push(row);
                 
-                if self.buffer.len() >= self.config.flush_interval {
+                if self.buffer.len() >= self.config.buffer_size
+                    || self.last_flush.elapsed() >= self.config.flush_interval
+                {
                     self.flush_buffer()?;
                 }
             }
@@ -122,14 +152,35 @@ impl OutputManager {
 
     /// 刷新缓冲区到文件
     fn flush_buffer(&mut self) -> Result<(), RuntimeError> {
-        if let Some(ref mut writer) = self.file_writer {
+        if let OutputMode::Partitioned { dir, key_column, template } = &self.config.mode {
+            for row in &self.buffer {
+                let key = match row.get(key_column) {
+                    Some(Value::String(s)) => s.clone(),
+                    Some(value) => value.to_string(),
+                    None => "null".to_string(),
+                };
+                // 按清洗后的文件名区分写入器，避免 "a/b" 与 "a_b" 重复创建同一文件而覆盖已写入的行
+                let file_name = template.replace("{key}", &sanitize_file_key(&key));
+                let writer = match self.partition_writers.entry(file_name) {
+                    std::collections::hash_map::Entry::Occupied(entry) => entry.into_mut(),
+                    std::collections::hash_map::Entry::Vacant(entry) => {
+                        let path = dir.join(entry.key());
+                        let file = File::create(&path)
+                            .map_err(|e| RuntimeError::type_error(&format!("无法创建输出文件 {}: {}", path.display(), e)))?;
+                        entry.insert(BufWriter::new(file))
+                    }
+                };
+                Self::write_csv_line(writer, row, &self.column_order)?;
+            }
+            
+            for writer in self.partition_writers.values_mut() {
+                writer.flush()
+                    .map_err(|e| RuntimeError::type_error(&format!("刷新文件失败: {}", e)))?;
+            }
+        } else if let Some(ref mut writer) = self.file_writer {
             // 写入CSV格式
             for row in &self.buffer {
-                let line = Self::format_row_as_csv_static(row, &self.column_order);
-                writer.write_all(line.as_bytes())
-                    .map_err(|e| RuntimeError::type_error(&format!("写入文件失败: {}", e)))?;
-                writer.write_all(b"\n")
-                    .map_err(|e| RuntimeError::type_error(&format!("写入文件失败: {}", e)))?;
+                Self::write_csv_line(writer, row, &self.column_order)?;
             }
             
             writer.flush()
@@ -137,9 +188,19 @@ impl OutputManager {
         }
 
         self.buffer.clear();
+        self.last_flush = Instant::now();
         Ok(())
     }
 
+    /// 写入一行 CSV
+    fn write_csv_line(writer: &mut BufWriter<File>, row: &OutputRow, column_order: &[String]) -> Result<(), RuntimeError> {
+        let line = Self::format_row_as_csv_static(row, column_order);
+        writer.write_all(line.as_bytes())
+            .map_err(|e| RuntimeError::type_error(&format!("写入文件失败: {}", e)))?;
+        writer.write_all(b"\n")
+            .map_err(|e| RuntimeError::type_error(&format!("写入文件失败: {}", e)))
+    }
+
     /// 将行格式化为CSV（静态方法）
     fn format_row_as_csv_static(row: &OutputRow, column_order: &[String]) -> String {
         let mut values = Vec::new();
@@ -160,6 +221,7 @@ impl OutputManager {
     fn value_to_csv_string_static(value: &Value) -> String {
         match value {
             Value::Number(n) => n.to_string(),
+            Value::Integer(i) => i.to_string(),
             Value::String(s) => format!("\"{}\"", s.replace("\"", "\"\"")),
             Value::Bool(b) => b.to_string(),
             Value::Null => String::new(),
@@ -174,14 +236,15 @@ impl OutputManager {
     /// 完成输出并返回结果
     pub fn finalize(mut self) -> Result<Option<Vec<OutputRow>>, RuntimeError> {
         // 刷新剩余缓冲区
-        if !self.buffer.is_empty() && self.file_writer.is_some() {
+        let writes_files = self.file_writer.is_some() || matches!(self.config.mode, OutputMode::Partitioned { .. });
+        if !self.buffer.is_empty() && writes_files {
             self.flush_buffer()?;
         }
 
         // 根据模式返回结果
         match self.config.mode {
             OutputMode::InMemory => Ok(Some(self.buffer)),
-            OutputMode::StreamToFile { .. } => {
+            OutputMode::StreamToFile { .. } | OutputMode::Partitioned { .. } => {
                 // 流式模式不返回数据
                 Ok(None)
             }
@@ -199,12 +262,127 @@ impl OutputManager {
         self.buffer.len()
     }
 
+    /// 获取内存中收集的所有行（StreamToFile / Partitioned 模式下行已写入文件，返回 None）
+    pub fn rows(&self) -> Option<&[OutputRow]> {
+        match self.config.mode {
+            OutputMode::StreamToFile { .. } | OutputMode::Partitioned { .. } => None,
+            _ => Some(&self.buffer),
+        }
+    }
+
     /// 设置列顺序（用于控制CSV列顺序）
     pub fn set_column_order(&mut self, columns: Vec<String>) {
         self.column_order = columns;
     }
 }
 
+/// 分区键中不适合作为文件名的字符替换为下划线（防止路径穿越）
+fn sanitize_file_key(key: &str) -> String {
+    let sanitized: String = key
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    if sanitized.is_empty() {
+        "_".to_string()
+    } else {
+        sanitized
+    }
+}
+
+/// 输出队列满时的处理策略
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackpressurePolicy {
+    /// 阻塞生产者，直到写入端消费出空位（反压）
+    Block,
+    /// 丢弃新行并计数
+    DropNewest,
+}
+
+/// 有界输出队列配置
+#[derive(Clone, Debug)]
+pub struct OutputQueueConfig {
+    /// 队列容量（行数）
+    pub capacity: usize,
+    /// 队列满时的处理策略
+    pub policy: BackpressurePolicy,
+}
+
+impl Default for OutputQueueConfig {
+    fn default() -> Self {
+        OutputQueueConfig {
+            capacity: 1024,
+            policy: BackpressurePolicy::Block,
+        }
+    }
+}
+
+/// 有界输出队列：执行器与写入端之间的缓冲，写入端在独立线程中消费
+/// 队列中传递格式化后的行文本，输出格式由调用方决定，写入端只负责写出
+pub struct OutputQueue {
+    sender: SyncSender<String>,
+    writer: JoinHandle<usize>,
+    policy: BackpressurePolicy,
+    dropped: usize,
+}
+
+impl OutputQueue {
+    /// 创建队列并启动写入线程，sink 对每一行调用一次
+    pub fn spawn<F>(config: OutputQueueConfig, mut sink: F) -> Self
+    where
+        F: FnMut(String) + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::sync_channel::<String>(config.capacity);
+        let writer = std::thread::spawn(move || {
+            let mut written = 0;
+            for line in receiver {
+                sink(line);
+                written += 1;
+            }
+            written
+        });
+        
+        OutputQueue {
+            sender,
+            writer,
+            policy: config.policy,
+            dropped: 0,
+        }
+    }
+    
+    /// 推入一行，返回是否入队（DropNewest 策略下队列满时返回 false）
+    pub fn push(&mut self, line: String) -> Result<bool, RuntimeError> {
+        let closed = || RuntimeError::type_error("输出队列的写入端已关闭");
+        match self.policy {
+            BackpressurePolicy::Block => {
+                self.sender.send(line).map_err(|_| closed())?;
+                Ok(true)
+            }
+            BackpressurePolicy::DropNewest => match self.sender.try_send(line) {
+                Ok(()) => Ok(true),
+                Err(TrySendError::Full(_)) => {
+                    self.dropped += 1;
+                    Ok(false)
+                }
+                Err(TrySendError::Disconnected(_)) => Err(closed()),
+            },
+        }
+    }
+    
+    /// 已丢弃的行数
+    pub fn dropped_count(&self) -> usize {
+        self.dropped
+    }
+    
+    /// 关闭队列并等待写入端处理完剩余行，返回 (写入行数, 丢弃行数)
+    pub fn finish(self) -> Result<(usize, usize), RuntimeError> {
+        drop(self.sender);
+        let written = self.writer
+            .join()
+            .map_err(|_| RuntimeError::type_error("输出写入线程异常退出"))?;
+        Ok((written, self.dropped))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -243,8 +421,8 @@ mod tests {
                 path: temp_path.clone(),
                 buffer_size: 8,
             },
-            buffer_size: 1000,
-            flush_interval: 2,
+            buffer_size: 2,
+            flush_interval: Duration::from_secs(3600),
         };
         
         let mut manager = OutputManager::new(config).unwrap();
@@ -279,6 +457,65 @@ mod tests {
         fs::remove_file(temp_path).ok();
     }
 
+    #[test]
+    fn test_partitioned_mode_routes_rows_by_key() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = OutputManagerConfig {
+            mode: OutputMode::Partitioned {
+                dir: dir.path().to_path_buf(),
+                key_column: "code".to_string(),
+                template: "part_{key}.csv".to_string(),
+            },
+            buffer_size: 2,
+            flush_interval: Duration::from_secs(3600),
+        };
+        let mut manager = OutputManager::new(config).unwrap();
+        
+        for (code, close) in [("SH600000", 1.0), ("../evil", 2.0), ("SH600000", 3.0)] {
+            let mut row = HashMap::new();
+            row.insert("code".to_string(), Value::String(code.to_string()));
+            row.insert("close".to_string(), Value::Number(close));
+            manager.write_row(row).unwrap();
+        }
+        assert!(manager.finalize().unwrap().is_none());
+        
+        let mut files: Vec<_> = fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, vec!["part_SH600000.csv", "part____evil.csv"]);
+        
+        let contents = fs::read_to_string(dir.path().join("part_SH600000.csv")).unwrap();
+        assert_eq!(contents, "1,\"SH600000\"\n3,\"SH600000\"\n");
+        let contents = fs::read_to_string(dir.path().join("part____evil.csv")).unwrap();
+        assert_eq!(contents, "2,\"../evil\"\n");
+    }
+
+    #[test]
+    fn test_partitioned_keys_sharing_a_file_name_are_not_truncated() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = OutputManagerConfig {
+            mode: OutputMode::Partitioned {
+                dir: dir.path().to_path_buf(),
+                key_column: "code".to_string(),
+                template: "part_{key}.csv".to_string(),
+            },
+            buffer_size: 1,
+            flush_interval: Duration::from_secs(3600),
+        };
+        let mut manager = OutputManager::new(config).unwrap();
+        
+        for code in ["a/b", "a_b", "a/b"] {
+            let mut row = HashMap::new();
+            row.insert("code".to_string(), Value::String(code.to_string()));
+            manager.write_row(row).unwrap();
+        }
+        manager.finalize().unwrap();
+        
+        let contents = fs::read_to_string(dir.path().join("part_a_b.csv")).unwrap();
+        assert_eq!(contents, "\"a/b\"\n\"a_b\"\n\"a/b\"\n");
+    }
+
     #[test]
     fn test_value_to_csv_string() {
         assert_eq!(OutputManager::value_to_csv_string_static(&Value::Number(123.45)), "123.45");
@@ -296,8 +533,8 @@ mod tests {
                 path: temp_path.clone(),
                 buffer_size: 8,
             },
-            buffer_size: 1000,
-            flush_interval: 3, // 每3行刷新一次
+            buffer_size: 3, // 每3行刷新一次
+            flush_interval: Duration::from_secs(3600),
         };
         
         let mut manager = OutputManager::new(config).unwrap();
@@ -317,4 +554,123 @@ mod tests {
         // 清理测试文件
         fs::remove_file(temp_path).ok();
     }
+
+    #[test]
+    fn test_rows_survive_buffer_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rows.csv");
+        let row = |i: usize| -> OutputRow {
+            vec![("value".to_string(), Value::Number(i as f64))].into_iter().collect()
+        };
+        
+        // 文件模式：10 行跨越多个 3 行缓冲边界，finalize 写入剩余的 1 行
+        let config = OutputManagerConfig {
+            mode: OutputMode::StreamToFile { path: path.clone(), buffer_size: 8 },
+            buffer_size: 3,
+            flush_interval: Duration::from_secs(3600),
+        };
+        let mut manager = OutputManager::new(config).unwrap();
+        for i in 0..10 {
+            manager.write_row(row(i)).unwrap();
+        }
+        assert_eq!(manager.buffer_size(), 1);
+        assert!(manager.rows().is_none());
+        assert!(manager.finalize().unwrap().is_none());
+        
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        let expected: Vec<String> = (0..10).map(|i| i.to_string()).collect();
+        assert_eq!(lines, expected);
+        
+        // 内存模式：不受缓冲阈值影响，全部保留
+        let config = OutputManagerConfig {
+            buffer_size: 3,
+            ..OutputManagerConfig::default()
+        };
+        let mut manager = OutputManager::new(config).unwrap();
+        for i in 0..10 {
+            manager.write_row(row(i)).unwrap();
+        }
+        assert_eq!(manager.rows().unwrap().len(), 10);
+        assert_eq!(manager.rows().unwrap()[9], row(9));
+        assert_eq!(manager.finalize().unwrap().unwrap().len(), 10);
+    }
+
+    #[test]
+    fn test_flush_interval_is_time_based() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("interval.csv");
+        
+        // 缓冲行数阈值很大，但刷新间隔为 0，每次写入都会刷新
+        let config = OutputManagerConfig {
+            mode: OutputMode::StreamToFile { path: path.clone(), buffer_size: 8 },
+            buffer_size: 1000,
+            flush_interval: Duration::ZERO,
+        };
+        let mut manager = OutputManager::new(config).unwrap();
+        for i in 0..3 {
+            let mut row = HashMap::new();
+            row.insert("value".to_string(), Value::Number(i as f64));
+            manager.write_row(row).unwrap();
+            assert_eq!(manager.buffer_size(), 0);
+        }
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 3);
+        manager.finalize().unwrap();
+    }
+
+    #[test]
+    fn test_output_queue_block_respects_capacity() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+        
+        let pending = Arc::new(AtomicUsize::new(0));
+        let max_pending = Arc::new(AtomicUsize::new(0));
+        
+        let config = OutputQueueConfig { capacity: 2, policy: BackpressurePolicy::Block };
+        let sink_pending = Arc::clone(&pending);
+        let mut queue = OutputQueue::spawn(config, move |_line| {
+            // 模拟慢速写入端
+            std::thread::sleep(Duration::from_millis(5));
+            sink_pending.fetch_sub(1, Ordering::SeqCst);
+        });
+        
+        for i in 0..20 {
+            let now = pending.fetch_add(1, Ordering::SeqCst) + 1;
+            max_pending.fetch_max(now, Ordering::SeqCst);
+            assert!(queue.push(format!("row {}", i)).unwrap());
+        }
+        
+        let (written, dropped) = queue.finish().unwrap();
+        assert_eq!(written, 20);
+        assert_eq!(dropped, 0);
+        // 队列中最多 capacity 行，另有 1 行正在写入、1 行正在推入
+        assert!(max_pending.load(Ordering::SeqCst) <= 2 + 2);
+    }
+    
+    #[test]
+    fn test_output_queue_drop_newest() {
+        use std::sync::mpsc;
+        
+        // 写入端阻塞直到收到信号，队列必然被填满
+        let (release, wait) = mpsc::channel::<()>();
+        let config = OutputQueueConfig { capacity: 2, policy: BackpressurePolicy::DropNewest };
+        let mut queue = OutputQueue::spawn(config, move |_line| {
+            let _ = wait.recv();
+        });
+        
+        let accepted = (0..10)
+            .filter(|i| queue.push(format!("row {}", i)).unwrap())
+            .count();
+        
+        assert!(accepted <= 3);
+        assert_eq!(queue.dropped_count(), 10 - accepted);
+        
+        for _ in 0..accepted {
+            release.send(()).unwrap();
+        }
+        let (written, dropped) = queue.finish().unwrap();
+        assert_eq!(written, accepted);
+        assert_eq!(dropped, 10 - accepted);
+    }
 }
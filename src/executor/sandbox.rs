@@ -0,0 +1,59 @@
+// 沙箱配置 - 多租户嵌入场景下禁用访问文件、输出等副作用的内置函数
+
+use crate::runtime::RuntimeError;
+use std::collections::HashSet;
+
+/// 默认禁用的内置函数：读文件、打印到标准输出
+const UNSAFE_BUILTINS: &[&str] = &["load_csv", "print", "tap"];
+
+/// 沙箱配置：列出禁止脚本调用的内置函数
+///
+/// 执行器在调用内置函数前检查；`SemanticAnalyzer::with_sandbox` 可在执行前拒绝脚本。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SandboxConfig {
+    /// 禁用的内置函数名
+    disallowed: HashSet<String>,
+}
+
+impl SandboxConfig {
+    /// 创建空配置（不禁用任何函数）
+    pub fn new() -> Self {
+        SandboxConfig::default()
+    }
+
+    /// 默认的安全配置：禁用访问文件和标准输出的内置函数
+    pub fn safe() -> Self {
+        UNSAFE_BUILTINS.iter().fold(SandboxConfig::new(), |config, name| config.disallow(name))
+    }
+
+    /// 禁用内置函数
+    pub fn disallow(mut self, name: &str) -> Self {
+        self.disallowed.insert(name.to_string());
+        self
+    }
+
+    /// 重新允许内置函数
+    pub fn allow(mut self, name: &str) -> Self {
+        self.disallowed.remove(name);
+        self
+    }
+
+    /// 是否允许调用该内置函数
+    pub fn is_allowed(&self, name: &str) -> bool {
+        !self.disallowed.contains(name)
+    }
+
+    /// 禁用的内置函数名
+    pub fn disallowed(&self) -> impl Iterator<Item = &str> {
+        self.disallowed.iter().map(String::as_str)
+    }
+
+    /// 调用前检查：被禁用则返回权限错误
+    pub fn check(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.is_allowed(name) {
+            Ok(())
+        } else {
+            Err(RuntimeError::permission_denied(name))
+        }
+    }
+}
@@ -1,16 +1,18 @@
 // 语句执行逻辑
 
 use super::Executor;
-use crate::parser::Stmt;
+use crate::parser::{DestructurePattern, Stmt};
 use crate::runtime::{Value, RuntimeError};
 
 impl Executor {
     /// 执行语句
     pub(crate) fn execute_stmt(&mut self, stmt: &Stmt) -> Result<Option<Value>, RuntimeError> {
+        self.check_cancelled()?;
+        
         match stmt {
             Stmt::Assignment { name, value, .. } => {
                 let val = self.execute_expr(value)?;
-                self.context.set(name.clone(), val);
+                self.assign(name, val)?;
                 Ok(None)
             }
             Stmt::Return(expr) => {
@@ -39,24 +41,43 @@ impl Executor {
                 Ok(None)
             }
             Stmt::Destructure { pattern, value } => {
-                let val = self.execute_expr(value)?;
-                if let Value::Array(arr) = val {
-                    for (i, p) in pattern.iter().enumerate() {
-                        match p {
-                            crate::parser::DestructurePattern::Identifier(name) => {
-                                if i < arr.len() {
-                                    self.context.set(name.clone(), arr[i].clone());
-                                }
-                            }
-                            crate::parser::DestructurePattern::Ignore => {
-                                // 忽略
-                            }
-                            crate::parser::DestructurePattern::Spread(name) => {
-                                // 收集剩余元素
-                                let rest: Vec<Value> = arr.iter().skip(i).cloned().collect();
-                                self.context.set(name.clone(), Value::Array(rest));
-                                break;
-                            }
+                let arr = match self.execute_expr(value)? {
+                    Value::Array(arr) => arr,
+                    other => {
+                        return Err(RuntimeError::type_error(&format!("解构赋值需要数组，实际为: {}", other)));
+                    }
+                };
+                
+                // 展开之前的位置从头对应，之后的位置从尾对应，展开绑定中间剩余元素
+                let spread_pos = pattern.iter().position(|p| matches!(p, DestructurePattern::Spread(_)));
+                let tail_len = spread_pos.map_or(0, |pos| pattern.len() - pos - 1);
+                
+                for (i, p) in pattern.iter().enumerate() {
+                    let index = match spread_pos {
+                        Some(pos) if i > pos => (arr.len() + i).checked_sub(pattern.len()).filter(|&idx| idx >= pos),
+                        _ => Some(i),
+                    };
+                    let element = index.and_then(|idx| arr.get(idx)).cloned().unwrap_or(Value::Null);
+                    
+                    match p {
+                        DestructurePattern::Identifier(name) => {
+                            self.assign(name, element)?;
+                        }
+                        DestructurePattern::Default(name, default) => {
+                            let value = if element.is_null() {
+                                self.execute_expr(default)?
+                            } else {
+                                element
+                            };
+                            self.assign(name, value)?;
+                        }
+                        DestructurePattern::Ignore => {
+                            // 忽略
+                        }
+                        DestructurePattern::Spread(name) => {
+                            let end = arr.len().saturating_sub(tail_len).max(i);
+                            let rest = arr.get(i..end).map(|s| s.to_vec()).unwrap_or_default();
+                            self.assign(name, Value::Array(rest))?;
                         }
                     }
                 }
@@ -64,4 +85,14 @@ impl Executor {
             }
         }
     }
+    
+    /// 变量赋值（开启跟踪时记录），注入的参数只读
+    fn assign(&mut self, name: &str, value: Value) -> Result<(), RuntimeError> {
+        if self.params.contains_key(name) {
+            return Err(RuntimeError::type_error(&format!("参数 {} 是只读的，不能赋值", name)));
+        }
+        self.record_trace(name, &value);
+        self.context.set(name.to_string(), value);
+        Ok(())
+    }
 }
@@ -1,12 +1,14 @@
 // 流式执行器 - 支持增量 tick 推送
 
-use super::{Executor, ExecutionContext};
-use crate::parser::{Script, PrecisionSetting};
+use super::{Executor, BarBuilder, MemoCache, CancelToken, SandboxConfig, ResourceLimits, PrintSink, ExecutionContext, map_return_to_output};
+use crate::parser::{Script, PrecisionSetting, DivMode, NanMode, IndexMode, is_exported};
 use crate::runtime::{Value, RuntimeError};
 use crate::package_loader::PackageLoader;
 use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::cell::RefCell;
 
-use super::data_stream::CURRENT_DATA_STREAM;
+use super::data_stream::{CURRENT_DATA_STREAM, TimeSeriesSource};
 
 /// 实时流式执行器（支持增量 tick 推送）
 pub struct StreamingExecutor {
@@ -28,17 +30,57 @@ pub struct StreamingExecutor {
     /// 精度设置
     precision: Option<PrecisionSetting>,
     
+    /// 除法模式
+    div_mode: DivMode,
+    
+    /// NaN/Infinity 处理模式
+    nan_mode: NanMode,
+    
+    /// 数组越界访问模式
+    index_mode: IndexMode,
+    
+    /// 是否允许脚本访问文件（load_csv 等）
+    file_access: bool,
+    
+    /// 外部参考表缓存（所有行共享）
+    table_cache: Rc<RefCell<HashMap<String, Value>>>,
+    
+    /// @memo 函数结果缓存（所有行共享）
+    memo_cache: Rc<RefCell<MemoCache>>,
+    
+    /// 取消令牌（所有行共享）
+    cancel_token: Option<CancelToken>,
+    
+    /// 沙箱配置（所有行共享）
+    sandbox: Option<Rc<SandboxConfig>>,
+    
+    /// print / tap 的输出目标（所有行共享）
+    print_sink: Option<PrintSink>,
+    
+    /// 资源限制
+    limits: ResourceLimits,
+    
     /// 导入的包
     packages: HashMap<String, HashMap<String, Value>>,
+    
+    /// K 线合成前置阶段（设置后 tick 先聚合为 bar，只有完成的 bar 进入脚本）
+    bar_builder: Option<BarBuilder>,
 }
 
 impl StreamingExecutor {
     /// 创建流式执行器
     pub fn new(script: Script, window_size: usize) -> Self {
-        let precision = if let Script::DataScript { precision, .. } = &script {
-            precision.clone()
+        // 提取精度和各运算模式（PRECISION 声明优先于 CONFIG 中的 precision）
+        let (precision, div_mode, nan_mode, index_mode) = if let Script::DataScript { precision, config, .. } = &script {
+            let precision = config.effective_precision(precision);
+            (
+                precision,
+                config.div_mode.unwrap_or_default(),
+                config.nan_mode.unwrap_or_default(),
+                config.index_mode.unwrap_or_default(),
+            )
         } else {
-            None
+            (None, DivMode::default(), NanMode::default(), IndexMode::default())
         };
         
         StreamingExecutor {
@@ -48,10 +90,97 @@ impl StreamingExecutor {
             window_size,
             current_index: 0,
             precision,
+            div_mode,
+            nan_mode,
+            index_mode,
+            file_access: true,
+            table_cache: Rc::new(RefCell::new(HashMap::new())),
+            memo_cache: Rc::new(RefCell::new(MemoCache::default())),
+            cancel_token: None,
+            sandbox: None,
+            print_sink: None,
+            limits: ResourceLimits::default(),
             packages: HashMap::new(),
+            bar_builder: None,
         }
     }
     
+    /// 设置是否允许脚本访问文件（服务端可关闭）
+    pub fn set_file_access(&mut self, enabled: bool) {
+        self.file_access = enabled;
+    }
+    
+    /// 覆盖脚本中的精度设置（如命令行 --precision）
+    pub fn set_precision(&mut self, precision: Option<PrecisionSetting>) {
+        self.precision = precision;
+    }
+    
+    /// 覆盖脚本中的除法模式（如命令行 --div-mode）
+    pub fn set_div_mode(&mut self, div_mode: DivMode) {
+        self.div_mode = div_mode;
+    }
+    
+    /// 覆盖脚本中的数组越界访问模式
+    pub fn set_index_mode(&mut self, index_mode: IndexMode) {
+        self.index_mode = index_mode;
+    }
+    
+    /// 设置沙箱配置（所有行共享，禁用的内置函数被调用时报权限错误）
+    pub fn set_sandbox(&mut self, sandbox: SandboxConfig) {
+        self.sandbox = Some(Rc::new(sandbox));
+    }
+    
+    /// 设置资源限制（内置函数生成的数组长度）
+    pub fn set_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+    
+    /// 设置 print / tap 的输出目标（所有行共享，默认打印到标准输出）
+    pub fn set_print_sink(&mut self, sink: impl Fn(&str) + 'static) {
+        self.print_sink = Some(Rc::new(sink));
+    }
+    
+    /// 设置 K 线合成前置阶段：push_tick 推入的 tick 先聚合，bar 完成时才执行脚本
+    pub fn set_bar_builder(&mut self, builder: BarBuilder) {
+        self.bar_builder = Some(builder);
+    }
+    
+    /// 设置取消令牌（每条语句和 map/filter/reduce 迭代时检查）
+    pub fn set_cancel_token(&mut self, token: CancelToken) {
+        self.cancel_token = Some(token);
+    }
+    
+    /// 替换运行中的脚本（如配置热加载）
+    /// INPUT/OUTPUT、导入的包和历史访问的变量都不变时保留窗口历史并返回 true，
+    /// 否则清空历史从头开始并返回 false。导入的包不会重新加载。
+    pub fn reload_script(&mut self, script: Script) -> bool {
+        let old_info = self.script.describe();
+        let new_info = script.describe();
+        let same_shape = old_info.inputs == new_info.inputs
+            && old_info.outputs == new_info.outputs
+            && old_info.imports == new_info.imports
+            && old_info.history_vars == new_info.history_vars;
+        
+        if let Script::DataScript { precision, config, .. } = &script {
+            self.precision = config.effective_precision(precision);
+            self.div_mode = config.div_mode.unwrap_or_default();
+            self.nan_mode = config.nan_mode.unwrap_or_default();
+            self.index_mode = config.index_mode.unwrap_or_default();
+        }
+        self.script = script;
+        
+        // 函数体可能已变化，旧的缓存结果不再可信
+        *self.memo_cache.borrow_mut() = MemoCache::default();
+        
+        if !same_shape {
+            self.input_window.clear();
+            self.output_window.clear();
+            self.current_index = 0;
+        }
+        
+        same_shape
+    }
+    
     /// 创建流式执行器（带包加载）
     pub fn new_with_packages(
         script: Script,
@@ -97,17 +226,57 @@ impl StreamingExecutor {
     }
     
     /// 推送单个 tick 数据，执行计算，返回输出结果
+    /// 设置了 K 线合成时，只有 tick 使上一根 bar 完成时才执行脚本
     pub fn push_tick(&mut self, tick_data: HashMap<String, Value>) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+        match &mut self.bar_builder {
+            Some(builder) => match builder.push(&tick_data)? {
+                Some(bar) => self.push_row(bar),
+                None => Ok(None),
+            },
+            None => self.push_row(tick_data),
+        }
+    }
+    
+    /// 流结束时调用：K 线合成配置为输出未完成 bar 时，对最后一根 bar 执行脚本
+    pub fn finish(&mut self) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+        let partial = match &mut self.bar_builder {
+            Some(builder) if builder.emits_partial() => builder.flush(),
+            _ => None,
+        };
+        match partial {
+            Some(bar) => self.push_row(bar),
+            None => Ok(None),
+        }
+    }
+    
+    /// 对一行输入执行脚本并更新历史窗口
+    fn push_row(&mut self, tick_data: HashMap<String, Value>) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+        // 当前 tick 先进入输入窗口，作为时间序列的当前行（offset 0）
+        self.input_window.push_back(tick_data);
+        
         // 设置线程局部变量（用于 ref 函数访问）
         CURRENT_DATA_STREAM.with(|cell| {
-            *cell.borrow_mut() = Some(self as *const _ as *const super::DataStreamExecutor);
+            *cell.borrow_mut() = Some(self as &dyn TimeSeriesSource as *const dyn TimeSeriesSource);
         });
         
         // 执行单个 tick
-        let result = self.execute_tick(&tick_data)?;
+        let result = self.execute_tick();
+        
+        // 清理线程局部变量（出错时也要清理）
+        CURRENT_DATA_STREAM.with(|cell| {
+            *cell.borrow_mut() = None;
+        });
+        
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // 出错的 tick 不计入历史
+                self.input_window.pop_back();
+                return Err(e);
+            }
+        };
         
         // 更新窗口：添加到历史
-        self.input_window.push_back(tick_data);
         if let Some(ref output) = result {
             self.output_window.push_back(output.clone());
         }
@@ -122,16 +291,26 @@ impl StreamingExecutor {
         
         self.current_index += 1;
         
-        // 清理线程局部变量
-        CURRENT_DATA_STREAM.with(|cell| {
-            *cell.borrow_mut() = None;
-        });
-        
         Ok(result)
     }
     
-    /// 执行单个 tick 的计算
-    fn execute_tick(&self, tick_data: &HashMap<String, Value>) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+    /// 预热：按顺序灌入历史行以填充窗口，不返回输出
+    /// 用于切换标的或断点续算，使第一个实时 tick 就有完整历史
+    /// 历史行直接进入脚本，不经过 K 线合成
+    pub fn prime(&mut self, rows: Vec<HashMap<String, Value>>) -> Result<(), RuntimeError> {
+        for row in rows {
+            self.push_row(row)?;
+        }
+        Ok(())
+    }
+    
+    /// 执行单个 tick 的计算（当前 tick 为输入窗口的最后一行）
+    fn execute_tick(&self) -> Result<Option<HashMap<String, Value>>, RuntimeError> {
+        let tick_data = match self.input_window.back() {
+            Some(tick) => tick,
+            None => return Ok(None),
+        };
+        
         if let Script::DataScript { input, body, output, .. } = &self.script {
             let mut context = ExecutionContext::new();
             
@@ -149,7 +328,20 @@ impl StreamingExecutor {
                 context,
                 functions: HashMap::new(),
                 package_vars: HashMap::new(),
+                current_package: None,
+                params: HashMap::new(),
                 precision: self.precision.clone(),
+                div_mode: self.div_mode,
+                nan_mode: self.nan_mode,
+                index_mode: self.index_mode,
+                file_access: self.file_access,
+                table_cache: Rc::clone(&self.table_cache),
+                memo_cache: Rc::clone(&self.memo_cache),
+                cancel_token: self.cancel_token.clone(),
+                sandbox: self.sandbox.clone(),
+                print_sink: self.print_sink.clone(),
+                limits: self.limits,
+                trace: None,
             };
             
             // 注入包数据
@@ -162,38 +354,31 @@ impl StreamingExecutor {
             
             let result = executor.execute_body(body)?;
             
-            // 收集输出
-            if let Some(Value::Array(output_values)) = result {
-                let mut output_row = HashMap::new();
-                
-                for (i, param) in output.iter().enumerate() {
-                    if let Some(value) = output_values.get(i) {
-                        output_row.insert(param.name.clone(), value.clone());
-                    }
-                }
-                
-                return Ok(Some(output_row));
+            // 收集输出（应用精度后按位置映射到 OUTPUT 列名）
+            if let Some(result) = result {
+                let result = executor.apply_precision_to_value(result)?;
+                return Ok(Some(map_return_to_output(output, result)?));
             }
         }
         
         Ok(None)
     }
     
-    /// 获取输入历史（用于 ref 函数）
+    /// 获取输入历史（offset 0 为当前 tick，1 为上一个 tick）
     pub fn get_input_history(&self, name: &str, offset: usize) -> Option<Value> {
         let window_len = self.input_window.len();
-        if offset == 0 || offset > window_len {
+        if offset >= window_len {
             return None;
         }
         
-        let target_index = window_len - offset;
+        let target_index = window_len - 1 - offset;
         self.input_window
             .get(target_index)
             .and_then(|row| row.get(name))
             .cloned()
     }
     
-    /// 获取输出历史
+    /// 获取输出历史（输出窗口不含当前 tick，offset 1 为上一个输出）
     pub fn get_output_history(&self, name: &str, offset: usize) -> Option<Value> {
         if offset == 0 {
             return None;
@@ -213,19 +398,19 @@ impl StreamingExecutor {
     
     /// 执行包脚本一次
     fn execute_package_once(&self, package_script: &Script) -> Result<HashMap<String, Value>, RuntimeError> {
-        if let Script::Package { variables, functions, .. } = package_script {
+        if let Script::Package { exports, variables, functions, .. } = package_script {
             let mut package_executor = Executor::new();
             package_executor.execute_package_script(package_script)?;
             
             let mut package_data = HashMap::new();
             
-            for var_def in variables {
+            for var_def in variables.iter().filter(|v| is_exported(exports.as_deref(), &v.name)) {
                 if let Some(value) = package_executor.package_vars.get(&var_def.name) {
                     package_data.insert(var_def.name.clone(), value.clone());
                 }
             }
             
-            for func_def in functions {
+            for func_def in functions.iter().filter(|f| is_exported(exports.as_deref(), &f.name)) {
                 let func_value = Value::Function(Box::new(func_def.clone()));
                 package_data.insert(func_def.name.clone(), func_value);
             }
@@ -246,3 +431,51 @@ impl StreamingExecutor {
         self.window_size
     }
 }
+
+impl TimeSeriesSource for StreamingExecutor {
+    fn get_input_history(&self, name: &str, offset: usize) -> Option<Value> {
+        StreamingExecutor::get_input_history(self, name, offset)
+    }
+    
+    fn get_output_history(&self, name: &str, offset: usize) -> Option<Value> {
+        StreamingExecutor::get_output_history(self, name, offset)
+    }
+    
+    /// 窗口外的历史按 null 填充，与批量执行器一致
+    fn get_input_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
+        let column_data = (end_offset..=start_offset)
+            .rev()
+            .map(|offset| self.get_input_history(name, offset).unwrap_or(Value::Null))
+            .collect();
+        Ok(Value::Array(column_data))
+    }
+    
+    fn get_output_slice(&self, name: &str, start_offset: usize, end_offset: usize) -> Result<Value, RuntimeError> {
+        if end_offset == 0 {
+            return Err(RuntimeError::type_error("无法获取当前行的输出值"));
+        }
+        let column_data = (end_offset..=start_offset)
+            .rev()
+            .map(|offset| self.get_output_history(name, offset).unwrap_or(Value::Null))
+            .collect();
+        Ok(Value::Array(column_data))
+    }
+    
+    fn get_current_index(&self) -> usize {
+        self.current_index
+    }
+    
+    /// 流式场景没有总行数，返回已接收的 tick 数（含当前 tick）
+    fn get_total_rows(&self) -> usize {
+        self.current_index + 1
+    }
+    
+    fn get_current_row(&self) -> Option<&HashMap<String, Value>> {
+        self.input_window.back()
+    }
+    
+    /// 实时数据没有后续行
+    fn get_input_forward(&self, _name: &str, _offset: usize) -> Result<Option<Value>, RuntimeError> {
+        Err(RuntimeError::type_error("forward 在流式执行中不可用（无法预读后续行）"))
+    }
+}
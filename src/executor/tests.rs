@@ -263,6 +263,36 @@ return [result]
     }
 }
 
+#[test]
+fn test_decimal_aggregates_stay_exact() {
+    let source = r#"
+-- INPUT a:decimal, b:decimal --
+-- OUTPUT total:decimal, high:decimal, low:decimal, mixed:number --
+
+return [sum([a, b]), max(a, null, b), min([a, b]), sum([a, 1])]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    
+    executor.set_input("a".to_string(), Value::Decimal(Decimal::from_str("0.1").unwrap()));
+    executor.set_input("b".to_string(), Value::Decimal(Decimal::from_str("0.2").unwrap()));
+    
+    let result = executor.execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Decimal(Decimal::from_str("0.3").unwrap()),
+        Value::Decimal(Decimal::from_str("0.2").unwrap()),
+        Value::Decimal(Decimal::from_str("0.1").unwrap()),
+        // 混合类型回退到 f64
+        Value::Number(1.1),
+    ])));
+}
+
 #[test]
 fn test_data_stream_executor_basic() {
     let source = r#"
@@ -425,6 +455,85 @@ return [pi, result]
     assert_eq!(output[0].get("result"), Some(&Value::Number(10.0)));
 }
 
+#[test]
+fn test_package_private_members_not_accessible() {
+    let package_source = r#"
+package math
+
+RATE = 0.05
+_secret = 42
+SCALED = _secret * 2
+
+_double(x):
+    return x * 2
+"#;
+    let mut pkg_lexer = Lexer::new(package_source);
+    let pkg_tokens = pkg_lexer.tokenize().unwrap();
+    let mut pkg_parser = Parser::new(pkg_tokens);
+    let package_script = pkg_parser.parse().unwrap();
+    
+    let run = |body: &str| {
+        let source = format!("-- IMPORT math --\n-- OUTPUT v:number --\n\nv = {}\nreturn [v]\n", body);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let mut packages = HashMap::new();
+        packages.insert("math".to_string(), package_script.clone());
+        let mut executor = DataStreamExecutor::new_with_packages(script, vec![HashMap::new()], packages).unwrap();
+        executor.execute_all()
+    };
+    
+    // 公有成员可以访问，包内可以使用私有成员
+    assert_eq!(run("math.RATE").unwrap()[0].get("v"), Some(&Value::Number(0.05)));
+    assert_eq!(run("math.SCALED").unwrap()[0].get("v"), Some(&Value::Number(84.0)));
+    
+    // 包外访问私有变量和私有函数报错
+    let err = run("math._secret").unwrap_err();
+    assert!(err.message.contains("私有成员: _secret"));
+    let err = run("math._double(1)").unwrap_err();
+    assert!(err.message.contains("私有成员: _double"));
+}
+
+#[test]
+fn test_package_export_whitelist() {
+    let package_source = r#"
+package math
+-- EXPORT RATE, _scale --
+
+RATE = 0.05
+HIDDEN = 7
+_scale = 2
+
+helper(x):
+    return x * 2
+"#;
+    let mut pkg_lexer = Lexer::new(package_source);
+    let pkg_tokens = pkg_lexer.tokenize().unwrap();
+    let mut pkg_parser = Parser::new(pkg_tokens);
+    let package_script = pkg_parser.parse().unwrap();
+    
+    let run = |body: &str| {
+        let source = format!("-- IMPORT math --\n-- OUTPUT v:number --\n\nv = {}\nreturn [v]\n", body);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let mut packages = HashMap::new();
+        packages.insert("math".to_string(), package_script.clone());
+        let mut executor = DataStreamExecutor::new_with_packages(script, vec![HashMap::new()], packages).unwrap();
+        executor.execute_all()
+    };
+    
+    // 只有 EXPORT 列出的成员可访问（包括以 _ 开头的）
+    assert_eq!(run("math.RATE").unwrap()[0].get("v"), Some(&Value::Number(0.05)));
+    assert_eq!(run("math._scale").unwrap()[0].get("v"), Some(&Value::Integer(2)));
+    assert!(run("math.HIDDEN").is_err());
+    assert!(run("math.helper(1)").is_err());
+}
+
 #[test]
 fn test_package_function_call() {
     // 暂时跳过包函数测试，因为需要完善缩进处理
@@ -693,6 +802,80 @@ return [rsi]
     }
 }
 
+/// 以 indicators 包执行单行数据脚本
+fn run_with_indicators(source: &str) -> HashMap<String, Value> {
+    let parse = |source: &str| {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    };
+    let mut packages = HashMap::new();
+    packages.insert("indicators".to_string(), parse(include_str!("../../packages/indicators.dp")));
+    
+    let script = parse(source);
+    let mut executor = DataStreamExecutor::new_with_packages(script, vec![HashMap::new()], packages).unwrap();
+    executor.execute_all().unwrap().remove(0)
+}
+
+#[test]
+fn test_indicators_ema_seeding() {
+    let row = run_with_indicators(r#"
+-- IMPORT indicators --
+-- INPUT --
+-- OUTPUT first:number, sma:number, short:number, dif_first:number, dif_sma:number --
+
+prices = [1, 2, 3, 4, 5]
+first = indicators.EMA(prices, 3)
+sma = indicators.EMA(prices, 3, "sma")
+short = indicators.EMA([1, 2], 3, "sma")
+dif_first = indicators.MACD(prices, 2, 3)[0]
+dif_sma = indicators.MACD(prices, 2, 3, 9, "sma")[0]
+return [first, sma, short, dif_first, dif_sma]
+"#);
+    
+    // α = 0.5；首值起算: 1 → 1.5 → 2.25 → 3.125 → 4.0625
+    assert_eq!(row.get("first"), Some(&Value::Number(4.0625)));
+    // SMA 起算: mean(1, 2, 3) = 2 → 3 → 4
+    assert_eq!(row.get("sma"), Some(&Value::Number(4.0)));
+    // 数据不足 period 个时 SMA 起算返回 null
+    assert_eq!(row.get("short"), Some(&Value::Null));
+    // MACD 快线 EMA(2) 与慢线 EMA(3) 使用同一起算方式
+    // SMA 起算: 快线 1.5 → 2.5 → 3.5 → 4.5，慢线 4.0，DIF = 0.5
+    assert_eq!(row.get("dif_sma"), Some(&Value::Number(0.5)));
+    // 首值起算: 快线 1 → 5/3 → 23/9 → 95/27 → 365/81，慢线 4.0625
+    let dif_first = row.get("dif_first").unwrap().to_number().unwrap();
+    assert!((dif_first - (365.0 / 81.0 - 4.0625)).abs() < 1e-12);
+}
+
+#[test]
+fn test_indicators_wma_and_hma() {
+    let row = run_with_indicators(r#"
+-- IMPORT indicators --
+-- INPUT --
+-- OUTPUT wma:number, wma_null:number, wma_short:number, hma_line:number, hma:number, hma_short:number --
+
+wma = indicators.WMA([1, 2, 3, 4, 5], 3)
+wma_null = indicators.WMA([1, null, 2, 3], 3)
+wma_short = indicators.WMA([1, 2], 3)
+hma_line = indicators.HMA([1, 2, 3, 4, 5], 4)
+hma = indicators.HMA([1, 3, 2, 5, 4, 6], 4)
+hma_short = indicators.HMA([1, 3, 2, 5], 4)
+return [wma, wma_null, wma_short, hma_line, hma, hma_short]
+"#);
+    let number = |name: &str| row.get(name).unwrap().to_number().unwrap();
+    
+    // (3*1 + 4*2 + 5*3) / 6
+    assert!((number("wma") - 26.0 / 6.0).abs() < 1e-12);
+    // 跳过 null: (1*1 + 2*2 + 3*3) / 6
+    assert!((number("wma_null") - 14.0 / 6.0).abs() < 1e-12);
+    assert_eq!(row.get("wma_short"), Some(&Value::Null));
+    // 线性序列上 HMA 无滞后，等于最新值
+    assert!((number("hma_line") - 5.0).abs() < 1e-12);
+    // half = 2, root = 2: 差值 73/15、88/15，HMA = (73/15 + 2 * 88/15) / 3 = 83/15
+    assert!((number("hma") - 83.0 / 15.0).abs() < 1e-12);
+    // 需要 period + root - 1 = 5 个有效值
+    assert_eq!(row.get("hma_short"), Some(&Value::Null));
+}
+
 #[test]
 fn test_print_function() {
     let source = r#"
@@ -1154,7 +1337,7 @@ return [result]
     if let Some(Value::Array(arr)) = result {
         if let Value::Array(result_arr) = &arr[0] {
             assert_eq!(result_arr.len(), 5);
-            assert!(result_arr.iter().all(|v| matches!(v, Value::Number(0.0))));
+            assert!(result_arr.iter().all(|v| *v == Value::Number(0.0)));
         } else {
             panic!("Expected array in result");
         }
@@ -1710,3 +1893,1958 @@ return [result]
         panic!("Expected array result");
     }
 }
+
+#[test]
+fn test_when_expression_with_subject() {
+    let source = r#"
+-- INPUT status:string --
+-- OUTPUT code:number --
+
+# 带主体的 when：分支值与主体做相等比较
+code = when status: "active" -> 1, "closed" -> 0, else -> -1
+return [code]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let cases = [("active", 1.0), ("closed", 0.0), ("pending", -1.0)];
+    for (status, expected) in cases {
+        let mut executor = Executor::new();
+        executor.set_input("status".to_string(), Value::String(status.to_string()));
+        
+        let result = executor.execute_data_script(&script).unwrap();
+        
+        if let Some(Value::Array(arr)) = result {
+            assert_eq!(arr[0], Value::Number(expected), "status = {}", status);
+        } else {
+            panic!("Expected array result");
+        }
+    }
+}
+
+#[test]
+fn test_config_declaration() {
+    let source = r#"
+-- CONFIG div=null, precision=4 --
+-- INPUT a:decimal, b:decimal --
+-- OUTPUT ratio:decimal, zero:number --
+
+ratio = a / b
+zero = 1 / 0
+return [ratio, zero]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    executor.set_input("a".to_string(), Value::Decimal(rust_decimal::Decimal::from(1)));
+    executor.set_input("b".to_string(), Value::Decimal(rust_decimal::Decimal::from(3)));
+    
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    if let Some(Value::Array(arr)) = result {
+        // precision=4 应用于 Decimal 结果
+        assert_eq!(arr[0], Value::Decimal("0.3333".parse().unwrap()));
+        // div=null 时除零返回 null
+        assert_eq!(arr[1], Value::Null);
+    } else {
+        panic!("Expected array result");
+    }
+}
+
+#[test]
+fn test_mask_function() {
+    let source = r#"
+-- INPUT prices:array --
+-- OUTPUT result:array --
+
+result = mask(prices, prices > 100)
+return [result]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    executor.set_input("prices".to_string(), Value::Array(vec![
+        Value::Number(90.0),
+        Value::Number(120.0),
+        Value::Number(100.0),
+        Value::Number(150.0),
+    ]));
+    
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    if let Some(Value::Array(arr)) = result {
+        assert_eq!(arr[0], Value::Array(vec![Value::Number(120.0), Value::Number(150.0)]));
+    } else {
+        panic!("Expected array result");
+    }
+    
+    // 长度不匹配
+    let mut executor = Executor::new();
+    let err = executor.execute_builtin("mask", &[
+        Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]),
+        Value::Array(vec![Value::Bool(true)]),
+    ]).unwrap_err();
+    assert!(err.message.contains("长度不匹配"));
+}
+
+#[test]
+fn test_load_csv_and_lookup() {
+    let dir = tempfile::TempDir::new().unwrap();
+    let path = dir.path().join("sectors.csv");
+    // 引号内的逗号不切分列
+    std::fs::write(&path, "code,name,sector\n600000,\"浦发,银行\",银行\n600519,贵州茅台,白酒\n").unwrap();
+    
+    let source = r#"
+-- INPUT code:number --
+-- OUTPUT code:number, sector:string --
+
+sectors = load_csv("__PATH__")
+sector = lookup(sectors, "code", code, "sector")
+return [code, sector]
+"#.replace("__PATH__", path.to_str().unwrap());
+    let mut lexer = Lexer::new(&source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = vec![
+        HashMap::from([("code".to_string(), Value::Number(600519.0))]),
+        HashMap::from([("code".to_string(), Value::Number(300750.0))]),
+        HashMap::from([("code".to_string(), Value::Number(600000.0))]),
+    ];
+    
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("sector"), Some(&Value::String("白酒".to_string())));
+    assert_eq!(output[1].get("sector"), Some(&Value::Null));
+    assert_eq!(output[2].get("sector"), Some(&Value::String("银行".to_string())));
+    
+    // 关闭文件访问后 load_csv 报权限错误
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_file_access(false);
+    let err = executor.execute_all().unwrap_err();
+    assert_eq!(err.error_type, crate::runtime::ErrorType::PermissionDenied);
+    assert_eq!(err.name.as_deref(), Some("load_csv"));
+}
+
+#[test]
+fn test_non_finite_policy() {
+    let source = r#"
+-- INPUT x:number --
+-- OUTPUT inv:number, root:number, gt:bool, lt:bool, eq:bool --
+
+inv = 0.0 ^ -1.0
+root = sqrt(-1)
+gt = x > 1
+lt = x < 1
+eq = x == x
+return [inv, root, gt, lt, eq]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    // 默认模式：NaN/Infinity 结果转为 null，NaN 比较永不为真
+    let mut executor = Executor::new();
+    executor.set_input("x".to_string(), Value::Number(f64::NAN));
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    if let Some(Value::Array(arr)) = result {
+        assert_eq!(arr[0], Value::Null);
+        assert_eq!(arr[1], Value::Null);
+        assert_eq!(arr[2], Value::Bool(false));
+        assert_eq!(arr[3], Value::Bool(false));
+        assert_eq!(arr[4], Value::Bool(false));
+    } else {
+        panic!("Expected array result");
+    }
+    
+    // strict 模式：产生非有限数时报错
+    let strict_source = r#"
+-- CONFIG nan=strict --
+-- OUTPUT root:number --
+
+root = sqrt(-1)
+return [root]
+"#;
+    let mut lexer = Lexer::new(strict_source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let err = executor.execute_data_script(&script).unwrap_err();
+    assert_eq!(err.error_type, crate::runtime::ErrorType::NonFinite);
+    
+    // to_number / to_decimal 拒绝非有限数
+    assert!(Value::Number(f64::INFINITY).to_number().is_err());
+    assert!(Value::Number(f64::NAN).to_decimal().is_err());
+}
+
+#[test]
+fn test_index_mode_lenient_and_strict() {
+    let run = |config: &str| {
+        let source = format!(r#"
+{}
+-- OUTPUT a:number, b:number, c:array --
+
+arr = [1, 2, 3]
+a = arr[5]
+b = arr[-4]
+c = arr[1:10]
+return [a, b, c]
+"#, config);
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script)
+    };
+    
+    // 默认（lenient）：越界读取返回 null，切片边界截断
+    let result = run("").unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Null,
+        Value::Null,
+        Value::Array(vec![Value::Number(2.0), Value::Number(3.0)]),
+    ])));
+    
+    // strict：越界读取报错
+    let err = run("-- CONFIG index=strict --").unwrap_err();
+    assert_eq!(err.error_type, crate::runtime::ErrorType::IndexOutOfBounds);
+}
+
+#[test]
+fn test_injected_param_used_in_comparison() {
+    let source = r#"
+-- INPUT score:number --
+-- OUTPUT selected:bool --
+
+return [score > threshold]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = vec![
+        vec![("score".to_string(), Value::Number(0.9))].into_iter().collect(),
+        vec![("score".to_string(), Value::Number(0.5))].into_iter().collect(),
+    ];
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix);
+    executor.set_param("threshold", Value::Number(0.8)).unwrap();
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("selected"), Some(&Value::Bool(true)));
+    assert_eq!(output[1].get("selected"), Some(&Value::Bool(false)));
+    
+    // 与 INPUT 列同名报错
+    let mut executor = DataStreamExecutor::new(script.clone(), vec![]);
+    assert!(executor.set_param("score", Value::Number(1.0)).is_err());
+    
+    // 参数只读
+    let source = "threshold = 1\nreturn threshold";
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    let mut executor = Executor::new();
+    executor.set_param("threshold", Value::Number(0.8));
+    assert!(executor.execute_data_script(&script).is_err());
+}
+
+#[test]
+fn test_sandbox_rejects_disallowed_builtin() {
+    use crate::executor::SandboxConfig;
+    
+    let run = |source: &str, sandbox: SandboxConfig| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        let mut executor = Executor::new();
+        executor.set_sandbox(sandbox);
+        executor.execute_data_script(&script)
+    };
+    
+    let err = run(r#"return load_csv("prices.csv")"#, SandboxConfig::safe()).unwrap_err();
+    assert_eq!(err.error_type, crate::runtime::ErrorType::PermissionDenied);
+    let err = run("return print(1)", SandboxConfig::safe()).unwrap_err();
+    assert_eq!(err.error_type, crate::runtime::ErrorType::PermissionDenied);
+    
+    // 未禁用的函数照常执行，禁用可按名称增减
+    assert_eq!(run("return sum([1, 2])", SandboxConfig::safe()).unwrap(), Some(Value::Number(3.0)));
+    assert!(run("return sum([1, 2])", SandboxConfig::new().disallow("sum")).is_err());
+    assert!(run("return print(1)", SandboxConfig::safe().allow("print")).is_ok());
+}
+
+#[test]
+fn test_format_number() {
+    let source = r##"
+a = format_number(1234.5, "#,##0.00")
+b = format_number(0.123, "0.0%")
+c = format_number(1000, "¥#,##0")
+d = format_number(-1234567.891, "#,##0.## 元")
+e = format_number(0.5, "#.##")
+f = format_number(null, "0.00")
+return [a, b, c, d, e, f]
+"##;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    let s = |text: &str| Value::String(text.to_string());
+    assert_eq!(result, Some(Value::Array(vec![
+        s("1,234.50"),
+        s("12.3%"),
+        s("¥1,000"),
+        s("-1,234,567.89 元"),
+        s(".5"),
+        Value::Null,
+    ])));
+}
+
+#[test]
+fn test_pct_change_data_stream() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT chg:number --
+
+chg = pct_change("close")
+return [chg]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = vec![
+        vec![("close".to_string(), Value::Number(10.0))].into_iter().collect(),
+        vec![("close".to_string(), Value::Number(11.0))].into_iter().collect(),
+        vec![("close".to_string(), Value::Number(0.0))].into_iter().collect(),
+        vec![("close".to_string(), Value::Number(5.0))].into_iter().collect(),
+    ];
+    
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let output = executor.execute_all().unwrap();
+    
+    // 首行无前值、前值为 0 时均为 null
+    assert_eq!(output[0].get("chg"), Some(&Value::Null));
+    assert_eq!(output[1].get("chg"), Some(&Value::Number(0.1)));
+    assert_eq!(output[2].get("chg"), Some(&Value::Number(-1.0)));
+    assert_eq!(output[3].get("chg"), Some(&Value::Null));
+}
+
+#[test]
+fn test_pct_change_rejects_invalid_periods() {
+    let run = |periods: &str| {
+        let source = format!(
+            "-- INPUT close:number --\n-- OUTPUT chg:number --\nreturn [pct_change(\"close\", {})]\n",
+            periods
+        );
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let input_matrix = vec![vec![("close".to_string(), Value::Number(10.0))].into_iter().collect()];
+        DataStreamExecutor::new(script, input_matrix).execute_all()
+    };
+    
+    assert!(run("2").is_ok());
+    assert!(run("0").is_err());
+    assert!(run("-1").is_err());
+    assert!(run("1.5").is_err());
+}
+
+#[test]
+fn test_roc_and_mom_over_ramp() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT r:number, m:number, batch:array --
+
+return [roc("close", 2), mom("close", 2), roc([0, 10, 20, 30, 40], 2)]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = (0..5)
+        .map(|i| vec![("close".to_string(), Value::Number(i as f64 * 10.0))].into_iter().collect())
+        .collect();
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let output = executor.execute_all().unwrap();
+    
+    // 前 2 行历史不足，第 3 行基准为 0
+    let roc: Vec<_> = output.iter().map(|row| row["r"].clone()).collect();
+    let mom: Vec<_> = output.iter().map(|row| row["m"].clone()).collect();
+    assert_eq!(roc, vec![Value::Null, Value::Null, Value::Null, Value::Number(200.0), Value::Number(100.0)]);
+    assert_eq!(mom, vec![Value::Null, Value::Null, Value::Number(20.0), Value::Number(20.0), Value::Number(20.0)]);
+    assert_eq!(output[0]["batch"], Value::Array(roc));
+}
+
+#[test]
+fn test_returns_function() {
+    let source = r#"
+-- OUTPUT rets:array --
+
+rets = returns([10, 11, 0, 5])
+return [rets]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    if let Some(Value::Array(arr)) = result {
+        assert_eq!(arr[0], Value::Array(vec![
+            Value::Null,
+            Value::Number(0.1),
+            Value::Number(-1.0),
+            Value::Null,
+        ]));
+    } else {
+        panic!("Expected array result");
+    }
+}
+
+#[test]
+fn test_precision_rounding_mode() {
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    
+    let run = |declaration: &str| -> String {
+        let source = format!(
+            "{}\n-- INPUT price:decimal --\n-- OUTPUT result:decimal --\n\nresult = price\nreturn [result]\n",
+            declaration
+        );
+        let mut lexer = Lexer::new(&source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let mut executor = Executor::new();
+        executor.set_input("price".to_string(), Value::Decimal(Decimal::from_str("2.345").unwrap()));
+        
+        match executor.execute_data_script(&script).unwrap() {
+            Some(Value::Array(arr)) => arr[0].to_string(),
+            other => panic!("Expected array result, got: {:?}", other),
+        }
+    };
+    
+    assert_eq!(run("-- PRECISION 2 half_up --"), "2.35");
+    assert_eq!(run("-- PRECISION 2 half_even --"), "2.34");
+    assert_eq!(run("-- PRECISION 2 --"), "2.34");
+    assert_eq!(run("-- PRECISION 2 truncate --"), "2.34");
+    assert_eq!(run("-- PRECISION 2 ceil --"), "2.35");
+    assert_eq!(run("-- CONFIG precision=2, rounding=half_up --"), "2.35");
+}
+
+#[test]
+fn test_round_to_tick() {
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script)
+    };
+    
+    assert_eq!(
+        run("return [round_to_tick(10.017, 0.01) == 10.02, round_to_tick(99.9996, 0.001), round_to_tick(null, 0.01)]").unwrap(),
+        Some(Value::Array(vec![Value::Bool(true), Value::Number(100.0), Value::Null])),
+    );
+    
+    // 舍入方式跟随 PRECISION 设置（默认 half_even）
+    let half = "return [round_to_tick(10.025, 0.01)]";
+    assert_eq!(run(half).unwrap(), Some(Value::Array(vec![Value::Number(10.02)])));
+    assert_eq!(
+        run(&format!("-- PRECISION 4 half_up --\n{}", half)).unwrap(),
+        Some(Value::Array(vec![Value::Number(10.03)])),
+    );
+    
+    assert!(run("return [round_to_tick(10.0, 0)]").is_err());
+}
+
+#[test]
+fn test_first_executed_return_is_row_output() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT label:string --
+
+if close > 10:
+    if close > 20:
+        return ["high"]
+    else:
+        return ["mid"]
+    no_such_function()
+label = "low"
+return [label]
+no_such_function()
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = [5.0, 15.0, 25.0]
+        .iter()
+        .map(|close| vec![("close".to_string(), Value::Number(*close))].into_iter().collect())
+        .collect();
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let output = executor.execute_all().unwrap();
+    
+    // 分支内的 return 直接结束本行，未返回时落到末尾的 return；之后的语句不会执行
+    let labels: Vec<_> = output.iter().map(|row| row["label"].clone()).collect();
+    assert_eq!(labels, vec![
+        Value::String("low".to_string()),
+        Value::String("mid".to_string()),
+        Value::String("high".to_string()),
+    ]);
+}
+
+#[test]
+fn test_approx_eq() {
+    let source = r#"
+return [0.1 + 0.2 == 0.3, approx_eq(0.1 + 0.2, 0.3, 1e-9), approx_eq(1.0, 1.1), approx_eq([1, 2, null], 2.0000000001)]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Bool(false),
+        Value::Bool(true),
+        Value::Bool(false),
+        Value::Array(vec![Value::Bool(false), Value::Bool(true), Value::Null]),
+    ])));
+}
+
+#[test]
+fn test_run_row() {
+    let source = r#"
+-- INPUT close:number, open:number --
+-- OUTPUT change:number, up:bool --
+
+change = close - open
+up = change > 0
+return [change, up]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let row = HashMap::from([
+        ("close".to_string(), Value::Number(12.0)),
+        ("open".to_string(), Value::Number(10.0)),
+    ]);
+    let output = Executor::run_row(&script, &row).unwrap();
+    
+    assert_eq!(output.len(), 2);
+    assert_eq!(output.get("change"), Some(&Value::Number(2.0)));
+    assert_eq!(output.get("up"), Some(&Value::Bool(true)));
+}
+
+#[test]
+fn test_return_maps_to_output_columns() {
+    let parse = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    };
+    let input_matrix = vec![
+        vec![("close".to_string(), Value::Number(10.0))].into_iter().collect(),
+    ];
+    
+    // 数组按位置映射到 OUTPUT 列名
+    let script = parse(r#"
+-- INPUT close:number --
+-- OUTPUT half:number, double:number --
+
+return [close / 2, close * 2]
+"#);
+    let mut executor = DataStreamExecutor::new(script, input_matrix.clone());
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("half"), Some(&Value::Number(5.0)));
+    assert_eq!(output[0].get("double"), Some(&Value::Number(20.0)));
+    
+    // 返回值数量与 OUTPUT 声明不一致时报错
+    let script = parse(r#"
+-- INPUT close:number --
+-- OUTPUT half:number, double:number --
+
+return [close / 2]
+"#);
+    let mut executor = DataStreamExecutor::new(script, input_matrix.clone());
+    let err = executor.execute_all().unwrap_err();
+    assert!(err.message.contains("不匹配"));
+    
+    // 只有一列 OUTPUT 时可以直接返回值
+    let script = parse(r#"
+-- INPUT close:number --
+-- OUTPUT half:number --
+
+return close / 2
+"#);
+    let mut executor = DataStreamExecutor::new(script, input_matrix.clone());
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("half"), Some(&Value::Number(5.0)));
+    
+    // 一列 array 类型的 OUTPUT：直接返回数组与包一层等价
+    for body in ["return [close, close * 2]", "return [[close, close * 2]]"] {
+        let script = parse(&format!("-- INPUT close:number --\n-- OUTPUT pair:array --\n\n{}\n", body));
+        let mut executor = DataStreamExecutor::new(script, input_matrix.clone());
+        let output = executor.execute_all().unwrap();
+        assert_eq!(output[0].get("pair"), Some(&Value::Array(vec![Value::Number(10.0), Value::Number(20.0)])));
+    }
+    
+    // 多列 OUTPUT 时直接返回标量报错
+    let script = parse(r#"
+-- INPUT close:number --
+-- OUTPUT half:number, double:number --
+
+return close / 2
+"#);
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let err = executor.execute_all().unwrap_err();
+    assert!(err.message.contains("OUTPUT 声明了 2 列"));
+}
+
+#[test]
+fn test_null_filling_functions() {
+    let source = r#"
+-- OUTPUT filled:array, forward:array, leading:array, interp:array --
+
+data = [1, null, null, 4]
+filled = fill_null(data, 0)
+forward = ffill(data)
+leading = ffill([null, 2, null])
+interp = interpolate(data)
+return [filled, forward, leading, interp]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    let nums = |values: &[f64]| Value::Array(values.iter().map(|n| Value::Number(*n)).collect());
+    if let Some(Value::Array(arr)) = result {
+        assert_eq!(arr[0], nums(&[1.0, 0.0, 0.0, 4.0]));
+        assert_eq!(arr[1], nums(&[1.0, 1.0, 1.0, 4.0]));
+        assert_eq!(arr[2], Value::Array(vec![Value::Null, Value::Number(2.0), Value::Number(2.0)]));
+        assert_eq!(arr[3], nums(&[1.0, 2.0, 3.0, 4.0]));
+    } else {
+        panic!("Expected array result");
+    }
+}
+
+#[test]
+fn test_sign_clamp_lerp() {
+    let source = r#"
+-- OUTPUT s:array, c:number, cs:array, l:number, ls:array --
+
+s = sign([-3, 0, 2])
+c = clamp(150, 0, 100)
+cs = clamp([-5, 50, 150], 0, 100)
+l = lerp(0, 10, 0.25)
+ls = lerp(0, 10, [0, 0.5, 1])
+return [s, c, cs, l, ls]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    
+    let nums = |values: &[f64]| Value::Array(values.iter().map(|n| Value::Number(*n)).collect());
+    if let Some(Value::Array(arr)) = result {
+        assert_eq!(arr[0], nums(&[-1.0, 0.0, 1.0]));
+        assert_eq!(arr[1], Value::Number(100.0));
+        assert_eq!(arr[2], nums(&[0.0, 50.0, 100.0]));
+        assert_eq!(arr[3], Value::Number(2.5));
+        assert_eq!(arr[4], nums(&[0.0, 5.0, 10.0]));
+    } else {
+        panic!("Expected array result");
+    }
+    
+    // lo > hi 报错
+    let err = executor.execute_builtin("clamp", &[
+        Value::Number(5.0),
+        Value::Number(10.0),
+        Value::Number(0.0),
+    ]).unwrap_err();
+    assert!(err.message.contains("clamp"));
+}
+
+#[test]
+fn test_fstring_compiled_once_many_rows() {
+    let source = r#"
+-- INPUT code:string, close:number --
+-- OUTPUT message:string --
+
+message = f"{code}: {close * 2} ({close > 500 ? "high" : "low"})"
+return [message]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = (0..1000)
+        .map(|i| HashMap::from([
+            ("code".to_string(), Value::String(format!("S{}", i))),
+            ("close".to_string(), Value::Number(i as f64)),
+        ]))
+        .collect();
+    
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let output = executor.execute_all().unwrap();
+    
+    assert_eq!(output.len(), 1000);
+    assert_eq!(output[3].get("message"), Some(&Value::String("S3: 6 (low)".to_string())));
+    assert_eq!(output[999].get("message"), Some(&Value::String("S999: 1998 (high)".to_string())));
+    
+    // 嵌入表达式的语法错误在解析阶段报告
+    let mut lexer = Lexer::new("message = f\"{close +}\"\n");
+    let tokens = lexer.tokenize().unwrap();
+    assert!(Parser::new(tokens).parse().is_err());
+}
+
+#[test]
+fn test_error_policy_skip_row() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT double:number --
+
+return close * 2
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = vec![
+        vec![("close".to_string(), Value::Number(1.0))].into_iter().collect(),
+        vec![("close".to_string(), Value::Bool(true))].into_iter().collect(),
+        vec![("close".to_string(), Value::Number(3.0))].into_iter().collect(),
+    ];
+    
+    // 默认 FailFast：出错即终止
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    assert!(executor.execute_all().is_err());
+    
+    // SkipRow：出错行输出空行，其余行继续执行
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_error_policy(ErrorPolicy::SkipRow);
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output.len(), 3);
+    assert_eq!(output[0].get("double"), Some(&Value::Number(2.0)));
+    assert!(output[1].is_empty());
+    assert_eq!(output[2].get("double"), Some(&Value::Number(6.0)));
+    
+    let errors = executor.row_errors();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, 1);
+}
+
+#[test]
+fn test_cross_sectional_rank_and_zscore() {
+    let source = r#"
+-- INPUT code:string, pe:number --
+-- OUTPUT code:string, pe:number --
+
+return [code, pe]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = [("A", 30.0), ("B", 10.0), ("C", 20.0), ("D", 40.0)]
+        .iter()
+        .map(|(code, pe)| {
+            vec![
+                ("code".to_string(), Value::String(code.to_string())),
+                ("pe".to_string(), Value::Number(*pe)),
+            ].into_iter().collect()
+        })
+        .collect();
+    
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.execute_all().unwrap();
+    executor.rank_column("pe").unwrap();
+    executor.zscore_column("pe").unwrap();
+    
+    let output = executor.get_output();
+    let ranks: Vec<Value> = output.iter().map(|row| row["pe_rank"].clone()).collect();
+    assert_eq!(ranks, vec![Value::Number(3.0), Value::Number(1.0), Value::Number(2.0), Value::Number(4.0)]);
+    
+    let zsum: f64 = output.iter().map(|row| row["pe_zscore"].to_number().unwrap()).sum();
+    assert!(zsum.abs() < 1e-9);
+    
+    assert!(executor.rank_column("missing").is_err());
+}
+
+#[test]
+fn test_argument_mismatch_error_type() {
+    use crate::runtime::ErrorType;
+    
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script).unwrap_err()
+    };
+    
+    // 参数数量错误与类型错误可以区分
+    let err = run("-- OUTPUT x:number --\nreturn clamp(1, 2)\n");
+    assert_eq!(err.error_type, ErrorType::ArgumentMismatch);
+    assert!(err.message.contains("clamp 需要 3 个参数"));
+    
+    let err = run("-- OUTPUT x:array --\nreturn map(1, x -> x)\n");
+    assert_eq!(err.error_type, ErrorType::TypeError);
+}
+
+#[test]
+fn test_null_propagation_in_arithmetic() {
+    let source = r#"
+-- OUTPUT a:number, b:number, c:number, d:array, total:number --
+
+a = null + 5
+b = 2 * null
+c = -null
+d = [1, null, 3] * 2
+total = sum([1, null, 3])
+return [a, b, c, d, total]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Null,
+        Value::Null,
+        Value::Null,
+        Value::Array(vec![Value::Number(2.0), Value::Null, Value::Number(6.0)]),
+        Value::Number(4.0),
+    ])));
+}
+
+#[test]
+fn test_streaming_quantile_matches_batch_percentile() {
+    let parse = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    };
+    
+    let streaming_script = parse(r#"
+-- INPUT close:number --
+-- OUTPUT q:number --
+
+return quantile("close", 5, 0.95)
+"#);
+    let batch_script = parse(r#"
+-- INPUT prices:array --
+-- OUTPUT q:number --
+
+return percentile(prices, 95)
+"#);
+    
+    // 线性插值：[1, 2, 3, 4, 5] 的 95 百分位为 4.8
+    let mut batch = Executor::new();
+    batch.set_input("prices".to_string(), Value::Array(vec![
+        Value::Number(5.0), Value::Number(1.0), Value::Null, Value::Number(4.0), Value::Number(2.0), Value::Number(3.0),
+    ]));
+    match batch.execute_data_script(&batch_script).unwrap() {
+        Some(Value::Number(q)) => assert!((q - 4.8).abs() < 1e-9),
+        other => panic!("Expected number, got {:?}", other),
+    }
+    
+    let closes: Vec<f64> = (0..20).map(|i| ((i * 37) % 11) as f64 + 100.0).collect();
+    let mut streaming = StreamingExecutor::new(streaming_script, 10);
+    
+    for (i, close) in closes.iter().enumerate() {
+        let tick = vec![("close".to_string(), Value::Number(*close))].into_iter().collect();
+        let output = streaming.push_tick(tick).unwrap().unwrap();
+        
+        let window = &closes[i.saturating_sub(4)..=i];
+        if window.len() < 2 {
+            assert_eq!(output.get("q"), Some(&Value::Null));
+            continue;
+        }
+        
+        let mut batch = Executor::new();
+        batch.set_input(
+            "prices".to_string(),
+            Value::Array(window.iter().map(|v| Value::Number(*v)).collect()),
+        );
+        let expected = batch.execute_data_script(&batch_script).unwrap();
+        assert_eq!(output.get("q").cloned(), expected, "tick {}", i);
+    }
+}
+
+#[test]
+fn test_spread_in_arrays_and_calls() {
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script)
+    };
+    
+    let result = run(r#"
+-- OUTPUT spliced:array, biggest:number --
+
+rest = [2, 3, 4]
+spliced = [1, ...rest, 5]
+biggest = max(...[1, 5, 3])
+return [spliced, biggest]
+"#).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Array((1..=5).map(|i| Value::Number(i as f64)).collect()),
+        Value::Number(5.0),
+    ])));
+    
+    // 展开非数组报错
+    let err = run("-- OUTPUT x:array --\nreturn [...5]\n").unwrap_err();
+    assert!(err.message.contains("只能展开数组"));
+}
+
+#[test]
+fn test_destructure_middle_spread_and_defaults() {
+    let source = r#"
+-- OUTPUT first:number, mid:array, last:number, a:number, b:number, c:number, d:number --
+
+[first, ...mid, last] = [1, 2, 3, 4, 5]
+[a, _, b, c] = [10, 20, 30]
+[d = 7, e = 8] = [null, 9]
+return [first, mid, last, a, b, c, d + e]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    let result = executor.execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Number(1.0),
+        Value::Array(vec![Value::Number(2.0), Value::Number(3.0), Value::Number(4.0)]),
+        Value::Number(5.0),
+        Value::Number(10.0),
+        Value::Number(30.0),
+        // 超出数组长度的位置绑定 null
+        Value::Null,
+        // 缺失或 null 的位置使用默认值
+        Value::Number(16.0),
+    ])));
+    
+    // 数组短于模式时，展开为空，尾部位置为 null
+    let source = "-- OUTPUT x:array --\n[h, ...m, t] = [1]\nreturn [h, m, t]\n";
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![Value::Number(1.0), Value::Array(vec![]), Value::Null])));
+}
+
+#[test]
+fn test_execution_overrides_take_effect() {
+    use crate::api::ExecutionOverrides;
+    use crate::parser::DivMode;
+    use rust_decimal::Decimal;
+    use std::str::FromStr;
+    
+    let source = r#"
+-- INPUT price:decimal, qty:number --
+-- OUTPUT price:decimal, ratio:number --
+-- PRECISION 4 --
+
+return [price, qty / 0]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = vec![vec![
+        ("price".to_string(), Value::Decimal(Decimal::from_str("2.34567").unwrap())),
+        ("qty".to_string(), Value::Number(3.0)),
+    ].into_iter().collect()];
+    
+    // 脚本默认除零报错
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    assert!(executor.execute_all().is_err());
+    
+    // 命令行覆盖：精度 2 位、除零返回 null
+    let overrides = ExecutionOverrides { precision: Some(2), div_mode: Some(DivMode::Null), ..Default::default() };
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    overrides.apply(&mut executor).unwrap();
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("price"), Some(&Value::Decimal(Decimal::from_str("2.35").unwrap())));
+    assert_eq!(output[0].get("ratio"), Some(&Value::Null));
+    
+    // --precision 只覆盖位数，保留脚本声明的舍入方式（默认 half_even 时 2.345 -> 2.34）
+    let run_rounded = |header: &str| {
+        let source = format!("-- INPUT price:decimal --\n-- OUTPUT price:decimal --\n{}\n\nreturn [price]\n", header);
+        let tokens = Lexer::new(&source).tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        let input_matrix = vec![HashMap::from([
+            ("price".to_string(), Value::Decimal(Decimal::from_str("2.345").unwrap())),
+        ])];
+        let mut executor = DataStreamExecutor::new(script, input_matrix);
+        ExecutionOverrides { precision: Some(2), ..Default::default() }.apply(&mut executor).unwrap();
+        executor.execute_all().unwrap()[0].get("price").cloned()
+    };
+    let expected = Some(Value::Decimal(Decimal::from_str("2.35").unwrap()));
+    assert_eq!(run_rounded("-- PRECISION 4 half_up --"), expected);
+    assert_eq!(run_rounded("-- CONFIG rounding=half_up --"), expected);
+}
+
+#[test]
+fn test_memo_function_cache_hits() {
+    let package_source = r#"package fast
+@memo
+fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+"#;
+    let mut lexer = Lexer::new(package_source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let package = parser.parse().unwrap();
+    
+    let mut executor = Executor::new();
+    executor.execute_package_script(&package).unwrap();
+    
+    let source = "return fib(15)";
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = executor.execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Number(610.0)));
+    
+    let (hits, _) = executor.memo_stats();
+    assert!(hits > 0);
+}
+
+#[test]
+fn test_memo_functions_with_same_name_in_different_packages() {
+    let parse = |source: &str| {
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        Parser::new(tokens).parse().unwrap()
+    };
+    let mut packages = HashMap::new();
+    packages.insert("pa".to_string(), parse("package pa\n@memo\nf(x):\n    return x + 1\n"));
+    packages.insert("pb".to_string(), parse("package pb\n@memo\nf(x):\n    return x * 100\n"));
+    
+    let script = parse(r#"
+-- IMPORT pa, pb --
+-- INPUT x:number --
+-- OUTPUT a:number, b:number --
+
+return [pa.f(x), pb.f(x)]
+"#);
+    let input_matrix = vec![vec![("x".to_string(), Value::Number(2.0))].into_iter().collect()];
+    
+    let mut executor = DataStreamExecutor::new_with_packages(script, input_matrix, packages).unwrap();
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0].get("a"), Some(&Value::Number(3.0)));
+    assert_eq!(output[0].get("b"), Some(&Value::Number(200.0)));
+}
+
+#[test]
+fn test_chunk_and_windows() {
+    let source = r#"
+return [chunk([1, 2, 3, 4, 5], 2), windows([1, 2, 3], 2)]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    let nums = |v: &[f64]| Value::Array(v.iter().map(|n| Value::Number(*n)).collect());
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Array(vec![nums(&[1.0, 2.0]), nums(&[3.0, 4.0]), nums(&[5.0])]),
+        Value::Array(vec![nums(&[1.0, 2.0]), nums(&[2.0, 3.0])]),
+    ])));
+    
+    // 大小必须为正
+    let mut lexer = Lexer::new("return chunk([1, 2], 0)");
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    assert!(Executor::new().execute_data_script(&script).is_err());
+}
+
+#[test]
+fn test_head_and_tail() {
+    let source = r#"
+return [head(["a", "b", "c"], 2), tail(["a", "b", "c"], 2), head(["a"], 5), tail(["a"], 0)]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    let strs = |v: &[&str]| Value::Array(v.iter().map(|s| Value::String(s.to_string())).collect());
+    assert_eq!(result, Some(Value::Array(vec![
+        strs(&["a", "b"]),
+        strs(&["b", "c"]),
+        strs(&["a"]),
+        strs(&[]),
+    ])));
+    
+    // 个数不能为负
+    for source in ["return head([1, 2], -1)", "return tail([1, 2], -1)"] {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        assert!(Executor::new().execute_data_script(&script).is_err());
+    }
+}
+
+#[test]
+fn test_cancel_token_aborts_long_script() {
+    use std::time::{Duration, Instant};
+    
+    // 约 1 亿次 Lambda 调用，不取消的话要跑很久
+    let source = r#"
+-- INPUT n:number --
+-- OUTPUT total:number --
+
+total = sum(map(Range(0, 100000), i -> sum(map(Range(0, 1000), j -> j))))
+return [total]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> =
+        vec![vec![("n".to_string(), Value::Number(1.0))].into_iter().collect()];
+    
+    // 另一个线程在执行中途置位取消标志
+    let token = CancelToken::new();
+    let flag = token.flag();
+    let canceller = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        flag.store(true, std::sync::atomic::Ordering::Relaxed);
+    });
+    
+    let started = Instant::now();
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    // SkipRow 也不会吞掉取消错误
+    executor.set_error_policy(ErrorPolicy::SkipRow);
+    executor.set_cancel_token(token);
+    let err = executor.execute_all().unwrap_err();
+    canceller.join().unwrap();
+    
+    assert!(err.is_cancelled());
+    assert_eq!(err.message, "执行已取消");
+    assert!(started.elapsed() < Duration::from_secs(10));
+    
+    // 截止时间同样生效
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_cancel_token(CancelToken::new().with_timeout(Duration::from_millis(20)));
+    let err = executor.execute_all().unwrap_err();
+    assert_eq!(err.message, "执行超时");
+}
+
+#[test]
+fn test_integer_literals_and_indexing() {
+    let source = r#"
+-- INPUT id:number --
+-- OUTPUT same:number, next:number, picked:string, half:number --
+
+arr = ["a", "b", "c"]
+return [id, id + 1, arr[length(arr) - 1], 7 / 2]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    // 超过 2^53 的 ID 经 CSV 解析后保持精确
+    let input = crate::api::parse_csv("id\n9007199254740993").unwrap();
+    assert_eq!(input[0].get("id"), Some(&Value::Integer(9007199254740993)));
+    
+    let mut executor = DataStreamExecutor::new(script, input);
+    let output = executor.execute_all().unwrap();
+    assert!(matches!(output[0].get("same"), Some(Value::Integer(9007199254740993))));
+    assert!(matches!(output[0].get("next"), Some(Value::Integer(9007199254740994))));
+    assert_eq!(output[0].get("picked"), Some(&Value::String("c".to_string())));
+    assert!(matches!(output[0].get("half"), Some(Value::Number(n)) if *n == 3.5));
+    
+    // 整数与同值的小数相等
+    assert_eq!(Value::Integer(2), Value::Number(2.0));
+}
+
+#[test]
+fn test_streaming_reload_script_keeps_history_for_body_edits() {
+    let parse = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    };
+    let tick = |close: f64| -> HashMap<String, Value> {
+        vec![("close".to_string(), Value::Number(close))].into_iter().collect()
+    };
+    
+    let mut streaming = StreamingExecutor::new(parse(r#"
+-- INPUT close:number --
+-- OUTPUT prev:number --
+
+return [close[-1]]
+"#), 10);
+    streaming.push_tick(tick(10.0)).unwrap();
+    streaming.push_tick(tick(11.0)).unwrap();
+    
+    // 只改函数体（形状和历史访问不变）：保留历史
+    let cosmetic = parse(r#"
+-- INPUT close:number --
+-- OUTPUT prev:number --
+
+last_close = close[-1]
+return [last_close]
+"#);
+    assert!(streaming.reload_script(cosmetic));
+    let output = streaming.push_tick(tick(12.0)).unwrap().unwrap();
+    assert_eq!(output.get("prev"), Some(&Value::Number(11.0)));
+    
+    // OUTPUT 变化：清空历史
+    let reshaped = parse(r#"
+-- INPUT close:number --
+-- OUTPUT prev:number, close_out:number --
+
+return [close[-1], close]
+"#);
+    assert!(!streaming.reload_script(reshaped));
+    let output = streaming.push_tick(tick(13.0)).unwrap().unwrap();
+    assert_eq!(output.get("prev"), Some(&Value::Null));
+    assert_eq!(output.get("close_out"), Some(&Value::Number(13.0)));
+}
+
+#[test]
+fn test_trace_records_assignments_for_selected_row() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT result:number --
+
+doubled = close * 2
+shifted = doubled + 1
+result = shifted / 2
+return [result]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = [10.0, 20.0]
+        .iter()
+        .map(|c| vec![("close".to_string(), Value::Number(*c))].into_iter().collect())
+        .collect();
+    
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_trace(Some(1));
+    executor.execute_all().unwrap();
+    
+    let trace: Vec<(&str, &Value)> = executor.trace().iter().map(|e| (e.name.as_str(), &e.value)).collect();
+    assert_eq!(trace, vec![
+        ("doubled", &Value::Number(40.0)),
+        ("shifted", &Value::Number(41.0)),
+        ("result", &Value::Number(20.5)),
+    ]);
+}
+
+#[test]
+fn test_offset_zero_reads_current_row_assignments() {
+    let source = r#"
+-- INPUT high:number, low:number --
+-- OUTPUT spread:number, chg:number, recent:array --
+
+spread = high - low
+chg = pct_change("spread")
+recent = spread[-1:0]
+return [spread, chg, recent]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = [(10.0, 8.0), (12.0, 9.0)]
+        .iter()
+        .map(|(h, l)| vec![
+            ("high".to_string(), Value::Number(*h)),
+            ("low".to_string(), Value::Number(*l)),
+        ].into_iter().collect())
+        .collect();
+    
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    let output = executor.execute_all().unwrap();
+    
+    // 第一行没有历史：当前值来自本行的赋值
+    assert_eq!(output[0].get("chg"), Some(&Value::Null));
+    assert_eq!(output[0].get("recent"), Some(&Value::Array(vec![Value::Null, Value::Number(2.0)])));
+    
+    // 第二行：offset 0 为本行 spread = 3，offset 1 为上一行输出的 spread = 2
+    assert_eq!(output[1].get("chg"), Some(&Value::Number(0.5)));
+    assert_eq!(output[1].get("recent"), Some(&Value::Array(vec![Value::Number(2.0), Value::Number(3.0)])));
+}
+
+#[test]
+fn test_to_json_from_json_round_trip() {
+    let source = r#"
+data = [1, 2.5, "a", null, [true, [3]]]
+text = to_json(data)
+return [text, from_json(text) == data, from_json("[1, [2, 3]]")]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::String(r#"[1,2.5,"a",null,[true,[3]]]"#.to_string()),
+        Value::Bool(true),
+        Value::Array(vec![
+            Value::Integer(1),
+            Value::Array(vec![Value::Integer(2), Value::Integer(3)]),
+        ]),
+    ])));
+    
+    // 函数值无法序列化
+    let mut lexer = Lexer::new("return to_json(x -> x)");
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    assert!(Executor::new().execute_data_script(&script).is_err());
+}
+
+#[test]
+fn test_histogram_equal_width_and_custom_edges() {
+    let source = r#"
+data = [1, 2, 2, 3, null, 4, 5]
+return [histogram(data, 2), histogram(data, [0, 2, 4]), histogram([], 3)]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    let bin = |lo: f64, hi: f64, count: i64| Value::Array(vec![
+        Value::Number(lo),
+        Value::Number(hi),
+        Value::Integer(count),
+    ]);
+    assert_eq!(result, Some(Value::Array(vec![
+        // 等宽 [1, 3) [3, 5]，最后一个桶包含最大值
+        Value::Array(vec![bin(1.0, 3.0, 3), bin(3.0, 5.0, 3)]),
+        // 自定义边界，5 在范围之外
+        Value::Array(vec![bin(0.0, 2.0, 1), bin(2.0, 4.0, 4)]),
+        Value::Array(vec![]),
+    ])));
+}
+
+#[test]
+fn test_streaming_prime_fills_history_before_first_tick() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT sma:number --
+
+sma = close[-19] == null ? null : sum(close[-19:0]) / 20
+return [sma]
+"#;
+    let parse = || {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        parser.parse().unwrap()
+    };
+    let tick = |close: f64| -> HashMap<String, Value> {
+        vec![("close".to_string(), Value::Number(close))].into_iter().collect()
+    };
+    
+    // 未预热：历史不足 20 行时为 null
+    let mut cold = StreamingExecutor::new(parse(), 30);
+    let output = cold.push_tick(tick(20.0)).unwrap().unwrap();
+    assert_eq!(output.get("sma"), Some(&Value::Null));
+    
+    // 预热 19 行历史后，第一个实时 tick 就有完整的 20 行窗口
+    let mut warm = StreamingExecutor::new(parse(), 30);
+    warm.prime((1..=19).map(|i| tick(i as f64)).collect()).unwrap();
+    let output = warm.push_tick(tick(20.0)).unwrap().unwrap();
+    assert_eq!(output.get("sma"), Some(&Value::Number(10.5)));
+}
+
+#[test]
+fn test_grouped_executor_symbol_local_history_and_cross_rank() {
+    let source = r#"
+-- INPUT code:string, close:number --
+-- OUTPUT code:string, ma:number --
+
+ma = close[-1] == null ? null : (close + close[-1]) / 2
+return [code, ma]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    // 两只股票按日期交错排列
+    let row = |date: &str, code: &str, close: f64| -> HashMap<String, Value> {
+        vec![
+            ("date".to_string(), Value::String(date.to_string())),
+            ("code".to_string(), Value::String(code.to_string())),
+            ("close".to_string(), Value::Number(close)),
+        ].into_iter().collect()
+    };
+    let input_matrix = vec![
+        row("d1", "A", 10.0),
+        row("d1", "B", 100.0),
+        row("d2", "A", 12.0),
+        row("d2", "B", 90.0),
+        row("d3", "A", 14.0),
+        row("d3", "B", 2.0),
+    ];
+    
+    let mut executor = GroupedExecutor::new(script, input_matrix, "code");
+    executor.set_time_key("date");
+    executor.execute_all().unwrap();
+    executor.rank_column("ma").unwrap();
+    let output = executor.get_output();
+    
+    // 历史只看本标的：A 的均线不会混入 B 的价格
+    let ma: Vec<_> = output.iter().map(|row| row["ma"].clone()).collect();
+    assert_eq!(ma, vec![
+        Value::Null, Value::Null,
+        Value::Number(11.0), Value::Number(95.0),
+        Value::Number(13.0), Value::Number(46.0),
+    ]);
+    
+    // 排名只在同一日期的标的之间进行
+    let rank: Vec<_> = output.iter().map(|row| row["ma_rank"].clone()).collect();
+    assert_eq!(rank, vec![
+        Value::Null, Value::Null,
+        Value::Number(1.0), Value::Number(2.0),
+        Value::Number(1.0), Value::Number(2.0),
+    ]);
+}
+
+#[test]
+fn test_grouped_executor_parallel_matches_serial() {
+    let source = r#"
+-- INPUT code:string, close:number --
+-- OUTPUT code:string, chg:number --
+
+return [code, pct_change("close")]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = (0..40)
+        .map(|i| vec![
+            ("code".to_string(), Value::String(["A", "B", "C"][i % 3].to_string())),
+            ("close".to_string(), Value::Number(10.0 + (i * 7 % 11) as f64)),
+        ].into_iter().collect())
+        .collect();
+    
+    let mut serial = GroupedExecutor::new(script.clone(), input_matrix.clone(), "code");
+    let serial_output = serial.execute_all().unwrap();
+    
+    let mut parallel = GroupedExecutor::new(script, input_matrix, "code");
+    parallel.set_parallelism(4);
+    let parallel_output = parallel.execute_all().unwrap();
+    
+    assert_eq!(parallel_output, serial_output);
+    assert_eq!(parallel_output[0]["chg"], Value::Null);
+    assert_ne!(parallel_output[3]["chg"], Value::Null);
+}
+
+#[test]
+fn test_grouped_index_is_group_relative() {
+    let source = r#"
+-- INPUT code:string --
+-- OUTPUT code:string, i:number, n:number, gi:number, gn:number --
+
+return [code, _index, _total, _global_index, _global_total]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let codes = ["A", "B", "A", "A", "B"];
+    let input_matrix: Vec<HashMap<String, Value>> = codes.iter()
+        .map(|code| vec![("code".to_string(), Value::String(code.to_string()))].into_iter().collect())
+        .collect();
+    
+    let positions = |output: &[HashMap<String, Value>]| -> Vec<[Value; 4]> {
+        output.iter()
+            .map(|row| [row["i"].clone(), row["n"].clone(), row["gi"].clone(), row["gn"].clone()])
+            .collect()
+    };
+    let int = Value::Integer;
+    let expected = vec![
+        [int(0), int(3), int(0), int(5)],
+        [int(0), int(2), int(1), int(5)],
+        [int(1), int(3), int(2), int(5)],
+        [int(2), int(3), int(3), int(5)],
+        [int(1), int(2), int(4), int(5)],
+    ];
+    
+    let mut serial = GroupedExecutor::new(script.clone(), input_matrix.clone(), "code");
+    assert_eq!(positions(&serial.execute_all().unwrap()), expected);
+    
+    let mut parallel = GroupedExecutor::new(script.clone(), input_matrix.clone(), "code");
+    parallel.set_parallelism(2);
+    assert_eq!(positions(&parallel.execute_all().unwrap()), expected);
+    
+    // 不分组时与 _index / _total 相同
+    let output = DataStreamExecutor::new(script, input_matrix).execute_all().unwrap();
+    assert_eq!(output[4]["i"], int(4));
+    assert_eq!(output[4]["gi"], int(4));
+    assert_eq!(output[4]["gn"], int(5));
+}
+
+#[test]
+fn test_passthrough_columns_copied_to_output() {
+    let source = r#"
+-- INPUT code:string, close:number --
+-- OUTPUT code:string, doubled:number --
+
+return [code, close * 2]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let row = |code: &str, sector: &str, close: i64| -> HashMap<String, Value> {
+        vec![
+            ("code".to_string(), Value::String(code.to_string())),
+            ("sector".to_string(), Value::String(sector.to_string())),
+            ("close".to_string(), Value::Integer(close)),
+        ].into_iter().collect()
+    };
+    let input_matrix = vec![row("A", "银行", 10), row("B", "医药", 20), row("A", "银行", 11)];
+    
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    executor.set_passthrough(vec!["sector".to_string()]).unwrap();
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[1]["sector"], Value::String("医药".to_string()));
+    assert_eq!(output[1]["doubled"], Value::Integer(40));
+    
+    // 列式执行和分组执行同样带上透传列
+    let columns = executor.execute_all_columnar().unwrap();
+    assert_eq!(columns.get_value("sector", 2), Some(Value::String("银行".to_string())));
+    let mut grouped = GroupedExecutor::new(script.clone(), input_matrix.clone(), "code");
+    grouped.set_passthrough(vec!["sector".to_string()]).unwrap();
+    assert_eq!(grouped.execute_all().unwrap(), output);
+    
+    // 与 OUTPUT 列同名、或输入中没有该列时报错
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    assert!(executor.set_passthrough(vec!["doubled".to_string()]).is_err());
+    executor.set_passthrough(vec!["name".to_string()]).unwrap();
+    let err = executor.execute_all().unwrap_err();
+    assert!(err.message.contains("透传列 name"), "{}", err.message);
+}
+
+#[test]
+fn test_input_schema_checked_before_execution() {
+    let source = r#"
+-- INPUT close:number, volume:number, flag:bool --
+-- OUTPUT amount:number --
+
+return [close * volume]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix: Vec<HashMap<String, Value>> = vec![vec![
+        ("close".to_string(), Value::Number(10.0)),
+        ("flag".to_string(), Value::String("yes".to_string())),
+    ].into_iter().collect()];
+    
+    // 一次列出所有问题，且不执行任何行
+    let mut executor = DataStreamExecutor::new(script.clone(), input_matrix.clone());
+    let err = executor.execute_all().unwrap_err();
+    assert!(err.message.contains("缺少列 volume"), "{}", err.message);
+    assert!(err.message.contains("列 flag 期望 bool"), "{}", err.message);
+    assert!(executor.get_output().is_empty());
+    
+    // 关闭检查后缺少的列按 null 处理
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_schema_check(false);
+    let output = executor.execute_all().unwrap();
+    assert_eq!(output[0]["amount"], Value::Null);
+}
+
+#[test]
+fn test_cummax_cummin_and_drawdown() {
+    let source = r#"
+return [cummax([1, 3, 2, 5]) == [1, 3, 3, 5], cummin([4, null, 5, 2]), drawdown([1, 2, 1.5, null, 3, 1.5])]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let result = Executor::new().execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Bool(true),
+        Value::Array(vec![Value::Integer(4), Value::Null, Value::Integer(4), Value::Integer(2)]),
+        Value::Array(vec![
+            Value::Number(0.0),
+            Value::Number(0.0),
+            Value::Number(-0.25),
+            Value::Null,
+            Value::Number(0.0),
+            Value::Number(-0.5),
+        ]),
+    ])));
+}
+
+/// 累计求和脚本（引用上一行的输出）与 n 行输入
+fn running_total_fixture(rows: usize) -> (Script, Vec<HashMap<String, Value>>) {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT total:number, prev:number --
+
+total = close
+prev = total[-1]
+total = prev == null ? close : prev + close
+return [total, prev]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let input_matrix = (0..rows)
+        .map(|i| vec![("close".to_string(), Value::Number(i as f64))].into_iter().collect())
+        .collect();
+    (script, input_matrix)
+}
+
+#[test]
+fn test_execute_all_columnar_matches_rows() {
+    let (script, input_matrix) = running_total_fixture(5);
+    
+    let rows = DataStreamExecutor::new(script.clone(), input_matrix.clone()).execute_all().unwrap();
+    let columns = DataStreamExecutor::new(script, input_matrix).execute_all_columnar().unwrap();
+    
+    assert_eq!(columns.column_names(), vec!["total", "prev"]);
+    assert_eq!(columns.row_count(), rows.len());
+    for (i, row) in rows.iter().enumerate() {
+        assert_eq!(columns.get_row(i).as_ref(), Some(row));
+    }
+    assert_eq!(columns.get_value("total", 4), Some(Value::Number(10.0)));
+    assert_eq!(columns.get_value("prev", 0), Some(Value::Null));
+}
+
+/// 基准：cargo test --release bench_execute_all_columnar -- --ignored --nocapture
+#[test]
+#[ignore]
+fn bench_execute_all_columnar() {
+    use std::time::Instant;
+    
+    let (script, input_matrix) = running_total_fixture(10_000);
+    
+    let started = Instant::now();
+    let rows = DataStreamExecutor::new(script.clone(), input_matrix.clone()).execute_all().unwrap();
+    let row_elapsed = started.elapsed();
+    
+    let started = Instant::now();
+    let columns = DataStreamExecutor::new(script, input_matrix).execute_all_columnar().unwrap();
+    let column_elapsed = started.elapsed();
+    
+    assert_eq!(columns.row_count(), rows.len());
+    println!("execute_all: {:?}, execute_all_columnar: {:?}", row_elapsed, column_elapsed);
+}
+
+#[test]
+fn test_tap_records_pipeline_stages() {
+    let source = r#"
+total = [1, 2, 3] |> tap("input") |> map(x -> x * 2) |> tap("doubled") |> sum()
+return total
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let lines = Rc::new(RefCell::new(Vec::new()));
+    let sink_lines = Rc::clone(&lines);
+    let mut executor = Executor::new();
+    executor.set_print_sink(move |line| sink_lines.borrow_mut().push(line.to_string()));
+    
+    // tap 不改变管道中的值
+    let result = executor.execute_data_script(&script).unwrap();
+    assert_eq!(result, Some(Value::Number(12.0)));
+    assert_eq!(*lines.borrow(), vec!["input: [1, 2, 3]", "doubled: [2, 4, 6]"]);
+}
+
+#[test]
+fn test_resource_limits() {
+    use crate::runtime::ErrorType;
+    
+    let limits = ResourceLimits { max_array_len: 10, max_input_rows: 3 };
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        let mut executor = Executor::new();
+        executor.set_limits(limits);
+        executor.execute_data_script(&script)
+    };
+    
+    assert!(run("return length(Range(1, 10))").is_ok());
+    let err = run("return Range(1, 11)").unwrap_err();
+    assert_eq!(err.error_type, ErrorType::LimitExceeded);
+    assert_eq!(run("return Array(11, 0)").unwrap_err().error_type, ErrorType::LimitExceeded);
+    
+    // 输入行数超限时拒绝执行
+    let (script, input_matrix) = running_total_fixture(4);
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_limits(limits);
+    assert_eq!(executor.execute_all().unwrap_err().error_type, ErrorType::LimitExceeded);
+}
+
+#[test]
+fn test_forward_generates_lookahead_label() {
+    let source = r#"
+-- INPUT close:number --
+-- OUTPUT label:number --
+
+next_close = forward("close")
+label = next_close == null ? null : next_close / close - 1
+return [label]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let tick = |close: f64| -> HashMap<String, Value> {
+        vec![("close".to_string(), Value::Number(close))].into_iter().collect()
+    };
+    let input_matrix = vec![tick(10.0), tick(15.0), tick(30.0)];
+    
+    let output = DataStreamExecutor::new(script.clone(), input_matrix).execute_all().unwrap();
+    assert_eq!(output[0]["label"], Value::Number(0.5));
+    assert_eq!(output[1]["label"], Value::Number(1.0));
+    // 最后一行没有后续数据
+    assert_eq!(output[2]["label"], Value::Null);
+    
+    // 流式执行无法预读
+    let mut streaming = StreamingExecutor::new(script, 10);
+    let err = streaming.push_tick(tick(10.0)).unwrap_err();
+    assert!(err.message.contains("流式执行中不可用"), "{}", err.message);
+}
+
+#[test]
+fn test_runtime_error_carries_symbol_name() {
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script).unwrap_err()
+    };
+    
+    let err = run("return no_such_fn(1)");
+    assert_eq!(err.error_type, crate::runtime::ErrorType::UndefinedFunction);
+    assert_eq!(err.name.as_deref(), Some("no_such_fn"));
+    // 展示文本不变
+    assert_eq!(err.to_string(), "运行时错误: 未定义的函数: no_such_fn");
+    
+    assert_eq!(run("return missing + 1").name.as_deref(), Some("missing"));
+    assert_eq!(run("return first(1, 2)").name.as_deref(), Some("first"));
+    assert_eq!(run("return 1 / 0").name, None);
+}
+
+#[test]
+fn test_bar_builder_aggregates_ticks() {
+    let source = r#"
+-- INPUT timestamp:number, open:number, high:number, low:number, close:number, volume:number --
+-- OUTPUT timestamp:number, open:number, high:number, low:number, close:number, volume:number --
+
+return [timestamp, open, high, low, close, volume]
+"#;
+    let mut lexer = Lexer::new(source);
+    let tokens = lexer.tokenize().unwrap();
+    let mut parser = Parser::new(tokens);
+    let script = parser.parse().unwrap();
+    
+    let tick = |ts: i64, price: f64, volume: i64| -> HashMap<String, Value> {
+        vec![
+            ("timestamp".to_string(), Value::Integer(ts)),
+            ("price".to_string(), Value::Number(price)),
+            ("volume".to_string(), Value::Integer(volume)),
+        ].into_iter().collect()
+    };
+    let bar = |ts: i64, o: f64, h: f64, l: f64, c: f64, v: f64| -> HashMap<String, Value> {
+        vec![
+            ("timestamp".to_string(), Value::Integer(ts)),
+            ("open".to_string(), Value::Number(o)),
+            ("high".to_string(), Value::Number(h)),
+            ("low".to_string(), Value::Number(l)),
+            ("close".to_string(), Value::Number(c)),
+            ("volume".to_string(), Value::Number(v)),
+        ].into_iter().collect()
+    };
+    
+    let mut streaming = StreamingExecutor::new(script.clone(), 10);
+    streaming.set_bar_builder(BarBuilder::new(60, "timestamp").with_emit_partial(true));
+    
+    let mut bars = Vec::new();
+    for t in [tick(0, 10.0, 1), tick(15, 12.0, 2), tick(40, 9.0, 3), tick(59, 11.0, 4),
+              tick(60, 11.5, 5), tick(100, 13.0, 1),
+              // 空缺的区间不产生 bar
+              tick(185, 14.0, 2), tick(190, 13.5, 2)] {
+        if let Some(row) = streaming.push_tick(t).unwrap() {
+            bars.push(row);
+        }
+    }
+    assert_eq!(bars, vec![
+        bar(0, 10.0, 12.0, 9.0, 11.0, 10.0),
+        bar(60, 11.5, 13.0, 11.5, 13.0, 6.0),
+    ]);
+    
+    // 结束时输出未完成的 bar，之后没有剩余
+    assert_eq!(streaming.finish().unwrap(), Some(bar(180, 14.0, 14.0, 13.5, 13.5, 4.0)));
+    assert_eq!(streaming.finish().unwrap(), None);
+    
+    // 乱序 tick 报错；默认不输出未完成的 bar
+    let mut streaming = StreamingExecutor::new(script, 10);
+    streaming.set_bar_builder(BarBuilder::new(60, "timestamp"));
+    assert_eq!(streaming.push_tick(tick(120, 10.0, 1)).unwrap(), None);
+    assert!(streaming.push_tick(tick(30, 10.0, 1)).is_err());
+    assert_eq!(streaming.finish().unwrap(), None);
+}
+
+#[test]
+fn test_ohlc_validation() {
+    let run = |source: &str| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        Executor::new().execute_data_script(&script)
+    };
+    
+    let result = run(r#"
+return [validate_ohlc(10, 12, 9, 11), validate_ohlc(10, 9, 12, 11), validate_ohlc(-1, 12, -2, 11), validate_ohlc(10, 12, 9, 11, -5), validate_ohlc(10, 12, 9, null)]
+"#).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Bool(true),
+        // high < low
+        Value::Bool(false),
+        // 负价格
+        Value::Bool(false),
+        // 负成交量
+        Value::Bool(false),
+        // 缺失数据
+        Value::Bool(false),
+    ])));
+    
+    // 逐元素清洗：第二根 high < low，第三根 close 超出 [low, high]
+    let result = run(r#"
+[o, h, l, c] = clean_ohlc([10, 10, 10], [12, 9, 12], [9, 12, 9], [11, 11, 13])
+return [o, h, c]
+"#).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Array(vec![Value::Integer(10), Value::Null, Value::Null]),
+        Value::Array(vec![Value::Integer(12), Value::Null, Value::Null]),
+        Value::Array(vec![Value::Integer(11), Value::Null, Value::Null]),
+    ])));
+    
+    let result = run("return [in_range(5, 0, 10), in_range(-1, 0, 10), in_range([0, 10, 11], 0, 10), in_range(null, 0, 1)]").unwrap();
+    assert_eq!(result, Some(Value::Array(vec![
+        Value::Bool(true),
+        Value::Bool(false),
+        Value::Array(vec![Value::Bool(true), Value::Bool(true), Value::Bool(false)]),
+        Value::Null,
+    ])));
+    assert!(run("return in_range(1, 5, 0)").is_err());
+}
+
+#[test]
+fn test_coalesce_skips_later_arguments() {
+    let run = |source: &str, x: Value| {
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        let mut executor = Executor::new();
+        executor.set_input("x".to_string(), x);
+        executor.execute_data_script(&script)
+    };
+    
+    // 第一个参数非 null 时，会出错的后备参数不被求值
+    let source = "return [coalesce(x, no_such_fn()), nvl(x, 1 / 0), if_null(x, no_such_fn())]";
+    let result = run(source, Value::Integer(7)).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![Value::Integer(7); 3])));
+    
+    // 为 null 时才求值后备参数
+    let err = run(source, Value::Null).unwrap_err();
+    assert_eq!(err.name.as_deref(), Some("no_such_fn"));
+    let result = run("return [coalesce(x, null, ...[null, 3], 4), nvl(x, 5)]", Value::Null).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![Value::Integer(3), Value::Integer(5)])));
+    
+    assert!(run("return nvl(x, 1, 2)", Value::Null).is_err());
+    
+    // 管道形式：左侧值作为第一个参数，同样按需求值
+    let source = "return [x |> coalesce(0), x |> nvl(no_such_fn()), x |> if_null(null) |> coalesce(9)]";
+    let result = run(source, Value::Integer(7)).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![Value::Integer(7); 3])));
+    let result = run("return [x |> coalesce(null, 0), x |> if_null(null) |> coalesce(9)]", Value::Null).unwrap();
+    assert_eq!(result, Some(Value::Array(vec![Value::Integer(0), Value::Integer(9)])));
+    assert!(run("return x |> nvl(1, 2)", Value::Null).is_err());
+}
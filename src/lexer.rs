@@ -1,8 +1,10 @@
 // DPLang 词法分析器
 
+use std::collections::HashMap;
 use std::fmt;
 
 /// f-string 的组成部分
+/// 文本中的字面量花括号以 `{{` / `}}` 书写，解析后已还原为 `{` / `}`
 #[derive(Debug, Clone, PartialEq)]
 pub enum FStringPart {
     Text(String),       // 普通文本
@@ -31,10 +33,13 @@ pub enum TokenType {
     Error,      // -- ERROR --
     ErrorEnd,   // -- ERROR_END --
     Precision(String),  // -- PRECISION ... --
+    Config(String),     // -- CONFIG key=value, ... --
+    Export(String),     // -- EXPORT name1, name2 -- （包的公开成员）
     
     // 标识符和字面量
     Identifier(String),
     Number(f64),
+    Integer(i64),
     String(String),
     FString(Vec<FStringPart>),  // f-string 支持
     
@@ -74,6 +79,7 @@ pub enum TokenType {
     Comma,        // ,
     Colon,        // :
     Question,     // ?
+    At,           // @
     Spread,       // ...
     Dot,          // .
     Underscore,   // _
@@ -90,6 +96,7 @@ impl fmt::Display for TokenType {
         match self {
             TokenType::Identifier(s) => write!(f, "Identifier({})", s),
             TokenType::Number(n) => write!(f, "Number({})", n),
+            TokenType::Integer(n) => write!(f, "Integer({})", n),
             TokenType::String(s) => write!(f, "String(\"{}\")", s),
             _ => write!(f, "{:?}", self),
         }
@@ -127,6 +134,25 @@ impl fmt::Display for LexError {
 
 impl std::error::Error for LexError {}
 
+/// 词法配置：注释标记和特殊声明的定界符
+/// 默认 `# 注释` 与 `-- INPUT ... --`；数据中常以 `#`/`--` 开头的场景可改用如 `//` 与 `@@INPUT ...@@`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexerConfig {
+    /// 行注释标记
+    pub comment_marker: String,
+    /// 特殊声明的开始和结束标记
+    pub declaration_marker: String,
+}
+
+impl Default for LexerConfig {
+    fn default() -> Self {
+        LexerConfig {
+            comment_marker: "#".to_string(),
+            declaration_marker: "--".to_string(),
+        }
+    }
+}
+
 /// 词法分析器
 pub struct Lexer {
     source: Vec<char>,
@@ -134,7 +160,15 @@ pub struct Lexer {
     line: usize,
     column: usize,
     indent_stack: Vec<usize>,  // 缩进栈
+    indent_text_stack: Vec<String>, // 各层缩进的原始字符（检查 tab/空格 一致性）
+    brace_depth: usize,        // { } 嵌套深度，花括号块内不计缩进
+    group_stack: Vec<char>,    // 未闭合的 ( [ {，最内层为 ( 或 [ 时换行不结束语句
+    continues_line: bool,      // 上一个 token 是二元运算符，下一行视为续行
     pending_tokens: Vec<Token>, // 待发送的 token
+    keyword_aliases: HashMap<String, String>, // 关键字别名 -> 英文关键字
+    comment_count: usize,      // 跳过的注释数（格式化器据此提示注释会丢失）
+    comment_marker: Vec<char>, // 行注释标记
+    declaration_marker: Vec<char>, // 特殊声明定界符
 }
 
 impl Lexer {
@@ -145,8 +179,61 @@ impl Lexer {
             line: 1,
             column: 1,
             indent_stack: vec![0],
+            indent_text_stack: vec![String::new()],
+            brace_depth: 0,
+            group_stack: Vec::new(),
+            continues_line: false,
             pending_tokens: Vec::new(),
+            keyword_aliases: HashMap::new(),
+            comment_count: 0,
+            comment_marker: vec!['#'],
+            declaration_marker: vec!['-', '-'],
+        }
+    }
+    
+    /// 创建使用自定义注释标记和声明定界符的词法分析器
+    /// 标记不能为空、不能含空白，两者不能相同
+    pub fn with_config(source: &str, config: &LexerConfig) -> Result<Self, LexError> {
+        let config_error = |message: String| LexError { message, line: 0, column: 0 };
+        
+        for marker in [&config.comment_marker, &config.declaration_marker] {
+            if marker.is_empty() || marker.chars().any(char::is_whitespace) {
+                return Err(config_error(format!("无效的标记: {:?}（不能为空或含空白）", marker)));
+            }
+        }
+        if config.comment_marker == config.declaration_marker {
+            return Err(config_error(format!("注释标记与声明定界符相同: {}", config.comment_marker)));
         }
+        
+        let mut lexer = Lexer::new(source);
+        lexer.comment_marker = config.comment_marker.chars().collect();
+        lexer.declaration_marker = config.declaration_marker.chars().collect();
+        Ok(lexer)
+    }
+    
+    /// 创建带关键字别名的词法分析器，如 {"如果": "if", "返回": "return"}
+    /// 别名与英文关键字产生相同的 token；别名必须是合法标识符且不能与已有关键字冲突
+    pub fn with_keywords(source: &str, aliases: &HashMap<String, String>) -> Result<Self, LexError> {
+        let alias_error = |message: String| LexError { message, line: 0, column: 0 };
+        
+        for (alias, keyword) in aliases {
+            if Self::keyword_token(keyword).is_none() {
+                return Err(alias_error(format!("别名 {} 指向的 {} 不是关键字", alias, keyword)));
+            }
+            let mut chars = alias.chars();
+            let valid = chars.next().is_some_and(Self::is_identifier_start)
+                && chars.all(Self::is_identifier_continue);
+            if !valid {
+                return Err(alias_error(format!("关键字别名不是合法的标识符: {}", alias)));
+            }
+            if Self::keyword_token(alias).is_some() {
+                return Err(alias_error(format!("关键字别名与已有关键字冲突: {}", alias)));
+            }
+        }
+        
+        let mut lexer = Lexer::new(source);
+        lexer.keyword_aliases = aliases.clone();
+        Ok(lexer)
     }
     
     /// 获取所有 tokens
@@ -165,8 +252,32 @@ impl Lexer {
         Ok(tokens)
     }
     
+    /// 已跳过的注释数
+    pub fn comment_count(&self) -> usize {
+        self.comment_count
+    }
+    
     /// 获取下一个 token
     pub fn next_token(&mut self) -> Result<Token, LexError> {
+        let token = self.scan_token()?;
+        self.continues_line = Self::is_continuation_operator(&token.token_type);
+        Ok(token)
+    }
+    
+    /// 行尾出现时表示表达式在下一行继续的运算符
+    fn is_continuation_operator(token_type: &TokenType) -> bool {
+        matches!(
+            token_type,
+            TokenType::Plus | TokenType::Minus | TokenType::Star | TokenType::Slash
+                | TokenType::Percent | TokenType::Caret
+                | TokenType::Greater | TokenType::Less | TokenType::GreaterEq | TokenType::LessEq
+                | TokenType::Equal | TokenType::NotEqual
+                | TokenType::And | TokenType::Or
+                | TokenType::Pipeline | TokenType::Question
+        )
+    }
+    
+    fn scan_token(&mut self) -> Result<Token, LexError> {
         // 先返回待发送的 token
         if !self.pending_tokens.is_empty() {
             return Ok(self.pending_tokens.remove(0));
@@ -190,22 +301,42 @@ impl Lexer {
         // 处理换行
         if ch == '\n' {
             self.advance();
-            let indent_tokens = self.handle_indent()?;
-            if !indent_tokens.is_empty() {
+            // 续行：在 ( ) 或 [ ] 内，或上一行以二元运算符结尾时，换行不结束语句
+            if self.continues_line || matches!(self.group_stack.last(), Some('(' | '[')) {
+                return self.next_token();
+            }
+            // 换行之后再发送缩进变化（花括号块内忽略缩进）
+            if self.brace_depth == 0 {
+                let indent_tokens = self.handle_indent()?;
                 self.pending_tokens.extend(indent_tokens);
-                return Ok(self.pending_tokens.remove(0));
             }
             return Ok(Token::new(TokenType::Newline, "\n".to_string(), start_line, start_column));
         }
         
+        // 显式续行：行尾的 \ 连接下一行
+        if ch == '\\' {
+            let mut offset = 1;
+            while matches!(self.peek_ahead(offset), Some(' ' | '\t' | '\r')) {
+                offset += 1;
+            }
+            if matches!(self.peek_ahead(offset), Some('\n') | None) {
+                for _ in 0..=offset {
+                    if !self.is_at_end() {
+                        self.advance();
+                    }
+                }
+                return self.next_token();
+            }
+        }
+        
         // 注释
-        if ch == '#' {
+        if self.at_marker(&self.comment_marker) {
             self.skip_comment();
             return self.next_token();
         }
         
         // 特殊声明 -- XXX --
-        if ch == '-' && self.peek_ahead(1) == Some('-') {
+        if self.at_marker(&self.declaration_marker) {
             return self.scan_special_declaration();
         }
         
@@ -245,6 +376,10 @@ impl Lexer {
         }
     }
     
+    fn peek_next(&self) -> char {
+        self.source.get(self.current + 1).copied().unwrap_or('\0')
+    }
+    
     fn peek_ahead(&self, n: usize) -> Option<char> {
         let pos = self.current + n;
         if pos < self.source.len() {
@@ -254,6 +389,11 @@ impl Lexer {
         }
     }
     
+    /// 当前位置是否以该标记开头
+    fn at_marker(&self, marker: &[char]) -> bool {
+        self.source[self.current..].starts_with(marker)
+    }
+    
     fn advance(&mut self) -> char {
         let ch = self.source[self.current];
         self.current += 1;
@@ -282,6 +422,7 @@ impl Lexer {
     }
     
     fn skip_comment(&mut self) {
+        self.comment_count += 1;
         while !self.is_at_end() && self.peek() != '\n' {
             self.advance();
         }
@@ -289,6 +430,7 @@ impl Lexer {
     
     fn handle_indent(&mut self) -> Result<Vec<Token>, LexError> {
         let mut indent_level = 0;
+        let mut indent_text = String::new();
         let start_line = self.line;
         
         // 计算缩进级别
@@ -296,17 +438,17 @@ impl Lexer {
             let ch = self.peek();
             if ch == ' ' {
                 indent_level += 1;
-                self.advance();
+                indent_text.push(self.advance());
             } else if ch == '\t' {
                 indent_level += 4;  // tab = 4 spaces
-                self.advance();
+                indent_text.push(self.advance());
             } else {
                 break;
             }
         }
         
         // 空行或注释行不影响缩进
-        if self.is_at_end() || self.peek() == '\n' || self.peek() == '#' {
+        if self.is_at_end() || self.peek() == '\n' || self.at_marker(&self.comment_marker) {
             return Ok(Vec::new());
         }
         
@@ -314,8 +456,10 @@ impl Lexer {
         let current_indent = *self.indent_stack.last().unwrap();
         
         if indent_level > current_indent {
-            // 缩进增加
+            // 缩进增加：新缩进必须以外层缩进为前缀
+            self.check_indent_consistency(&indent_text, false, start_line)?;
             self.indent_stack.push(indent_level);
+            self.indent_text_stack.push(indent_text);
             tokens.push(Token::new(TokenType::Indent, " ".repeat(indent_level), start_line, 1));
         } else if indent_level < current_indent {
             // 缩进减少
@@ -324,6 +468,7 @@ impl Lexer {
                     break;
                 }
                 self.indent_stack.pop();
+                self.indent_text_stack.pop();
                 tokens.push(Token::new(TokenType::Dedent, String::new(), start_line, 1));
             }
             
@@ -335,14 +480,39 @@ impl Lexer {
                     column: 1,
                 });
             }
+            self.check_indent_consistency(&indent_text, true, start_line)?;
+        } else {
+            self.check_indent_consistency(&indent_text, true, start_line)?;
         }
         
         Ok(tokens)
     }
     
+    /// 检查缩进字符与所在块是否一致（同一块内不能一行用 tab、另一行用空格）
+    /// exact 为 true 时要求与块缩进完全相同，否则要求以块缩进为前缀
+    fn check_indent_consistency(&self, indent_text: &str, exact: bool, line: usize) -> Result<(), LexError> {
+        let block_text = self.indent_text_stack.last().map(|s| s.as_str()).unwrap_or("");
+        let consistent = if exact {
+            indent_text == block_text
+        } else {
+            indent_text.starts_with(block_text)
+        };
+        
+        if consistent {
+            Ok(())
+        } else {
+            Err(LexError {
+                message: "缩进中 tab 与空格的使用不一致".to_string(),
+                line,
+                column: 1,
+            })
+        }
+    }
+    
     fn handle_eof_dedents(&mut self) {
         while self.indent_stack.len() > 1 {
             self.indent_stack.pop();
+            self.indent_text_stack.pop();
             self.pending_tokens.push(Token::new(TokenType::Dedent, String::new(), self.line, self.column));
         }
     }
@@ -376,6 +546,13 @@ impl Lexer {
             }
         }
         
+        // 纯整数字面量（超出 i64 范围时退化为 Number）
+        if num_str.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(i) = num_str.parse::<i64>() {
+                return Ok(Token::new(TokenType::Integer(i), num_str, start_line, start_column));
+            }
+        }
+        
         match num_str.parse::<f64>() {
             Ok(n) => Ok(Token::new(TokenType::Number(n), num_str, start_line, start_column)),
             Err(_) => Err(LexError {
@@ -437,7 +614,11 @@ impl Lexer {
         self.advance(); // 跳过开始引号
         
         while !self.is_at_end() && self.peek() != quote {
-            if self.peek() == '{' {
+            if (self.peek() == '{' && self.peek_next() == '{') || (self.peek() == '}' && self.peek_next() == '}') {
+                // {{ 和 }} 转义为字面量花括号
+                current_text.push(self.advance());
+                self.advance();
+            } else if self.peek() == '{' {
                 // 当前文本保存为 Text 部分
                 if !current_text.is_empty() {
                     parts.push(FStringPart::Text(current_text.clone()));
@@ -522,7 +703,16 @@ impl Lexer {
             ident.push(self.advance());
         }
         
-        let token_type = match ident.as_str() {
+        let word = self.keyword_aliases.get(&ident).unwrap_or(&ident);
+        let token_type = Self::keyword_token(word)
+            .unwrap_or_else(|| TokenType::Identifier(ident.clone()));
+        
+        Ok(Token::new(token_type, ident, start_line, start_column))
+    }
+    
+    /// 关键字对应的 token（非关键字返回 None）
+    fn keyword_token(word: &str) -> Option<TokenType> {
+        let token_type = match word {
             "return" => TokenType::Return,
             "if" => TokenType::If,
             "elif" => TokenType::Elif,
@@ -537,15 +727,15 @@ impl Lexer {
             "or" => TokenType::Or,
             "not" => TokenType::Not,
             "_" => TokenType::Underscore,
-            _ => TokenType::Identifier(ident.clone()),
+            _ => return None,
         };
-        
-        Ok(Token::new(token_type, ident, start_line, start_column))
+        Some(token_type)
     }
     
     fn scan_special_declaration(&mut self) -> Result<Token, LexError> {
         let start_line = self.line;
         let start_column = self.column;
+        let marker: String = self.declaration_marker.iter().collect();
         let mut text = String::new();
         
         // 读取 -- XXX ... -- 部分
@@ -555,18 +745,27 @@ impl Lexer {
             self.advance();
             
             // 如果找到第二个 --，停止
-            if text.len() >= 4 && text.ends_with("--") {
+            if text.len() >= marker.len() * 2 && text.ends_with(&marker) {
                 break;
             }
         }
         
+        // 缺少结尾的 -- 时报错，避免把整行内容误当作声明
+        if text.len() < marker.len() * 2 || !text.ends_with(&marker) {
+            return Err(LexError {
+                message: format!("未闭合的声明: {}（缺少结尾的 {}）", text.trim_end(), marker),
+                line: start_line,
+                column: start_column,
+            });
+        }
+        
         let text_upper = text.to_uppercase();
         
         // 提取声明中的内容（在两个 -- 之间）
-        let content = if let Some(start_pos) = text.find("--") {
-            let end_pos = text.rfind("--").unwrap();
-            if end_pos > start_pos + 2 {
-                text[start_pos + 2..end_pos].trim().to_string()
+        let content = if let Some(start_pos) = text.find(&marker) {
+            let end_pos = text.rfind(&marker).unwrap();
+            if end_pos > start_pos + marker.len() {
+                text[start_pos + marker.len()..end_pos].trim().to_string()
             } else {
                 String::new()
             }
@@ -574,7 +773,14 @@ impl Lexer {
             String::new()
         };
         
-        let token_type = if text_upper.contains("INPUT") {
+        let token_type = if content.to_uppercase().starts_with("CONFIG") {
+            // 移除 "CONFIG" 前缀，只保留 key=value 列表
+            // 需最先判断，避免 "precision=4" 之类的配置项被误识别为 PRECISION
+            TokenType::Config(content[6..].trim().to_string())
+        } else if content.to_uppercase().starts_with("EXPORT") {
+            // 同样先于 INPUT/OUTPUT 判断，避免成员名（如 output_of）被误识别
+            TokenType::Export(content[6..].trim().to_string())
+        } else if text_upper.contains("INPUT") {
             // 移除 "INPUT" 前缀，只保留参数列表
             let param_content = content.trim_start_matches("INPUT")
                 .trim_start_matches("input")
@@ -602,8 +808,13 @@ impl Lexer {
         } else if text_upper.contains("PRECISION") {
             TokenType::Precision(content)
         } else {
+            let keyword = content.split_whitespace().next().unwrap_or("").to_uppercase();
+            let message = match suggest_declaration(&keyword) {
+                Some(suggestion) => format!("未知的特殊声明: {}（是否想写 {}？）", text, suggestion),
+                None => format!("未知的特殊声明: {}", text),
+            };
             return Err(LexError {
-                message: format!("未知的特殊声明: {}", text),
+                message,
                 line: start_line,
                 column: start_column,
             });
@@ -679,15 +890,36 @@ impl Lexer {
                     });
                 }
             },
-            '(' => TokenType::LeftParen,
-            ')' => TokenType::RightParen,
-            '[' => TokenType::LeftBracket,
-            ']' => TokenType::RightBracket,
-            '{' => TokenType::LeftBrace,
-            '}' => TokenType::RightBrace,
+            '(' => {
+                self.group_stack.push('(');
+                TokenType::LeftParen
+            }
+            ')' => {
+                self.group_stack.pop();
+                TokenType::RightParen
+            }
+            '[' => {
+                self.group_stack.push('[');
+                TokenType::LeftBracket
+            }
+            ']' => {
+                self.group_stack.pop();
+                TokenType::RightBracket
+            }
+            '{' => {
+                self.brace_depth += 1;
+                self.group_stack.push('{');
+                TokenType::LeftBrace
+            }
+            '}' => {
+                self.brace_depth = self.brace_depth.saturating_sub(1);
+                self.group_stack.pop();
+                TokenType::RightBrace
+            }
             ',' => TokenType::Comma,
             ':' => TokenType::Colon,
             '?' => TokenType::Question,
+            '@' => TokenType::At,
             '.' => {
                 if !self.is_at_end() && self.peek() == '.' && self.peek_ahead(1) == Some('.') {
                     self.advance();
@@ -733,6 +965,39 @@ impl Lexer {
     }
 }
 
+/// 特殊声明的关键字
+const DECLARATION_KEYWORDS: &[&str] = &["INPUT", "OUTPUT", "IMPORT", "ERROR", "ERROR_END", "PRECISION", "CONFIG", "EXPORT"];
+
+/// 为拼错的声明关键字找最接近的有效关键字（编辑距离不超过 2）
+fn suggest_declaration(keyword: &str) -> Option<&'static str> {
+    if keyword.is_empty() {
+        return None;
+    }
+    DECLARATION_KEYWORDS
+        .iter()
+        .map(|candidate| (levenshtein(keyword, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// 编辑距离（按字符计算）
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + usize::from(ca != *cb);
+            current[j + 1] = substitute.min(prev[j + 1] + 1).min(current[j] + 1);
+        }
+        prev = current;
+    }
+    
+    prev[b.len()]
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -766,7 +1031,7 @@ mod tests {
         let mut lexer = Lexer::new(source);
         let tokens = lexer.tokenize().unwrap();
         
-        assert!(matches!(tokens[0].token_type, TokenType::Number(123.0)));
+        assert!(matches!(tokens[0].token_type, TokenType::Integer(123)));
         assert!(matches!(tokens[1].token_type, TokenType::Number(45.67)));
     }
     
@@ -780,4 +1045,117 @@ mod tests {
         assert!(matches!(tokens[12].token_type, TokenType::Arrow));
         assert!(matches!(tokens[13].token_type, TokenType::Pipeline));
     }
+    
+    #[test]
+    fn test_mixed_tab_space_indent_error() {
+        // 同一块内一行用 tab、一行用 4 个空格
+        let source = "if x > 0:\n\ty = 1\n    z = 2\n";
+        let mut lexer = Lexer::new(source);
+        let err = lexer.tokenize().unwrap_err();
+        
+        assert!(err.message.contains("tab"));
+        assert_eq!(err.line, 3);
+    }
+    
+    #[test]
+    fn test_consistent_indent_passes() {
+        let tab_source = "if x > 0:\n\ty = 1\n\tif y > 0:\n\t\tz = 2\nw = 3\n";
+        assert!(Lexer::new(tab_source).tokenize().is_ok());
+        
+        let space_source = "if x > 0:\n    y = 1\n    if y > 0:\n        z = 2\nw = 3\n";
+        assert!(Lexer::new(space_source).tokenize().is_ok());
+    }
+    
+    #[test]
+    fn test_fstring_brace_escape() {
+        let source = r#"f"use {{braces}} and {x}""#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        
+        if let TokenType::FString(parts) = &tokens[0].token_type {
+            assert_eq!(parts, &vec![
+                FStringPart::Text("use {braces} and ".to_string()),
+                FStringPart::Expr("x".to_string()),
+            ]);
+        } else {
+            panic!("Expected f-string");
+        }
+    }
+    
+    #[test]
+    fn test_chinese_keyword_aliases() {
+        let aliases: HashMap<String, String> = [("如果", "if"), ("否则", "else"), ("返回", "return"), ("且", "and")]
+            .iter()
+            .map(|(alias, keyword)| (alias.to_string(), keyword.to_string()))
+            .collect();
+        
+        let english = "if x > 0 and y > 0:\n    return x\nelse:\n    return y\n";
+        let chinese = "如果 x > 0 且 y > 0:\n    返回 x\n否则:\n    返回 y\n";
+        
+        let expected = Lexer::new(english).tokenize().unwrap();
+        let actual = Lexer::with_keywords(chinese, &aliases).unwrap().tokenize().unwrap();
+        let types = |tokens: &[Token]| tokens.iter().map(|t| t.token_type.clone()).collect::<Vec<_>>();
+        assert_eq!(types(&actual), types(&expected));
+        
+        // 英文关键字仍然可用
+        let mixed = Lexer::with_keywords("如果 x and y", &aliases).unwrap().tokenize().unwrap();
+        assert!(matches!(mixed[0].token_type, TokenType::If));
+        assert!(matches!(mixed[2].token_type, TokenType::And));
+        
+        // 别名不能与已有关键字冲突
+        let clash: HashMap<String, String> = [("or".to_string(), "and".to_string())].into_iter().collect();
+        assert!(Lexer::with_keywords("", &clash).is_err());
+    }
+    
+    #[test]
+    fn test_custom_comment_and_declaration_markers() {
+        let types = |tokens: &[Token]| tokens.iter().map(|t| t.token_type.clone()).collect::<Vec<_>>();
+        let expected = Lexer::new("-- INPUT close:number --\n# 注释\nx = close / 2  # 行尾注释\n").tokenize().unwrap();
+        
+        let comments = LexerConfig { comment_marker: "//".to_string(), ..LexerConfig::default() };
+        let actual = Lexer::with_config("-- INPUT close:number --\n// 注释\nx = close / 2  // 行尾注释\n", &comments)
+            .unwrap()
+            .tokenize()
+            .unwrap();
+        assert_eq!(types(&actual), types(&expected));
+        
+        // 自定义声明定界符后，-- 开头的行不再是声明
+        let declarations = LexerConfig { declaration_marker: "@@".to_string(), ..LexerConfig::default() };
+        let actual = Lexer::with_config("@@INPUT close:number@@\n# 注释\nx = close / 2  # 行尾注释\n", &declarations)
+            .unwrap()
+            .tokenize()
+            .unwrap();
+        assert_eq!(types(&actual), types(&expected));
+        let tokens = Lexer::with_config("x = --1\n", &declarations).unwrap().tokenize().unwrap();
+        assert!(matches!(tokens[2].token_type, TokenType::Minus));
+        
+        let unclosed = Lexer::with_config("@@INPUT close:number\n", &declarations).unwrap().tokenize().unwrap_err();
+        assert!(unclosed.message.contains("缺少结尾的 @@"), "{}", unclosed.message);
+        
+        // 标记为空或两者相同时报错
+        let empty = LexerConfig { comment_marker: String::new(), ..LexerConfig::default() };
+        assert!(Lexer::with_config("", &empty).is_err());
+        let same = LexerConfig { comment_marker: "--".to_string(), ..LexerConfig::default() };
+        assert!(Lexer::with_config("", &same).is_err());
+    }
+    
+    #[test]
+    fn test_declaration_typo_suggestion() {
+        let mut lexer = Lexer::new("-- INPT x:number --\n");
+        let err = lexer.tokenize().unwrap_err();
+        assert!(err.message.contains("是否想写 INPUT"), "{}", err.message);
+        
+        // 相差太远时不给建议
+        let mut lexer = Lexer::new("-- HELLO --\n");
+        let err = lexer.tokenize().unwrap_err();
+        assert!(err.message.starts_with("未知的特殊声明") && !err.message.contains("是否想写"));
+    }
+    
+    #[test]
+    fn test_unterminated_declaration() {
+        let mut lexer = Lexer::new("-- INPUT x:number\n-- OUTPUT y:number --\n");
+        let err = lexer.tokenize().unwrap_err();
+        assert!(err.message.starts_with("未闭合的声明"), "{}", err.message);
+        assert_eq!(err.line, 1);
+    }
 }
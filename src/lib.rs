@@ -10,4 +10,4 @@ pub mod api;
 
 // 导出公共 API
 pub use api::DPLangInterpreter;
-pub use api::{parse_csv, format_output_csv};
+pub use api::{parse_csv, parse_csv_with, parse_json_record, format_output_csv, format_output_csv_ordered, format_output_csv_typed, parse_csv_typed, csv_duplicate_headers, CsvOptions, NumberLocale};
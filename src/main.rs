@@ -2,15 +2,20 @@
 
 use dplang::{
     lexer::Lexer,
-    parser::Parser,
-    executor::DataStreamExecutor,
-    runtime::Value,
-    api::{parse_csv, format_output_csv},
+    parser::{Parser, Script, format::format_script},
+    executor::{DataStreamExecutor, GroupedExecutor, ErrorPolicy, StreamingExecutor, BarBuilder, OutputQueue, OutputQueueConfig, BackpressurePolicy},
+    runtime::{Value, RuntimeError},
+    semantic::SemanticAnalyzer,
+    api::{parse_csv, format_output_csv, format_row_csv, run_tick_stream, run_csv_pipe, StreamInputFormat, ExecutionOverrides, OutputWindow, SelectPredicate},
 };
 use std::collections::HashMap;
 use std::env;
 use std::fs;
-use std::io::{self, Write};
+use std::io::{self, BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -24,18 +29,151 @@ fn main() {
         "run" => {
             if args.len() < 3 {
                 eprintln!("错误: 请指定要运行的脚本文件");
-                eprintln!("用法: dplang run <script.dp> [data.csv]");
+                eprintln!("用法: dplang run <script.dp> [data.csv] [--precision <N>] [--div-mode strict|null] [--param name=value]");
+                eprintln!("      [--offset <N>] [--limit <N>]");
                 return;
             }
             
+            let (overrides, window) = match ExecutionOverrides::from_args(&args)
+                .and_then(|o| OutputWindow::from_args(&args).map(|w| (o, w)))
+            {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    eprintln!("错误: {}", e);
+                    return;
+                }
+            };
+            
             let script_path = &args[2];
-            let csv_path = if args.len() >= 4 {
-                Some(&args[3])
-            } else {
-                None
+            let csv_path = args.get(3).filter(|arg| !arg.starts_with("--"));
+            
+            run_script(script_path, csv_path, &overrides, &window);
+        }
+        "monitor" => {
+            if args.len() < 3 {
+                eprintln!("错误: 请指定要运行的脚本文件");
+                eprintln!("用法: dplang monitor <script.dp> [--input csv|json] [--window <size>] [--queue <size>] [--drop-when-full] [--bar <secs> [--time-col <col>] [--emit-partial]]");
+                return;
+            }
+            
+            let input_format = parse_option(&args, "--input").unwrap_or("csv");
+            if input_format != "csv" && input_format != "json" {
+                eprintln!("错误: 不支持的输入格式 '{}'（可选 csv 或 json）", input_format);
+                return;
+            }
+            
+            let window_size = parse_option(&args, "--window")
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(1000);
+            
+            // 输出队列：容量与队列满时的策略（默认阻塞反压）
+            let queue_config = OutputQueueConfig {
+                capacity: parse_option(&args, "--queue")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1024),
+                policy: if args.iter().any(|arg| arg == "--drop-when-full") {
+                    BackpressurePolicy::DropNewest
+                } else {
+                    BackpressurePolicy::Block
+                },
+            };
+            
+            // K 线合成：tick 先按 --bar 秒聚合为 OHLCV，bar 完成时才执行脚本
+            let bar_builder = match parse_option(&args, "--bar") {
+                Some(secs) => match secs.parse::<u64>() {
+                    Ok(secs) if secs > 0 => Some(
+                        BarBuilder::new(secs, parse_option(&args, "--time-col").unwrap_or("timestamp"))
+                            .with_emit_partial(args.iter().any(|arg| arg == "--emit-partial")),
+                    ),
+                    _ => {
+                        eprintln!("错误: --bar 需要正整数秒数，得到 '{}'", secs);
+                        return;
+                    }
+                },
+                None => None,
+            };
+            
+            run_monitor(&args[2], input_format == "json", window_size, queue_config, bar_builder);
+        }
+        "pipe" => {
+            if args.len() < 3 {
+                eprintln!("错误: 请指定要运行的脚本文件");
+                eprintln!("用法: dplang pipe <script.dp> [--window <size>] < input.csv > output.csv");
+                return;
+            }
+            
+            let window_size = parse_option(&args, "--window")
+                .and_then(|s| s.parse::<usize>().ok())
+                .unwrap_or(1000);
+            run_pipe(&args[2], window_size);
+        }
+        "check" => {
+            if args.len() < 3 {
+                eprintln!("错误: 请指定要检查的脚本文件");
+                eprintln!("用法: dplang check <script.dp> [--format text|json]");
+                return;
+            }
+            
+            let format = parse_option(&args, "--format").unwrap_or("text");
+            if format != "text" && format != "json" {
+                eprintln!("错误: 不支持的输出格式 '{}'（可选 text 或 json）", format);
+                return;
+            }
+            
+            run_check(&args[2], format == "json");
+        }
+        "fmt" => {
+            if args.len() < 3 {
+                eprintln!("错误: 请指定要格式化的脚本文件");
+                eprintln!("用法: dplang fmt <script.dp> [--stdout] [--force]");
+                return;
+            }
+            
+            let to_stdout = args.iter().any(|arg| arg == "--stdout");
+            let force = args.iter().any(|arg| arg == "--force");
+            run_fmt(&args[2], to_stdout, force);
+        }
+        "screen" => {
+            if args.len() < 4 {
+                eprintln!("错误: 请指定策略脚本和股票数据文件");
+                eprintln!("用法: dplang screen <strategy.dp> <stocks.csv> [--output <file>] [--select <条件>]");
+                eprintln!("      [--group-by <列>] [--time <列>] [--rank <列>] [--zscore <列>] [--jobs <N>]");
+                eprintln!("      [--offset <N>] [--limit <N>] [--passthrough <列,列>]");
+                return;
+            }
+            
+            let window = match OutputWindow::from_args(&args) {
+                Ok(w) => w,
+                Err(e) => {
+                    eprintln!("错误: {}", e);
+                    return;
+                }
             };
             
-            run_script(script_path, csv_path);
+            let predicate = match parse_option(&args, "--select").map(SelectPredicate::parse) {
+                Some(Ok(p)) => p,
+                Some(Err(e)) => {
+                    eprintln!("错误: {}", e);
+                    return;
+                }
+                None => SelectPredicate::default(),
+            };
+            
+            let cross = CrossSection {
+                group_by: parse_option(&args, "--group-by"),
+                time_key: parse_option(&args, "--time"),
+                rank: parse_option(&args, "--rank"),
+                zscore: parse_option(&args, "--zscore"),
+                jobs: parse_option(&args, "--jobs")
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .unwrap_or(1),
+                passthrough: parse_option(&args, "--passthrough")
+                    .map(|cols| cols.split(',').map(|c| c.trim().to_string()).filter(|c| !c.is_empty()).collect())
+                    .unwrap_or_default(),
+            };
+            
+            let output_file = parse_option(&args, "--output").unwrap_or("./selected_stocks.csv");
+            run_screen(&args[2], &args[3], output_file, &predicate, &cross, &window);
         }
         "help" | "-h" | "--help" => {
             print_usage();
@@ -54,6 +192,27 @@ fn print_usage() {
     println!("DPLang v0.4.0 - 流式数据处理语言解释器\n");
     println!("用法:");
     println!("  dplang run <script.dp> [data.csv]    执行脚本");
+    println!("      [--precision <N>] [--div-mode strict|null]   覆盖脚本中的精度与除法模式");
+    println!("      [--param name=value]                   注入只读参数（可重复）");
+    println!("      [--offset <N>] [--limit <N>]           跳过前 N 行 / 最多输出 N 行");
+    println!("  dplang monitor <script.dp> [--input csv|json] [--window <size>] [--queue <size>] [--drop-when-full] [--bar <secs> [--time-col <col>] [--emit-partial]]");
+    println!("                                       从标准输入流式计算");
+    println!("      --bar N 将 tick 按 N 秒合成 open/high/low/close/volume 后再计算（价格列 price）");
+    println!("      --time-col 时间戳列（Unix 秒，默认 timestamp），--emit-partial 结束时输出未完成的 bar");
+    println!("  dplang pipe <script.dp> [--window <size>]");
+    println!("                                       从标准输入读 CSV，结果 CSV 写到标准输出");
+    println!("  dplang check <script.dp> [--format text|json]");
+    println!("                                       静态检查脚本（未定义变量等）");
+    println!("  dplang fmt <script.dp> [--stdout] [--force]");
+    println!("                                       按规范格式改写脚本（含注释时需 --force）");
+    println!("  dplang screen <strategy.dp> <stocks.csv> [--output <file>] [--select <条件>]");
+    println!("                                       策略选股（出错的行跳过）");
+    println!("      --select 指定筛选列或条件，如 signal 或 \"score > 0.8\"（默认按 selected 列）");
+    println!("      --group-by code 按标的分组计算（历史只看本标的），--time date 按日期做截面");
+    println!("      --rank / --zscore <列> 截面排名或标准分，写入 <列>_rank / <列>_zscore");
+    println!("      --jobs N 分组时用 N 个线程并行计算");
+    println!("      --offset / --limit N 截取写出的结果行");
+    println!("      --passthrough name,sector 把输入列原样带到结果中（不必写进 OUTPUT）");
     println!("  dplang help                          显示帮助信息");
     println!("  dplang version                       显示版本信息\n");
     
@@ -64,6 +223,15 @@ fn print_usage() {
     println!("  # 使用CSV文件作为输入");
     println!("  dplang run script.dp data.csv");
     println!();
+    println!("  # 管道模式，与其他命令组合");
+    println!("  cat data.csv | dplang pipe script.dp > out.csv");
+    println!();
+    println!("  # 从标准输入读取 JSON-lines 实时计算");
+    println!("  tail -f ticks.jsonl | dplang monitor script.dp --input json");
+    println!();
+    println!("  # tick（timestamp,price,volume）按 60 秒合成 K 线后再计算");
+    println!("  tail -f ticks.csv | dplang monitor bars.dp --bar 60 --time-col timestamp");
+    println!();
     
     println!("更多信息: https://github.com/yourusername/dplang");
 }
@@ -74,33 +242,10 @@ fn print_version() {
 }
 
 /// 执行脚本
-fn run_script(script_path: &str, csv_path: Option<&String>) {
-    // 读取脚本文件
-    let source = match fs::read_to_string(script_path) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("错误: 无法读取脚本文件 '{}': {}", script_path, e);
-            return;
-        }
-    };
-    
-    // 解析脚本
-    let mut lexer = Lexer::new(&source);
-    let tokens = match lexer.tokenize() {
-        Ok(t) => t,
-        Err(e) => {
-            eprintln!("词法分析错误: {:?}", e);
-            return;
-        }
-    };
-    
-    let mut parser = Parser::new(tokens);
-    let script = match parser.parse() {
-        Ok(s) => s,
-        Err(e) => {
-            eprintln!("语法分析错误: {:?}", e);
-            return;
-        }
+fn run_script(script_path: &str, csv_path: Option<&String>, overrides: &ExecutionOverrides, window: &OutputWindow) {
+    let script = match load_script(script_path) {
+        Some(s) => s,
+        None => return,
     };
     
     println!("✅ 脚本解析成功\n");
@@ -164,9 +309,14 @@ fn run_script(script_path: &str, csv_path: Option<&String>) {
     
     // 执行脚本
     let mut executor = DataStreamExecutor::new(script, input_matrix);
+    if let Err(e) = overrides.apply(&mut executor) {
+        eprintln!("错误: {}", e);
+        return;
+    }
     match executor.execute_all() {
         Ok(output) => {
             println!("\n✅ 执行成功!\n");
+            let output = window.apply(output);
             
             if csv_path.is_some() {
                 // CSV输入时，输出CSV格式
@@ -176,7 +326,7 @@ fn run_script(script_path: &str, csv_path: Option<&String>) {
                 // 交互式输入时，输出JSON格式
                 println!("输出结果:");
                 for (i, row) in output.iter().enumerate() {
-                    println!("  行 {}: {:?}", i + 1, row);
+                    println!("  行 {}: {:?}", window.offset + i + 1, row);
                 }
             }
         }
@@ -186,6 +336,395 @@ fn run_script(script_path: &str, csv_path: Option<&String>) {
     }
 }
 
+/// 静态检查：输出语义分析的错误和警告（json 模式输出诊断数组）
+fn run_check(script_path: &str, json: bool) {
+    let script = match load_script(script_path) {
+        Some(s) => s,
+        None => return,
+    };
+    
+    let result = SemanticAnalyzer::new().analyze(&script);
+    if json {
+        println!("{}", result.diagnostics_json());
+        return;
+    }
+    
+    for error in &result.errors {
+        println!("错误: {}", error.message);
+    }
+    for warning in &result.warnings {
+        println!("警告: {}", warning.message);
+    }
+    println!("检查完成: {} 个错误，{} 个警告", result.errors.len(), result.warnings.len());
+}
+
+/// 格式化脚本：默认原地改写；脚本含注释时（格式化会丢弃注释）需加 --force
+fn run_fmt(script_path: &str, to_stdout: bool, force: bool) {
+    let source = match fs::read_to_string(script_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("错误: 无法读取脚本文件 '{}': {}", script_path, e);
+            return;
+        }
+    };
+    
+    let mut lexer = Lexer::new(&source);
+    let tokens = match lexer.tokenize() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("词法分析错误: {:?}", e);
+            return;
+        }
+    };
+    let script = match Parser::new(tokens).parse() {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("语法分析错误: {:?}", e);
+            return;
+        }
+    };
+    
+    let formatted = format_script(&script);
+    if to_stdout {
+        print!("{}", formatted);
+        return;
+    }
+    if formatted == source {
+        println!("{} 已是规范格式", script_path);
+        return;
+    }
+    if lexer.comment_count() > 0 && !force {
+        eprintln!("错误: {} 含有 {} 处注释，格式化会丢弃注释", script_path, lexer.comment_count());
+        eprintln!("可用 --stdout 预览结果，或加 --force 仍然改写");
+        return;
+    }
+    
+    match fs::write(script_path, formatted) {
+        Ok(()) => println!("已格式化 {}", script_path),
+        Err(e) => eprintln!("错误: 无法写入 '{}': {}", script_path, e),
+    }
+}
+
+/// screen 命令的分组与截面选项
+struct CrossSection<'a> {
+    /// 分组列（按标的分别执行）
+    group_by: Option<&'a str>,
+    /// 截面计算的时间列（仅分组时生效）
+    time_key: Option<&'a str>,
+    /// 截面排名的列
+    rank: Option<&'a str>,
+    /// 截面标准分的列
+    zscore: Option<&'a str>,
+    /// 分组并行计算的线程数
+    jobs: usize,
+    /// 原样复制到结果的输入列（如 name、sector）
+    passthrough: Vec<String>,
+}
+
+/// 策略选股：逐行执行策略，保留满足筛选条件的行
+/// （默认保留 selected 为 true 的行，无 selected 字段时全部保留）
+/// 单只股票出错不会终止整个筛选，出错行被跳过并汇总报告
+fn run_screen(
+    script_path: &str,
+    csv_path: &str,
+    output_file: &str,
+    predicate: &SelectPredicate,
+    cross: &CrossSection,
+    window: &OutputWindow,
+) {
+    let script = match load_script(script_path) {
+        Some(s) => s,
+        None => return,
+    };
+    
+    let input_matrix = match fs::read_to_string(csv_path)
+        .map_err(|e| e.to_string())
+        .and_then(|content| parse_csv(&content))
+    {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("错误: 无法加载股票数据 '{}': {}", csv_path, e);
+            return;
+        }
+    };
+    
+    println!("✅ 加载 {} 只股票\n", input_matrix.len());
+    
+    let result = match cross.group_by {
+        Some(group_by) => screen_grouped(script, input_matrix, group_by, cross),
+        None => screen_plain(script, input_matrix, cross),
+    };
+    let (output_matrix, row_errors) = match result {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("\n❌ 选股错误: {:?}", e);
+            return;
+        }
+    };
+    
+    for (row_idx, e) in &row_errors {
+        eprintln!("警告: 第 {} 行执行失败，已跳过: {}", row_idx + 1, e.message);
+    }
+    
+    let selected: Vec<_> = output_matrix.iter()
+        .enumerate()
+        .filter(|(i, _)| !row_errors.iter().any(|(row_idx, _)| row_idx == i))
+        .map(|(_, row)| row)
+        .filter(|row| predicate.matches(row))
+        .cloned()
+        .collect();
+    
+    println!("筛选出 {} 只股票（跳过出错行 {} 行）", selected.len(), row_errors.len());
+    let selected = window.apply(selected);
+    if *window != OutputWindow::default() {
+        println!("按 --offset/--limit 保留 {} 只", selected.len());
+    }
+    
+    if let Err(e) = fs::write(output_file, format_output_csv(&selected)) {
+        eprintln!("错误: 无法保存结果: {}", e);
+    } else {
+        println!("结果已保存到: {}", output_file);
+    }
+}
+
+/// 选股结果：输出行与出错的行
+type ScreenResult = Result<(Vec<HashMap<String, Value>>, Vec<(usize, RuntimeError)>), RuntimeError>;
+
+/// 不分组执行，截面计算覆盖全部行
+fn screen_plain(script: Script, input_matrix: Vec<HashMap<String, Value>>, cross: &CrossSection) -> ScreenResult {
+    let mut executor = DataStreamExecutor::new(script, input_matrix);
+    executor.set_error_policy(ErrorPolicy::SkipRow);
+    executor.set_passthrough(cross.passthrough.clone())?;
+    executor.execute_all()?;
+    if let Some(name) = cross.rank {
+        executor.rank_column(name)?;
+    }
+    if let Some(name) = cross.zscore {
+        executor.zscore_column(name)?;
+    }
+    Ok((executor.get_output().to_vec(), executor.row_errors().to_vec()))
+}
+
+/// 按标的分组执行，再按时间列做截面计算
+fn screen_grouped(script: Script, input_matrix: Vec<HashMap<String, Value>>, group_by: &str, cross: &CrossSection) -> ScreenResult {
+    let mut executor = GroupedExecutor::new(script, input_matrix, group_by);
+    executor.set_error_policy(ErrorPolicy::SkipRow);
+    executor.set_parallelism(cross.jobs);
+    executor.set_passthrough(cross.passthrough.clone())?;
+    if let Some(time_key) = cross.time_key {
+        executor.set_time_key(time_key);
+    }
+    executor.execute_all()?;
+    if let Some(name) = cross.rank {
+        executor.rank_column(name)?;
+    }
+    if let Some(name) = cross.zscore {
+        executor.zscore_column(name)?;
+    }
+    Ok((executor.get_output().to_vec(), executor.row_errors().to_vec()))
+}
+
+/// 读取并解析脚本文件（出错时打印错误并返回 None）
+fn load_script(script_path: &str) -> Option<Script> {
+    // 读取脚本文件
+    let source = match fs::read_to_string(script_path) {
+        Ok(content) => content,
+        Err(e) => {
+            eprintln!("错误: 无法读取脚本文件 '{}': {}", script_path, e);
+            return None;
+        }
+    };
+    
+    // 解析脚本
+    let mut lexer = Lexer::new(&source);
+    let tokens = match lexer.tokenize() {
+        Ok(t) => t,
+        Err(e) => {
+            eprintln!("词法分析错误: {:?}", e);
+            return None;
+        }
+    };
+    
+    let mut parser = Parser::new(tokens);
+    match parser.parse() {
+        Ok(s) => Some(s),
+        Err(e) => {
+            eprintln!("语法分析错误: {:?}", e);
+            None
+        }
+    }
+}
+
+/// 读取命令行选项的值，如 --input json
+fn parse_option<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
+/// 实时监控：从标准输入逐行读取 tick 并流式计算
+/// CSV 模式首行为表头；JSON 模式每行一个 JSON 对象
+fn run_monitor(
+    script_path: &str,
+    json_input: bool,
+    window_size: usize,
+    queue_config: OutputQueueConfig,
+    bar_builder: Option<BarBuilder>,
+) {
+    let script = match load_script(script_path) {
+        Some(s) => s,
+        None => return,
+    };
+    let mut columns: Vec<String> = match &script {
+        Script::DataScript { output, .. } => output.iter().map(|p| p.name.clone()).collect(),
+        Script::Package { .. } => Vec::new(),
+    };
+    
+    let mut executor = StreamingExecutor::new(script, window_size);
+    if let Some(builder) = bar_builder {
+        executor.set_bar_builder(builder);
+    }
+    
+    println!("🚀 实时引擎已启动（窗口大小: {} 行）", window_size);
+    if json_input {
+        println!("等待标准输入 (JSON-lines 格式, Ctrl+C 退出)...\n");
+    } else {
+        println!("等待标准输入 (CSV 格式, 首行为表头, Ctrl+C 退出)...\n");
+    }
+    
+    install_sigint_handler();
+    
+    let format = if json_input { StreamInputFormat::JsonLines } else { StreamInputFormat::Csv };
+    let lines = stdin_lines_until_stopped(&STOP_REQUESTED);
+    
+    // 输出经有界队列交给写入线程缓冲写入，退出（EOF 或 Ctrl-C）时统一刷新
+    let mut out = io::BufWriter::new(io::stdout());
+    let mut queue = OutputQueue::spawn(queue_config, move |line| {
+        if let Err(e) = writeln!(out, "{}", line).and_then(|_| out.flush()) {
+            eprintln!("警告: 写入输出失败: {}", e);
+        }
+    });
+    // 输出为 CSV：按 OUTPUT 声明的列顺序，首个结果前写表头（未声明时按列名排序）
+    let mut header_written = false;
+    let stats = run_tick_stream(lines, format, &mut executor, &STOP_REQUESTED, |row| {
+        if !header_written {
+            if columns.is_empty() {
+                columns = row.keys().cloned().collect();
+                columns.sort();
+            }
+            if let Err(e) = queue.push(columns.join(",")) {
+                eprintln!("警告: {}", e.message);
+            }
+            header_written = true;
+        }
+        if let Err(e) = queue.push(format_row_csv(row, &columns)) {
+            eprintln!("警告: {}", e.message);
+        }
+    });
+    
+    let dropped = match queue.finish() {
+        Ok((_, dropped)) => dropped,
+        Err(e) => {
+            eprintln!("警告: 刷新输出失败: {}", e.message);
+            0
+        }
+    };
+    
+    if stats.stopped {
+        println!("\n⏹  收到中断信号，已停止");
+    }
+    println!(
+        "\n总计处理: {} 行，跳过无效行: {} 行，执行失败: {} 行，丢弃输出: {} 行",
+        stats.processed, stats.skipped, stats.failed, dropped
+    );
+}
+
+/// 管道模式：标准输入的 CSV 逐行流式计算，输出 CSV 写到标准输出
+/// 标准输出只有数据，提示和错误都写到标准错误
+fn run_pipe(script_path: &str, window_size: usize) {
+    let script = match load_script(script_path) {
+        Some(s) => s,
+        None => std::process::exit(1),
+    };
+    let columns: Vec<String> = match &script {
+        Script::DataScript { output, .. } => output.iter().map(|p| p.name.clone()).collect(),
+        Script::Package { .. } => Vec::new(),
+    };
+    
+    let mut executor = StreamingExecutor::new(script, window_size);
+    let mut out = io::BufWriter::new(io::stdout().lock());
+    match run_csv_pipe(io::stdin().lock(), &mut out, &mut executor, &columns) {
+        Ok(stats) => {
+            if stats.skipped > 0 || stats.failed > 0 {
+                eprintln!("警告: 跳过无效行 {} 行，执行失败 {} 行", stats.skipped, stats.failed);
+            }
+        }
+        // 下游提前关闭管道（如 head）时静默退出
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Ctrl-C 停止标志（monitor 每个 tick 前检查）
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// 在后台线程逐行读取标准输入，主线程定时检查停止标志
+/// 输入空闲（读取阻塞）时 Ctrl-C 也能及时生效；通道有界，读取速度受计算速度约束
+fn stdin_lines_until_stopped(stop: &'static AtomicBool) -> impl Iterator<Item = String> {
+    let (tx, rx) = mpsc::sync_channel(1024);
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            match line {
+                Ok(l) => {
+                    if tx.send(l).is_err() {
+                        break;
+                    }
+                }
+                Err(e) => eprintln!("警告: 读取输入失败: {}", e),
+            }
+        }
+    });
+    
+    std::iter::from_fn(move || loop {
+        if stop.load(Ordering::SeqCst) {
+            return None;
+        }
+        match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(line) => return Some(line),
+            Err(RecvTimeoutError::Timeout) => continue,
+            Err(RecvTimeoutError::Disconnected) => return None,
+        }
+    })
+}
+
+/// 安装 SIGINT 处理器：只设置停止标志，由主循环退出并刷新输出
+/// 不设置 SA_RESTART，被信号打断的阻塞调用立即返回
+#[cfg(unix)]
+fn install_sigint_handler() {
+    extern "C" fn on_sigint(_signum: libc::c_int) {
+        STOP_REQUESTED.store(true, Ordering::SeqCst);
+    }
+    
+    let handler: extern "C" fn(libc::c_int) = on_sigint;
+    // SAFETY: sigaction 结构按 POSIX 要求清零后填写；处理器只写原子变量，是异步信号安全的
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = 0;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut()) != 0 {
+            eprintln!("警告: 无法安装 Ctrl-C 处理器: {}", io::Error::last_os_error());
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn install_sigint_handler() {}
+
 /// 简单的JSON解析器（仅支持基本类型）
 fn parse_simple_json(line: &str) -> Result<HashMap<String, Value>, ()> {
     let line = line.trim();
@@ -208,9 +747,9 @@ fn parse_simple_json(line: &str) -> Result<HashMap<String, Value>, ()> {
         let value = if value_str.starts_with('"') && value_str.ends_with('"') {
             // 字符串
             Value::String(value_str.trim_matches('"').to_string())
-        } else if let Ok(n) = value_str.parse::<f64>() {
+        } else if let Some(n) = Value::parse_number(value_str) {
             // 数字
-            Value::Number(n)
+            n
         } else if value_str == "true" {
             Value::Bool(true)
         } else if value_str == "false" {
@@ -6,13 +6,23 @@ use crate::runtime::RuntimeError;
 use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// 缓存的包解析结果（文件路径和修改时间不变时复用）
+struct CachedPackage {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    script: Script,
+}
 
 /// 包加载器
 pub struct PackageLoader {
     /// 包搜索路径
     search_paths: Vec<PathBuf>,
-    /// 已加载的包缓存（包名 -> Script）
-    cache: HashMap<String, Script>,
+    /// 已加载的包缓存（包名 -> 解析结果）
+    cache: HashMap<String, CachedPackage>,
+    /// 实际解析包文件的次数（缓存未命中时递增）
+    parse_count: usize,
 }
 
 impl PackageLoader {
@@ -21,6 +31,7 @@ impl PackageLoader {
         let mut loader = PackageLoader {
             search_paths: Vec::new(),
             cache: HashMap::new(),
+            parse_count: 0,
         };
         
         // 添加默认搜索路径
@@ -53,17 +64,22 @@ impl PackageLoader {
     }
     
     /// 加载包（从文件系统或缓存）
+    /// 缓存按文件路径和修改时间校验，包文件被修改后重新解析
     pub fn load_package(&mut self, name: &str) -> Result<Script, RuntimeError> {
-        // 先检查缓存
-        if let Some(script) = self.cache.get(name) {
-            return Ok(script.clone());
-        }
-        
         // 查找包文件
         let package_file = self.find_package_file(name)?;
+        let modified = fs::metadata(&package_file).and_then(|m| m.modified()).ok();
+        
+        // 先检查缓存
+        if let Some(cached) = self.cache.get(name) {
+            if cached.path == package_file && cached.modified.is_some() && cached.modified == modified {
+                return Ok(cached.script.clone());
+            }
+        }
         
         // 从文件加载
         let script = self.load_from_file(&package_file)?;
+        self.parse_count += 1;
         
         // 验证是否是包脚本
         if !matches!(script, Script::Package { .. }) {
@@ -74,7 +90,11 @@ impl PackageLoader {
         }
         
         // 缓存
-        self.cache.insert(name.to_string(), script.clone());
+        self.cache.insert(name.to_string(), CachedPackage {
+            path: package_file,
+            modified,
+            script: script.clone(),
+        });
         
         Ok(script)
     }
@@ -148,6 +168,11 @@ impl PackageLoader {
         self.cache.clear();
     }
     
+    /// 实际解析包文件的次数（用于观察缓存效果）
+    pub fn parse_count(&self) -> usize {
+        self.parse_count
+    }
+    
     /// 获取搜索路径列表（用于调试）
     pub fn search_paths(&self) -> &[PathBuf] {
         &self.search_paths
@@ -236,6 +261,30 @@ VALUE = 42
         assert!(matches!(script2, Script::Package { .. }));
     }
     
+    #[test]
+    fn test_package_cache_invalidated_by_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let package_path = temp_dir.path().join("reloaded.dp");
+        fs::write(&package_path, "package reloaded\n\nVALUE = 1\n").unwrap();
+        
+        let mut loader = PackageLoader::new();
+        loader.add_search_path(temp_dir.path());
+        
+        // 文件未修改：重复加载复用缓存的解析结果
+        loader.load_package("reloaded").unwrap();
+        loader.load_packages(&["reloaded".to_string()]).unwrap();
+        assert_eq!(loader.parse_count(), 1);
+        
+        // 修改时间变化后重新解析
+        fs::write(&package_path, "package reloaded\n\nVALUE = 2\nOTHER = 3\n").unwrap();
+        let file = fs::File::options().write(true).open(&package_path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10)).unwrap();
+        
+        let script = loader.load_package("reloaded").unwrap();
+        assert_eq!(loader.parse_count(), 2);
+        assert!(matches!(script, Script::Package { variables, .. } if variables.len() == 2));
+    }
+    
     #[test]
     fn test_load_nonexistent_package() {
         let mut loader = PackageLoader::new();
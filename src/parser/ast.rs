@@ -1,7 +1,7 @@
 // DPLang 语法分析器 - AST 定义
 
+use std::collections::BTreeSet;
 use std::fmt;
-use crate::lexer::FStringPart;
 
 /// when 表达式的分支
 #[derive(Debug, Clone, PartialEq)]
@@ -10,17 +10,27 @@ pub struct WhenBranch {
     pub result: Expr,
 }
 
+/// f-string 的组成部分（嵌入的表达式在解析阶段编译，求值时不再重新解析）
+#[derive(Debug, Clone, PartialEq)]
+pub enum FStringSegment {
+    Text(String),
+    Expr(Box<Expr>),
+}
+
 /// 表达式节点
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     /// 数字字面量
     Number(f64),
     
+    /// 整数字面量（不含小数点和指数）
+    Integer(i64),
+    
     /// 字符串字面量
     String(String),
     
     /// f-string 字符串插值
-    FString(Vec<FStringPart>),
+    FString(Vec<FStringSegment>),
     
     /// 布尔字面量
     Bool(bool),
@@ -55,7 +65,10 @@ pub enum Expr {
     },
     
     /// when 表达式
+    /// subject 为 None 时各分支条件按布尔求值；
+    /// 否则为 `when x: v1 -> r1, ...` 形式，分支条件与 subject 做相等比较
     When {
+        subject: Option<Box<Expr>>,
         branches: Vec<WhenBranch>,
         else_expr: Option<Box<Expr>>,
     },
@@ -195,11 +208,36 @@ pub enum Stmt {
 }
 
 /// 解构模式
+/// 展开绑定中间剩余的元素，超出数组长度的位置绑定 null
 #[derive(Debug, Clone, PartialEq)]
 pub enum DestructurePattern {
     Identifier(String),
     Ignore,  // _
     Spread(String),  // ...rest
+    Default(String, Expr),  // b = 0（对应元素缺失或为 null 时使用默认值）
+}
+
+/// 包成员是否私有（以下划线开头），私有成员只能在定义它的包内访问
+pub fn is_private_name(name: &str) -> bool {
+    name.starts_with('_')
+}
+
+/// 包成员能否被导入方访问：声明了 EXPORT 时只有列出的成员可访问，否则按下划线约定
+pub fn is_exported(exports: Option<&[String]>, name: &str) -> bool {
+    match exports {
+        Some(names) => names.iter().any(|n| n == name),
+        None => !is_private_name(name),
+    }
+}
+
+/// 已知的注解名，其他注解由语义分析给出警告
+pub const KNOWN_ANNOTATIONS: &[&str] = &["memo"];
+
+/// 注解（@name 或 @name(args)），写在包级函数或变量定义之前
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    pub name: String,
+    pub args: Vec<Expr>,
 }
 
 /// 函数定义
@@ -210,6 +248,20 @@ pub struct FunctionDef {
     pub return_type: Option<TypeAnnotation>,
     pub body: Vec<Stmt>,
     pub is_private: bool,
+    /// 定义前的注解（如 @memo）
+    pub annotations: Vec<Annotation>,
+}
+
+impl FunctionDef {
+    /// 是否带有指定注解
+    pub fn has_annotation(&self, name: &str) -> bool {
+        self.annotations.iter().any(|a| a.name == name)
+    }
+    
+    /// 是否缓存结果（@memo 注解）
+    pub fn memoize(&self) -> bool {
+        self.has_annotation("memo")
+    }
 }
 
 /// 函数参数
@@ -235,6 +287,147 @@ pub enum TypeAnnotation {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct PrecisionSetting {
     pub scale: u32,  // 小数位数
+    pub rounding: Option<RoundingMode>,  // 舍入方式（None 为默认的 half_even）
+}
+
+/// 舍入方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RoundingMode {
+    /// 四舍五入
+    HalfUp,
+    /// 四舍六入五成双（银行家舍入，默认）
+    #[default]
+    HalfEven,
+    /// 向零截断
+    Truncate,
+    /// 向上取整
+    Ceil,
+    /// 向下取整
+    Floor,
+}
+
+impl RoundingMode {
+    /// 从声明中的名称解析（half_up / half_even / truncate / ceil / floor）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "half_up" => Some(RoundingMode::HalfUp),
+            "half_even" => Some(RoundingMode::HalfEven),
+            "truncate" => Some(RoundingMode::Truncate),
+            "ceil" => Some(RoundingMode::Ceil),
+            "floor" => Some(RoundingMode::Floor),
+            _ => None,
+        }
+    }
+    
+    /// 脚本中的名称（from_name 的逆操作）
+    pub fn name(&self) -> &'static str {
+        match self {
+            RoundingMode::HalfUp => "half_up",
+            RoundingMode::HalfEven => "half_even",
+            RoundingMode::Truncate => "truncate",
+            RoundingMode::Ceil => "ceil",
+            RoundingMode::Floor => "floor",
+        }
+    }
+}
+
+/// 除法模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DivMode {
+    /// 除零报错（默认）
+    #[default]
+    Strict,
+    /// 除零返回 null
+    Null,
+}
+
+impl DivMode {
+    /// 从名称解析（strict / null）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "strict" => Some(DivMode::Strict),
+            "null" => Some(DivMode::Null),
+            _ => None,
+        }
+    }
+    
+    /// 脚本中的名称（from_name 的逆操作）
+    pub fn name(&self) -> &'static str {
+        match self {
+            DivMode::Strict => "strict",
+            DivMode::Null => "null",
+        }
+    }
+}
+
+/// 非有限数（NaN/Infinity）处理模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NanMode {
+    /// 运算结果为 NaN/Infinity 时返回 null（默认）
+    #[default]
+    Null,
+    /// 运算结果为 NaN/Infinity 时报错
+    Strict,
+}
+
+impl NanMode {
+    /// 脚本中的名称
+    pub fn name(&self) -> &'static str {
+        match self {
+            NanMode::Null => "null",
+            NanMode::Strict => "strict",
+        }
+    }
+}
+
+/// 数组越界访问模式（索引读取和切片边界）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexMode {
+    /// 越界索引返回 null，切片边界截断到数组范围（默认）
+    #[default]
+    Lenient,
+    /// 越界索引和越界的切片边界报错
+    Strict,
+}
+
+impl IndexMode {
+    /// 从名称解析（lenient / strict）
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "lenient" => Some(IndexMode::Lenient),
+            "strict" => Some(IndexMode::Strict),
+            _ => None,
+        }
+    }
+    
+    /// 脚本中的名称（from_name 的逆操作）
+    pub fn name(&self) -> &'static str {
+        match self {
+            IndexMode::Lenient => "lenient",
+            IndexMode::Strict => "strict",
+        }
+    }
+}
+
+/// 脚本级配置（-- CONFIG key=value, ... --）
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ConfigSettings {
+    pub precision: Option<u32>,     // precision=4
+    pub div_mode: Option<DivMode>,  // div=strict|null
+    pub nan_mode: Option<NanMode>,  // nan=null|strict
+    pub index_mode: Option<IndexMode>,  // index=lenient|strict
+    pub rounding: Option<RoundingMode>,  // rounding=half_up|half_even|truncate|ceil|floor
+    pub freq: Option<String>,       // freq=1d（K线周期）
+}
+
+impl ConfigSettings {
+    /// 生效的精度设置：PRECISION 声明优先于 CONFIG 中的 precision，
+    /// 声明未指定舍入方式时使用 CONFIG 中的 rounding
+    pub fn effective_precision(&self, declared: &Option<PrecisionSetting>) -> Option<PrecisionSetting> {
+        declared.clone()
+            .or_else(|| self.precision.map(|scale| PrecisionSetting { scale, rounding: None }))
+            .map(|p| PrecisionSetting { rounding: p.rounding.or(self.rounding), ..p })
+    }
 }
 
 impl fmt::Display for TypeAnnotation {
@@ -258,6 +451,8 @@ pub struct VariableDef {
     pub value: Expr,
     pub is_mut: bool,
     pub is_private: bool,
+    /// 定义前的注解
+    pub annotations: Vec<Annotation>,
 }
 
 /// 脚本类型
@@ -266,6 +461,8 @@ pub enum Script {
     /// 包脚本
     Package {
         name: String,
+        /// EXPORT 声明的公开成员（None 时按下划线约定）
+        exports: Option<Vec<String>>,
         variables: Vec<VariableDef>,
         functions: Vec<FunctionDef>,
     },
@@ -277,6 +474,172 @@ pub enum Script {
         output: Vec<Parameter>,
         error_block: Option<Vec<Stmt>>,
         precision: Option<PrecisionSetting>,
+        config: ConfigSettings,
         body: Vec<Stmt>,
     },
 }
+
+/// 脚本描述信息（不执行脚本，供编辑器、服务端等工具使用）
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ScriptInfo {
+    pub inputs: Vec<Parameter>,
+    pub outputs: Vec<Parameter>,
+    pub imports: Vec<String>,
+    /// 生效的精度（PRECISION 声明优先于 CONFIG 中的 precision）
+    pub precision: Option<u32>,
+    /// 脚本中调用的函数名（包函数为 "pkg.func"）
+    pub called_functions: BTreeSet<String>,
+    /// 访问历史值的变量（如 close[-1]、close[-5:]、pct_change("close")）
+    pub history_vars: BTreeSet<String>,
+}
+
+impl Script {
+    /// 描述脚本的声明和调用的函数
+    pub fn describe(&self) -> ScriptInfo {
+        let mut info = ScriptInfo::default();
+        
+        match self {
+            Script::DataScript { imports, input, output, error_block, precision, config, body } => {
+                info.inputs = input.clone();
+                info.outputs = output.clone();
+                info.imports = imports.clone();
+                info.precision = config.effective_precision(precision).map(|p| p.scale);
+                
+                for stmt in body.iter().chain(error_block.iter().flatten()) {
+                    collect_stmt_info(stmt, &mut info);
+                }
+            }
+            Script::Package { variables, functions, .. } => {
+                for var_def in variables {
+                    collect_expr_info(&var_def.value, &mut info);
+                }
+                for func_def in functions {
+                    for stmt in &func_def.body {
+                        collect_stmt_info(stmt, &mut info);
+                    }
+                }
+            }
+        }
+        
+        info
+    }
+}
+
+/// 按列名读取数据流历史的内置函数（第一个参数为列名）
+const HISTORY_BUILTINS: &[&str] = &["pct_change", "quantile", "roc", "mom"];
+
+/// 是否为负数字面量（时间序列下标，如 -1）
+fn is_negative_literal(expr: &Expr) -> bool {
+    match expr {
+        Expr::Unary { op: UnaryOp::Neg, operand } => {
+            matches!(operand.as_ref(), Expr::Number(_) | Expr::Integer(_))
+        }
+        Expr::Number(n) => *n < 0.0,
+        Expr::Integer(i) => *i < 0,
+        _ => false,
+    }
+}
+
+fn collect_stmt_info(stmt: &Stmt, info: &mut ScriptInfo) {
+    match stmt {
+        Stmt::Assignment { value, .. } => {
+            collect_expr_info(value, info);
+        }
+        Stmt::Destructure { pattern, value } => {
+            for p in pattern {
+                if let DestructurePattern::Default(_, default) = p {
+                    collect_expr_info(default, info);
+                }
+            }
+            collect_expr_info(value, info);
+        }
+        Stmt::If { condition, then_block, else_block } => {
+            collect_expr_info(condition, info);
+            for stmt in then_block.iter().chain(else_block.iter().flatten()) {
+                collect_stmt_info(stmt, info);
+            }
+        }
+        Stmt::Return(expr) | Stmt::Expression(expr) => collect_expr_info(expr, info),
+    }
+}
+
+fn collect_expr_info(expr: &Expr, info: &mut ScriptInfo) {
+    match expr {
+        Expr::Number(_) | Expr::Integer(_) | Expr::String(_) | Expr::Bool(_) | Expr::Null
+        | Expr::Identifier(_) | Expr::MemberAccess { .. } => {}
+        Expr::FString(segments) => {
+            for segment in segments {
+                if let FStringSegment::Expr(inner) = segment {
+                    collect_expr_info(inner, info);
+                }
+            }
+        }
+        Expr::Array(items) => {
+            for item in items {
+                collect_expr_info(item, info);
+            }
+        }
+        Expr::Binary { left, right, .. } => {
+            collect_expr_info(left, info);
+            collect_expr_info(right, info);
+        }
+        Expr::Unary { operand, .. } => collect_expr_info(operand, info),
+        Expr::Ternary { condition, then_expr, else_expr } => {
+            collect_expr_info(condition, info);
+            collect_expr_info(then_expr, info);
+            collect_expr_info(else_expr, info);
+        }
+        Expr::When { subject, branches, else_expr } => {
+            if let Some(subject) = subject {
+                collect_expr_info(subject, info);
+            }
+            for branch in branches {
+                collect_expr_info(&branch.condition, info);
+                collect_expr_info(&branch.result, info);
+            }
+            if let Some(else_expr) = else_expr {
+                collect_expr_info(else_expr, info);
+            }
+        }
+        Expr::Call { callee, args } => {
+            info.called_functions.insert(callee.clone());
+            // 按列名读取历史的内置函数
+            if HISTORY_BUILTINS.contains(&callee.as_str()) {
+                if let Some(Expr::String(name)) = args.first() {
+                    info.history_vars.insert(name.clone());
+                }
+            }
+            for arg in args {
+                collect_expr_info(arg, info);
+            }
+        }
+        Expr::Index { base, index } => {
+            if let Expr::Identifier(name) = base.as_ref() {
+                if is_negative_literal(index) {
+                    info.history_vars.insert(name.clone());
+                }
+            }
+            collect_expr_info(base, info);
+            collect_expr_info(index, info);
+        }
+        Expr::Slice { base, start, end } => {
+            if let Expr::Identifier(name) = base.as_ref() {
+                if start.iter().chain(end.iter()).any(|bound| is_negative_literal(bound)) {
+                    info.history_vars.insert(name.clone());
+                }
+            }
+            collect_expr_info(base, info);
+            for bound in start.iter().chain(end.iter()) {
+                collect_expr_info(bound, info);
+            }
+        }
+        Expr::Spread(inner) => collect_expr_info(inner, info),
+        Expr::Lambda { body, .. } => collect_expr_info(body, info),
+        Expr::Pipeline { value, stages } => {
+            collect_expr_info(value, info);
+            for stage in stages {
+                collect_expr_info(stage, info);
+            }
+        }
+    }
+}
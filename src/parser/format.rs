@@ -0,0 +1,337 @@
+// DPLang 格式化器 - 把 AST 重新输出为规范格式的源码
+//
+// 规范格式：4 空格缩进的块，运算符两侧各一个空格，只保留必要的括号，
+// when 表达式统一为单行花括号形式，elif 链还原为 elif。
+// AST 不含注释和空行，格式化后二者都会丢失。
+
+use super::ast::*;
+
+/// 缩进单位
+const INDENT: &str = "    ";
+
+/// 把脚本格式化为规范源码
+/// 对格式化结果再次解析、格式化，得到的源码不变
+pub fn format_script(script: &Script) -> String {
+    let mut out = String::new();
+
+    match script {
+        Script::DataScript { imports, input, output, error_block, precision, config, body } => {
+            if !imports.is_empty() {
+                out.push_str(&format!("-- IMPORT {} --\n", imports.join(", ")));
+            }
+            if !input.is_empty() {
+                out.push_str(&format!("-- INPUT {} --\n", format_declared_params(input)));
+            }
+            if !output.is_empty() {
+                out.push_str(&format!("-- OUTPUT {} --\n", format_declared_params(output)));
+            }
+            if let Some(precision) = precision {
+                match precision.rounding {
+                    Some(rounding) => out.push_str(&format!("-- PRECISION {} {} --\n", precision.scale, rounding.name())),
+                    None => out.push_str(&format!("-- PRECISION {} --\n", precision.scale)),
+                }
+            }
+            let config_items = format_config(config);
+            if !config_items.is_empty() {
+                out.push_str(&format!("-- CONFIG {} --\n", config_items.join(", ")));
+            }
+            if let Some(error_stmts) = error_block {
+                out.push_str("-- ERROR --\n");
+                format_block(error_stmts, 0, &mut out);
+                out.push_str("-- ERROR_END --\n");
+            }
+
+            if !out.is_empty() && !body.is_empty() {
+                out.push('\n');
+            }
+            format_block(body, 0, &mut out);
+        }
+
+        Script::Package { name, exports, variables, functions } => {
+            out.push_str(&format!("package {}\n", name));
+            if let Some(names) = exports {
+                out.push_str(&format!("-- EXPORT {} --\n", names.join(", ")));
+            }
+
+            if !variables.is_empty() {
+                out.push('\n');
+            }
+            for var_def in variables {
+                format_annotations(&var_def.annotations, &mut out);
+                let keyword = if var_def.is_mut { "mut " } else { "" };
+                out.push_str(&format!("{}{} = {}\n", keyword, var_def.name, format_expr(&var_def.value)));
+            }
+
+            for func_def in functions {
+                out.push('\n');
+                format_function(func_def, &mut out);
+            }
+        }
+    }
+
+    out
+}
+
+/// INPUT/OUTPUT 声明的参数列表 "code:string, close:number"
+fn format_declared_params(params: &[Parameter]) -> String {
+    params.iter()
+        .map(|param| match &param.type_annotation {
+            Some(ty) => format!("{}:{}", param.name, ty),
+            None => param.name.clone(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// CONFIG 声明的配置项（按固定顺序）
+fn format_config(config: &ConfigSettings) -> Vec<String> {
+    let mut items = Vec::new();
+    if let Some(precision) = config.precision {
+        items.push(format!("precision={}", precision));
+    }
+    if let Some(div_mode) = config.div_mode {
+        items.push(format!("div={}", div_mode.name()));
+    }
+    if let Some(nan_mode) = config.nan_mode {
+        items.push(format!("nan={}", nan_mode.name()));
+    }
+    if let Some(index_mode) = config.index_mode {
+        items.push(format!("index={}", index_mode.name()));
+    }
+    if let Some(rounding) = config.rounding {
+        items.push(format!("rounding={}", rounding.name()));
+    }
+    if let Some(freq) = &config.freq {
+        items.push(format!("freq={}", freq));
+    }
+    items
+}
+
+fn format_annotations(annotations: &[Annotation], out: &mut String) {
+    for annotation in annotations {
+        if annotation.args.is_empty() {
+            out.push_str(&format!("@{}\n", annotation.name));
+        } else {
+            out.push_str(&format!("@{}({})\n", annotation.name, format_expr_list(&annotation.args)));
+        }
+    }
+}
+
+fn format_function(func_def: &FunctionDef, out: &mut String) {
+    format_annotations(&func_def.annotations, out);
+
+    let params = func_def.params.iter()
+        .map(|param| {
+            let mut text = param.name.clone();
+            if let Some(ty) = &param.type_annotation {
+                text.push_str(&format!(": {}", ty));
+            }
+            if let Some(default) = &param.default_value {
+                text.push_str(&format!(" = {}", format_expr(default)));
+            }
+            text
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    out.push_str(&format!("{}({})", func_def.name, params));
+    if let Some(ty) = &func_def.return_type {
+        out.push_str(&format!(" -> {}", ty));
+    }
+    format_block_body(&func_def.body, 0, out);
+}
+
+/// 输出块：非空块为 `:` 加缩进的语句，空块为 `{}`
+fn format_block_body(stmts: &[Stmt], level: usize, out: &mut String) {
+    if stmts.is_empty() {
+        out.push_str(" {}\n");
+    } else {
+        out.push_str(":\n");
+        format_block(stmts, level + 1, out);
+    }
+}
+
+fn format_block(stmts: &[Stmt], level: usize, out: &mut String) {
+    for stmt in stmts {
+        format_stmt(stmt, level, out);
+    }
+}
+
+fn format_stmt(stmt: &Stmt, level: usize, out: &mut String) {
+    let indent = INDENT.repeat(level);
+
+    match stmt {
+        Stmt::Assignment { name, value, .. } => {
+            out.push_str(&format!("{}{} = {}\n", indent, name, format_expr(value)));
+        }
+        Stmt::Destructure { pattern, value } => {
+            let pattern = pattern.iter()
+                .map(|p| match p {
+                    DestructurePattern::Identifier(name) => name.clone(),
+                    DestructurePattern::Ignore => "_".to_string(),
+                    DestructurePattern::Spread(name) => format!("...{}", name),
+                    DestructurePattern::Default(name, default) => format!("{} = {}", name, format_expr(default)),
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            out.push_str(&format!("{}[{}] = {}\n", indent, pattern, format_expr(value)));
+        }
+        Stmt::If { condition, then_block, else_block } => {
+            out.push_str(&format!("{}if {}", indent, format_expr(condition)));
+            format_block_body(then_block, level, out);
+
+            // 只含一个 if 的 else 块还原为 elif
+            let mut else_block = else_block.as_deref();
+            while let Some([Stmt::If { condition, then_block, else_block: next }]) = else_block {
+                out.push_str(&format!("{}elif {}", indent, format_expr(condition)));
+                format_block_body(then_block, level, out);
+                else_block = next.as_deref();
+            }
+            if let Some(stmts) = else_block {
+                out.push_str(&format!("{}else", indent));
+                format_block_body(stmts, level, out);
+            }
+        }
+        Stmt::Return(expr) => {
+            out.push_str(&format!("{}return {}\n", indent, format_expr(expr)));
+        }
+        Stmt::Expression(expr) => {
+            out.push_str(&format!("{}{}\n", indent, format_expr(expr)));
+        }
+    }
+}
+
+/// 表达式的优先级（越大结合越紧），与 Parser 的递归下降层次一致
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Pipeline { .. } => 0,
+        Expr::Ternary { .. } | Expr::Lambda { .. } => 1,
+        Expr::Binary { op, .. } => binary_precedence(*op),
+        Expr::Unary { .. } => 8,
+        Expr::Call { .. } | Expr::Index { .. } | Expr::Slice { .. } | Expr::MemberAccess { .. } => 9,
+        _ => 10,
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Or => 2,
+        BinaryOp::And => 3,
+        BinaryOp::Gt | BinaryOp::Lt | BinaryOp::GtEq | BinaryOp::LtEq | BinaryOp::Eq | BinaryOp::NotEq => 4,
+        BinaryOp::Add | BinaryOp::Sub => 5,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => 6,
+        BinaryOp::Pow => 7,
+    }
+}
+
+/// 优先级低于 min 时加括号
+fn format_operand(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min {
+        format!("({})", format_expr(expr))
+    } else {
+        format_expr(expr)
+    }
+}
+
+fn format_expr_list(exprs: &[Expr]) -> String {
+    exprs.iter().map(format_expr).collect::<Vec<_>>().join(", ")
+}
+
+/// 把表达式格式化为源码
+pub fn format_expr(expr: &Expr) -> String {
+    match expr {
+        // {:?} 保留小数点（2.0 而不是 2），重新解析时仍为 Number
+        Expr::Number(n) => format!("{:?}", n),
+        Expr::Integer(i) => i.to_string(),
+        Expr::String(s) => format!("\"{}\"", escape_string(s)),
+        Expr::FString(segments) => {
+            let mut text = String::from("f\"");
+            for segment in segments {
+                match segment {
+                    FStringSegment::Text(s) => {
+                        text.push_str(&escape_string(s).replace('{', "{{").replace('}', "}}"));
+                    }
+                    FStringSegment::Expr(inner) => text.push_str(&format!("{{{}}}", format_expr(inner))),
+                }
+            }
+            text.push('"');
+            text
+        }
+        Expr::Bool(b) => b.to_string(),
+        Expr::Null => "null".to_string(),
+        Expr::Identifier(name) => name.clone(),
+        Expr::Array(items) => format!("[{}]", format_expr_list(items)),
+        Expr::Binary { left, op, right } => {
+            let prec = binary_precedence(*op);
+            let (left_min, right_min) = match op {
+                // 幂运算右结合
+                BinaryOp::Pow => (prec + 1, prec),
+                // 比较运算的两侧都不能是比较（链式比较在解析时已展开为 and）
+                _ if prec == 4 => (prec + 1, prec + 1),
+                _ => (prec, prec + 1),
+            };
+            format!("{} {} {}", format_operand(left, left_min), op, format_operand(right, right_min))
+        }
+        Expr::Unary { op, operand } => {
+            let operand_text = format_operand(operand, 8);
+            match op {
+                UnaryOp::Not => format!("not {}", operand_text),
+                // 避免输出 --（会被当作声明）
+                UnaryOp::Neg if operand_text.starts_with('-') => format!("-({})", operand_text),
+                UnaryOp::Neg => format!("-{}", operand_text),
+            }
+        }
+        Expr::Ternary { condition, then_expr, else_expr } => format!(
+            "{} ? {} : {}",
+            format_operand(condition, 2),
+            format_operand(then_expr, 2),
+            format_operand(else_expr, 1),
+        ),
+        Expr::When { subject, branches, else_expr } => {
+            let mut arms: Vec<String> = branches.iter()
+                .map(|branch| format!("{} -> {}", format_operand(&branch.condition, 2), format_operand(&branch.result, 1)))
+                .collect();
+            if let Some(else_expr) = else_expr {
+                arms.push(format!("else -> {}", format_operand(else_expr, 1)));
+            }
+            match subject {
+                Some(subject) => format!("when {} {{ {} }}", format_operand(subject, 2), arms.join(", ")),
+                None => format!("when {{ {} }}", arms.join(", ")),
+            }
+        }
+        Expr::Call { callee, args } => format!("{}({})", callee, format_expr_list(args)),
+        Expr::MemberAccess { object, member } => format!("{}.{}", object, member),
+        Expr::Index { base, index } => format!("{}[{}]", format_operand(base, 9), format_expr(index)),
+        Expr::Slice { base, start, end } => format!(
+            "{}[{}:{}]",
+            format_operand(base, 9),
+            start.as_deref().map(format_expr).unwrap_or_default(),
+            end.as_deref().map(format_expr).unwrap_or_default(),
+        ),
+        Expr::Spread(inner) => format!("...{}", format_operand(inner, 10)),
+        Expr::Lambda { params, body } => match params.as_slice() {
+            [param] => format!("{} -> {}", param, format_operand(body, 1)),
+            _ => format!("({}) -> {}", params.join(", "), format_operand(body, 1)),
+        },
+        Expr::Pipeline { value, stages } => {
+            let mut parts = vec![format_operand(value, 1)];
+            parts.extend(stages.iter().map(|stage| format_operand(stage, 1)));
+            parts.join(" |> ")
+        }
+    }
+}
+
+/// 字符串字面量转义（与词法分析器支持的转义一致）
+fn escape_string(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
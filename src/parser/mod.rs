@@ -1,8 +1,9 @@
 // DPLang 语法分析器
 
 pub mod ast;
+pub mod format;
 
-use crate::lexer::{Token, TokenType};
+use crate::lexer::{FStringPart, Lexer, Token, TokenType};
 pub use ast::*;
 use std::fmt;
 
@@ -52,6 +53,7 @@ impl Parser {
         let name = self.expect_identifier("期望包名")?;
         self.consume_newlines()?;
         
+        let mut exports: Option<Vec<String>> = None;
         let mut variables = Vec::new();
         let mut functions = Vec::new();
         
@@ -62,19 +64,54 @@ impl Parser {
                 break;
             }
             
+            // -- EXPORT name1, name2 --
+            if let TokenType::Export(content) = &self.peek().token_type.clone() {
+                if exports.is_some() {
+                    return Err(self.error("重复的 EXPORT 声明"));
+                }
+                self.advance();
+                exports = Some(content
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect());
+                continue;
+            }
+            
+            // 定义前的注解（如 @memo、@cache(100)）
+            let annotations = self.parse_annotations()?;
+            
             // 检查是否是函数定义 (有参数列表)
             if self.is_function_definition() {
-                functions.push(self.parse_function_def()?);
+                let mut func = self.parse_function_def()?;
+                func.annotations = annotations;
+                functions.push(func);
+            } else if annotations.iter().any(|a| a.name == "memo") {
+                return Err(self.error("@memo 只能用于函数定义"));
             } else {
                 // 包级变量
-                variables.push(self.parse_variable_def()?);
+                let mut var = self.parse_variable_def()?;
+                var.annotations = annotations;
+                variables.push(var);
             }
             
             self.skip_newlines();
         }
         
+        // EXPORT 只能列出包中定义的成员
+        if let Some(names) = &exports {
+            let defined = |name: &String| {
+                variables.iter().any(|v: &VariableDef| &v.name == name)
+                    || functions.iter().any(|f: &FunctionDef| &f.name == name)
+            };
+            if let Some(missing) = names.iter().find(|name| !defined(name)) {
+                return Err(self.error(format!("EXPORT 的成员未在包中定义: {}", missing)));
+            }
+        }
+        
         Ok(Script::Package {
             name,
+            exports,
             variables,
             functions,
         })
@@ -86,6 +123,7 @@ impl Parser {
         let mut output = Vec::new();
         let mut error_block = None;
         let mut precision = None;
+        let mut config = ConfigSettings::default();
         let mut body = Vec::new();
         
         // 解析声明部分
@@ -110,15 +148,29 @@ impl Parser {
                 self.consume_newlines()?;
                 error_block = Some(self.parse_block_until_error_end()?);
             } else if let TokenType::Precision(content) = &self.peek().token_type.clone() {
+                let line = self.peek().line;
                 self.advance();
-                // 觢析精度位数，例如 "-- PRECISION 6 --" -> content="PRECISION 6"
-                // 提取数字部分
-                let scale = content
-                    .split_whitespace()
-                    .last()
+                // 解析精度位数和可选的舍入方式
+                // 例如 "-- PRECISION 6 --" -> content="PRECISION 6"
+                //      "-- PRECISION 2 half_up --" -> content="PRECISION 2 half_up"
+                let mut words = content.split_whitespace().skip(1);
+                let scale = words
+                    .next()
                     .and_then(|s| s.parse::<u32>().ok())
                     .unwrap_or(6);
-                precision = Some(PrecisionSetting { scale });
+                let rounding = match words.next() {
+                    Some(name) => Some(RoundingMode::from_name(name).ok_or_else(|| ParseError {
+                        message: format!("未知的舍入方式: {}", name),
+                        line,
+                        column: 0,
+                    })?),
+                    None => None,
+                };
+                precision = Some(PrecisionSetting { scale, rounding });
+            } else if let TokenType::Config(content) = &self.peek().token_type.clone() {
+                let line = self.peek().line;
+                self.advance();
+                config = self.parse_config_from_string(content, line)?;
             } else {
                 break;
             }
@@ -139,10 +191,83 @@ impl Parser {
             output,
             error_block,
             precision,
+            config,
             body,
         })
     }
     
+    /// 编译 f-string：嵌入的表达式源码在解析阶段解析为 Expr
+    fn compile_fstring(&self, parts: &[FStringPart]) -> Result<Expr, ParseError> {
+        let mut segments = Vec::with_capacity(parts.len());
+        
+        for part in parts {
+            match part {
+                FStringPart::Text(text) => segments.push(FStringSegment::Text(text.clone())),
+                FStringPart::Expr(expr_str) => {
+                    let tokens = Lexer::new(expr_str).tokenize()
+                        .map_err(|e| self.error(format!("f-string 表达式解析错误: {}", e)))?;
+                    let expr = Parser::new(tokens).parse_expression()
+                        .map_err(|e| self.error(format!("f-string 表达式解析错误: {}", e.message)))?;
+                    segments.push(FStringSegment::Expr(Box::new(expr)));
+                }
+            }
+        }
+        
+        Ok(Expr::FString(segments))
+    }
+    
+    /// 从字符串解析配置项 "div=null, precision=4"
+    fn parse_config_from_string(&self, content: &str, line: usize) -> Result<ConfigSettings, ParseError> {
+        let mut config = ConfigSettings::default();
+        let config_error = |message: String| ParseError { message, line, column: 0 };
+        
+        for item in content.split(',') {
+            let item = item.trim();
+            if item.is_empty() {
+                continue;
+            }
+            
+            let (key, value) = item
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| config_error(format!("配置项格式错误: {}", item)))?;
+            
+            match key {
+                "precision" => {
+                    let scale = value
+                        .parse::<u32>()
+                        .map_err(|_| config_error(format!("无效的 precision 值: {}", value)))?;
+                    config.precision = Some(scale);
+                }
+                "div" => {
+                    config.div_mode = Some(DivMode::from_name(value)
+                        .ok_or_else(|| config_error(format!("无效的 div 值: {}", value)))?);
+                }
+                "nan" => {
+                    config.nan_mode = Some(match value {
+                        "null" => NanMode::Null,
+                        "strict" => NanMode::Strict,
+                        _ => return Err(config_error(format!("无效的 nan 值: {}", value))),
+                    });
+                }
+                "index" => {
+                    config.index_mode = Some(IndexMode::from_name(value)
+                        .ok_or_else(|| config_error(format!("无效的 index 值: {}", value)))?);
+                }
+                "rounding" => {
+                    config.rounding = Some(RoundingMode::from_name(value)
+                        .ok_or_else(|| config_error(format!("无效的 rounding 值: {}", value)))?);
+                }
+                "freq" => {
+                    config.freq = Some(value.to_string());
+                }
+                _ => return Err(config_error(format!("未知的配置项: {}", key))),
+            }
+        }
+        
+        Ok(config)
+    }
+    
     /// 从字符串解析参数列表 "code:string, close:number"
     fn parse_param_list_from_string(&self, content: &str) -> Result<Vec<Parameter>, ParseError> {
         let mut params = Vec::new();
@@ -166,6 +291,15 @@ impl Parser {
             let name = parts[0].trim().to_string();
             let type_str = parts[1].trim();
             
+            // 同名参数会在行数据中互相覆盖
+            if params.iter().any(|p: &Parameter| p.name == name) {
+                return Err(ParseError {
+                    message: format!("参数名重复: {}", name),
+                    line: 0,
+                    column: 0,
+                });
+            }
+            
             let type_annotation = match type_str {
                 "number" => Some(TypeAnnotation::Number),
                 "decimal" => Some(TypeAnnotation::Decimal),
@@ -225,6 +359,28 @@ impl Parser {
         Ok(stmts)
     }
     
+    /// 解析定义前的注解：@name 或 @name(args)，每个注解可单独占一行
+    /// 注解名不在此处校验，未知注解由语义分析给出警告
+    fn parse_annotations(&mut self) -> Result<Vec<Annotation>, ParseError> {
+        let mut annotations = Vec::new();
+        while self.match_token(&[TokenType::At]) {
+            let name = self.expect_identifier("期望注解名")?;
+            let mut args = Vec::new();
+            if self.match_token(&[TokenType::LeftParen]) {
+                while !self.check(&TokenType::RightParen) {
+                    args.push(self.parse_expression()?);
+                    if !self.match_token(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RightParen, "期望 )")?;
+            }
+            annotations.push(Annotation { name, args });
+            self.skip_newlines();
+        }
+        Ok(annotations)
+    }
+    
     fn is_function_definition(&self) -> bool {
         // 查找 identifier ( 模式
         let mut i = self.current;
@@ -249,7 +405,7 @@ impl Parser {
     fn parse_variable_def(&mut self) -> Result<VariableDef, ParseError> {
         let is_mut = self.match_token(&[TokenType::Mut]);
         let name = self.expect_identifier("期望变量名")?;
-        let is_private = name.starts_with('_');
+        let is_private = is_private_name(&name);
         
         self.consume(TokenType::Assign, "期望 =")?;
         let value = self.parse_expression()?;
@@ -260,12 +416,13 @@ impl Parser {
             value,
             is_mut,
             is_private,
+            annotations: Vec::new(),
         })
     }
     
     fn parse_function_def(&mut self) -> Result<FunctionDef, ParseError> {
         let name = self.expect_identifier("期望函数名")?;
-        let is_private = name.starts_with('_');
+        let is_private = is_private_name(&name);
         
         self.consume(TokenType::LeftParen, "期望 (")?;
         
@@ -283,7 +440,7 @@ impl Parser {
             // 解析默认值
             let default_value = if self.match_token(&[TokenType::Assign]) {
                 has_default = true;
-                Some(self.parse_expression()?)
+                Some(self.parse_constant_expression()?)
             } else {
                 if has_default {
                     // 默认参数后不能有非默认参数
@@ -311,24 +468,8 @@ impl Parser {
             None
         };
         
-        self.consume(TokenType::Colon, "期望 :")?;
-        self.consume_newlines()?;
-        
-        // 解析函数体 (缩进块)
-        self.consume(TokenType::Indent, "期望缩进的函数体")?;
-        
-        let mut body = Vec::new();
-        while !self.check(&TokenType::Dedent) && !self.is_at_end() {
-            self.skip_newlines();
-            if self.check(&TokenType::Dedent) {
-                break;
-            }
-            body.push(self.parse_statement()?);
-        }
-        
-        if !self.is_at_end() {
-            self.consume(TokenType::Dedent, "期望函数体结束")?;
-        }
+        // 解析函数体（缩进块或花括号块）
+        let body = self.parse_block("函数体")?;
         
         Ok(FunctionDef {
             name,
@@ -336,6 +477,7 @@ impl Parser {
             return_type,
             body,
             is_private,
+            annotations: Vec::new(),
         })
     }
     
@@ -393,72 +535,24 @@ impl Parser {
     
     fn parse_if_statement(&mut self) -> Result<Stmt, ParseError> {
         let condition = self.parse_expression()?;
-        self.consume(TokenType::Colon, "期望 :")?;
-        self.consume_newlines()?;
         
         // then 块
-        self.consume(TokenType::Indent, "期望缩进的 if 块")?;
-        let mut then_block = Vec::new();
-        while !self.check(&TokenType::Dedent) && !self.is_at_end() {
-            self.skip_newlines();
-            if self.check(&TokenType::Dedent) {
-                break;
-            }
-            then_block.push(self.parse_statement()?);
-        }
-        
-        if !self.is_at_end() {
-            self.consume(TokenType::Dedent, "期望 if 块结束")?;
-        }
-        
+        let then_block = self.parse_block("if 块")?;
         self.skip_newlines();  // 跳过if块后的换行
         
         // elif 块 (可以有多个)
         let mut elif_branches = Vec::new();
         while self.match_token(&[TokenType::Elif]) {
             let elif_condition = self.parse_expression()?;
-            self.consume(TokenType::Colon, "期望 :")?;
-            self.consume_newlines()?;
-            self.consume(TokenType::Indent, "期望缩进的 elif 块")?;
-            
-            let mut elif_block = Vec::new();
-            while !self.check(&TokenType::Dedent) && !self.is_at_end() {
-                self.skip_newlines();
-                if self.check(&TokenType::Dedent) {
-                    break;
-                }
-                elif_block.push(self.parse_statement()?);
-            }
-            
-            if !self.is_at_end() {
-                self.consume(TokenType::Dedent, "期望 elif 块结束")?;
-            }
-            
+            let elif_block = self.parse_block("elif 块")?;
             self.skip_newlines();
             elif_branches.push((elif_condition, elif_block));
         }
         
         // else 块 (可选)
         let else_block = if self.match_token(&[TokenType::Else]) {
-            self.consume(TokenType::Colon, "期望 :")?;
-            self.consume_newlines()?;
-            self.consume(TokenType::Indent, "期望缩进的 else 块")?;
-            
-            let mut stmts = Vec::new();
-            while !self.check(&TokenType::Dedent) && !self.is_at_end() {
-                self.skip_newlines();
-                if self.check(&TokenType::Dedent) {
-                    break;
-                }
-                stmts.push(self.parse_statement()?);
-            }
-            
-            if !self.is_at_end() {
-                self.consume(TokenType::Dedent, "期望 else 块结束")?;
-            }
-            
+            let stmts = self.parse_block("else 块")?;
             self.skip_newlines();  // 跳过else块后的换行
-            
             Some(stmts)
         } else {
             None
@@ -489,11 +583,19 @@ impl Parser {
             if self.match_token(&[TokenType::Underscore]) {
                 pattern.push(DestructurePattern::Ignore);
             } else if self.match_token(&[TokenType::Spread]) {
+                if pattern.iter().any(|p| matches!(p, DestructurePattern::Spread(_))) {
+                    return Err(self.error("解构模式中只能有一个展开"));
+                }
                 let name = self.expect_identifier("期望变量名")?;
                 pattern.push(DestructurePattern::Spread(name));
             } else {
                 let name = self.expect_identifier("期望变量名")?;
-                pattern.push(DestructurePattern::Identifier(name));
+                if self.match_token(&[TokenType::Assign]) {
+                    let default = self.parse_constant_expression()?;
+                    pattern.push(DestructurePattern::Default(name, default));
+                } else {
+                    pattern.push(DestructurePattern::Identifier(name));
+                }
             }
             
             if !self.match_token(&[TokenType::Comma]) {
@@ -842,13 +944,17 @@ impl Parser {
                 self.advance();
                 Ok(Expr::Number(*n))
             }
+            TokenType::Integer(n) => {
+                self.advance();
+                Ok(Expr::Integer(*n))
+            }
             TokenType::String(s) => {
                 self.advance();
                 Ok(Expr::String(s.clone()))
             }
             TokenType::FString(parts) => {
                 self.advance();
-                Ok(Expr::FString(parts.clone()))
+                self.compile_fstring(parts)
             }
             TokenType::True => {
                 self.advance();
@@ -877,14 +983,12 @@ impl Parser {
                 Ok(Expr::Identifier(name))
             }
             TokenType::LeftParen => {
-                self.advance();
-                
                 // Lambda 表达式: (params) ->
                 if self.is_lambda_params() {
-                    self.current -= 1; // 回退
                     return self.parse_lambda();
                 }
                 
+                self.advance();
                 let expr = self.parse_expression()?;
                 self.consume(TokenType::RightParen, "期望 )")?;
                 Ok(expr)
@@ -894,7 +998,7 @@ impl Parser {
                 let mut elements = Vec::new();
                 
                 while !self.check(&TokenType::RightBracket) {
-                    elements.push(self.parse_expression()?);
+                    elements.push(self.parse_constant_expression()?);
                     if !self.match_token(&[TokenType::Comma]) {
                         break;
                     }
@@ -912,6 +1016,18 @@ impl Parser {
         }
     }
     
+    /// 常量位置（数组元素、默认值）的表达式：-5 折叠为负数字面量，-x 仍为一元运算
+    fn parse_constant_expression(&mut self) -> Result<Expr, ParseError> {
+        Ok(match self.parse_expression()? {
+            Expr::Unary { op: UnaryOp::Neg, operand } => match *operand {
+                Expr::Number(n) => Expr::Number(-n),
+                Expr::Integer(n) => Expr::Integer(-n),
+                operand => Expr::Unary { op: UnaryOp::Neg, operand: Box::new(operand) },
+            },
+            expr => expr,
+        })
+    }
+    
     // Lambda 表达式
     fn parse_lambda(&mut self) -> Result<Expr, ParseError> {
         let mut params = Vec::new();
@@ -941,16 +1057,62 @@ impl Parser {
         })
     }
     
+    /// 解析语句块，支持两种写法，每个块只能使用其中一种：
+    /// - 缩进块：`:` 换行后缩进
+    /// - 花括号块：`{ ... }`，块内忽略缩进（嵌套块也需使用花括号），适合嵌入会规整空白的配置文件
+    fn parse_block(&mut self, what: &str) -> Result<Vec<Stmt>, ParseError> {
+        let mut stmts = Vec::new();
+        
+        if self.match_token(&[TokenType::LeftBrace]) {
+            loop {
+                self.skip_newlines();
+                if self.check(&TokenType::RightBrace) || self.is_at_end() {
+                    break;
+                }
+                stmts.push(self.parse_statement()?);
+            }
+            self.consume(TokenType::RightBrace, &format!("期望 }} 结束{}", what))?;
+            return Ok(stmts);
+        }
+        
+        self.consume(TokenType::Colon, "期望 : 或 {")?;
+        self.consume_newlines()?;
+        self.consume(TokenType::Indent, &format!("期望缩进的{}", what))?;
+        
+        while !self.check(&TokenType::Dedent) && !self.is_at_end() {
+            self.skip_newlines();
+            if self.check(&TokenType::Dedent) {
+                break;
+            }
+            stmts.push(self.parse_statement()?);
+        }
+        
+        if !self.is_at_end() {
+            self.consume(TokenType::Dedent, &format!("期望{}结束", what))?;
+        }
+        
+        Ok(stmts)
+    }
+    
     // when 表达式：when condition1 -> result1, condition2 -> result2, else -> defaultResult
+    // 带主体形式：when subject: value1 -> result1, value2 -> result2, else -> defaultResult
     fn parse_when_expression(&mut self) -> Result<Expr, ParseError> {
         self.consume(TokenType::When, "期望 when")?;
         
+        let mut subject = None;
         let mut branches = Vec::new();
         let mut else_expr = None;
         
+        // 花括号形式：when { ... } 或 when x { ... }，分支间可用逗号或换行分隔
+        let mut braced = self.match_token(&[TokenType::LeftBrace]);
+        
         loop {
             self.skip_newlines();
             
+            if braced && self.check(&TokenType::RightBrace) {
+                break;
+            }
+            
             // 检查是否是 else 分支
             if self.check(&TokenType::Else) {
                 self.advance();
@@ -961,6 +1123,20 @@ impl Parser {
             
             // 解析条件
             let condition = self.parse_or()?;
+            
+            // 第一个表达式后跟 : 或 { 表示带主体的 when
+            if subject.is_none() && branches.is_empty() {
+                if self.match_token(&[TokenType::Colon]) {
+                    subject = Some(Box::new(condition));
+                    continue;
+                }
+                if !braced && self.match_token(&[TokenType::LeftBrace]) {
+                    subject = Some(Box::new(condition));
+                    braced = true;
+                    continue;
+                }
+            }
+            
             self.consume(TokenType::Arrow, "期望 when 分支的 ->")?;
             let result = self.parse_ternary()?;
             
@@ -969,11 +1145,18 @@ impl Parser {
             // 检查是否还有更多分支
             if !self.match_token(&[TokenType::Comma]) {
                 self.skip_newlines();
-                break;
+                if !braced {
+                    break;
+                }
             }
         }
         
-        Ok(Expr::When { branches, else_expr })
+        if braced {
+            self.skip_newlines();
+            self.consume(TokenType::RightBrace, "期望 } 结束 when")?;
+        }
+        
+        Ok(Expr::When { subject, branches, else_expr })
     }
     
     fn is_lambda_params(&self) -> bool {
@@ -1141,6 +1324,22 @@ return [code, ma5]
         }
     }
     
+    #[test]
+    fn test_parse_multi_param_lambda() {
+        let source = "reduce([1,2,3], (acc, x) -> acc + x, 0)";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let expr = parser.parse_expression().unwrap();
+        if let Expr::Call { args, .. } = expr {
+            assert_eq!(args.len(), 3);
+            assert!(matches!(&args[1], Expr::Lambda { params, .. } if params.len() == 2));
+        } else {
+            panic!("Expected Call");
+        }
+    }
+    
     #[test]
     fn test_parse_chained_comparison() {
         let source = "0 < x < 10";
@@ -1158,4 +1357,253 @@ return [code, ma5]
             panic!("Expected Binary expression with And");
         }
     }
+    
+    #[test]
+    fn test_parse_config_declaration() {
+        let source = r#"
+-- CONFIG div=null, precision=4, nan=strict --
+-- INPUT x:number --
+-- OUTPUT result:number --
+
+return [x]
+"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let script = parser.parse().unwrap();
+        if let Script::DataScript { config, input, .. } = script {
+            assert_eq!(config.precision, Some(4));
+            assert_eq!(config.div_mode, Some(DivMode::Null));
+            assert_eq!(config.nan_mode, Some(NanMode::Strict));
+            assert_eq!(input.len(), 1);
+        } else {
+            panic!("Expected DataScript");
+        }
+    }
+    
+    #[test]
+    fn test_parse_config_unknown_key() {
+        let source = "-- CONFIG speed=fast --\nreturn [1]\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let err = parser.parse().unwrap_err();
+        assert!(err.message.contains("speed"));
+    }
+    
+    #[test]
+    fn test_script_describe() {
+        let source = r#"
+-- IMPORT math --
+-- INPUT close:number --
+-- OUTPUT ma5:number, signal:bool --
+-- PRECISION 2 --
+
+ma5 = math.SMA(close[-5:], 5)
+signal = close > max([ma5, 0])
+return [ma5, signal]
+"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let info = script.describe();
+        assert_eq!(info.imports, vec!["math".to_string()]);
+        assert_eq!(info.inputs.len(), 1);
+        assert_eq!(info.inputs[0].name, "close");
+        assert_eq!(info.inputs[0].type_annotation, Some(TypeAnnotation::Number));
+        let output_names: Vec<&str> = info.outputs.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(output_names, vec!["ma5", "signal"]);
+        assert_eq!(info.precision, Some(2));
+        assert_eq!(
+            info.called_functions.iter().map(|s| s.as_str()).collect::<Vec<_>>(),
+            vec!["math.SMA", "max"]
+        );
+    }
+    
+    #[test]
+    fn test_parse_brace_if_else() {
+        // 花括号块内缩进被忽略，可与缩进块混用于同一文件
+        let source = r#"
+-- INPUT x:number --
+-- OUTPUT y:number --
+
+if x > 0 {
+y = 1
+} elif x < 0 { y = -1 } else {
+        y = 0
+}
+if x > 10:
+    y = 10
+return y
+"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let script = parser.parse().unwrap();
+        if let Script::DataScript { body, .. } = script {
+            assert_eq!(body.len(), 3);
+            if let Stmt::If { then_block, else_block: Some(else_block), .. } = &body[0] {
+                assert_eq!(then_block.len(), 1);
+                // elif 转换为嵌套的 if-else
+                assert!(matches!(&else_block[0], Stmt::If { else_block: Some(inner), .. } if inner.len() == 1));
+            } else {
+                panic!("Expected If with else");
+            }
+            assert!(matches!(&body[1], Stmt::If { then_block, else_block: None, .. } if then_block.len() == 1));
+        } else {
+            panic!("Expected DataScript");
+        }
+    }
+    
+    #[test]
+    fn test_parse_brace_function_body() {
+        let source = "package util\nclip(x, lo) {\nif x < lo { return lo }\nreturn when { x > 100 -> 100\nelse -> x }\n}\nscale = 2\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let script = parser.parse().unwrap();
+        if let Script::Package { functions, variables, .. } = script {
+            assert_eq!(functions.len(), 1);
+            assert_eq!(functions[0].name, "clip");
+            assert_eq!(functions[0].body.len(), 2);
+            assert!(matches!(&functions[0].body[1], Stmt::Return(Expr::When { branches, else_expr: Some(_), .. }) if branches.len() == 1));
+            assert_eq!(variables.len(), 1);
+        } else {
+            panic!("Expected Package");
+        }
+    }
+    
+    #[test]
+    fn test_parse_annotations() {
+        let source = "package util\n@memo\n@cache(100)\nfib(n):\n    return n\n@deprecated\nRATE = 0.05\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let script = parser.parse().unwrap();
+        if let Script::Package { functions, variables, .. } = script {
+            assert_eq!(functions[0].annotations, vec![
+                Annotation { name: "memo".to_string(), args: vec![] },
+                Annotation { name: "cache".to_string(), args: vec![Expr::Integer(100)] },
+            ]);
+            assert!(functions[0].memoize());
+            assert_eq!(variables[0].annotations.len(), 1);
+            assert_eq!(variables[0].annotations[0].name, "deprecated");
+        } else {
+            panic!("Expected Package");
+        }
+        
+        // @memo 只能用于函数
+        let mut lexer = Lexer::new("package util\n@memo\nRATE = 0.05\n");
+        let tokens = lexer.tokenize().unwrap();
+        assert!(Parser::new(tokens).parse().is_err());
+    }
+    
+    #[test]
+    fn test_format_script_is_stable() {
+        let source = r#"-- INPUT close:number, volume:number --
+-- OUTPUT level:string, label:string --
+-- CONFIG precision=2, div=null --
+# 注释会被丢弃
+ma = (close + close[-1]) * 2 ^ 2 / -volume
+if ma > 10 and not (volume < 5):
+    level = when ma >= 20 -> "高", else -> "中"
+elif ma == 0:
+    level = "零"
+else:
+    level = "低"
+total = [1, 2, 3] |> map(x -> x * 2) |> filter(x -> x > 2)
+recent = close[-5:]
+label = f"ma={ma}, {{raw}} \"q\""
+return [level, label]
+"#;
+        let format_once = |source: &str| {
+            let tokens = Lexer::new(source).tokenize().unwrap();
+            format::format_script(&Parser::new(tokens).parse().unwrap())
+        };
+        
+        let first = format_once(source);
+        assert!(first.contains("elif ma == 0:"), "{}", first);
+        assert!(!first.contains("注释"));
+        assert_eq!(format_once(&first), first);
+        
+        let package = "package util\n@memo\nfib(n: number) -> number:\n    return n < 2 ? n : fib(n - 1) + fib(n - 2)\nmut COUNT = 0\n";
+        let first = format_once(package);
+        assert_eq!(format_once(&first), first);
+    }
+    
+    #[test]
+    fn test_negative_literals_in_array() {
+        let tokens = Lexer::new("a = [-1, -2.5, -x]\n").tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        if let Script::DataScript { body, .. } = script {
+            if let Stmt::Assignment { value: Expr::Array(elements), .. } = &body[0] {
+                assert_eq!(elements[0], Expr::Integer(-1));
+                assert_eq!(elements[1], Expr::Number(-2.5));
+                assert!(matches!(&elements[2], Expr::Unary { op: UnaryOp::Neg, .. }));
+            } else {
+                panic!("Expected array assignment");
+            }
+        } else {
+            panic!("Expected DataScript");
+        }
+    }
+    
+    #[test]
+    fn test_multiline_expression_continuation() {
+        let body = |source: &str| match Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap() {
+            Script::DataScript { body, .. } => body,
+            _ => panic!("Expected DataScript"),
+        };
+        
+        // 行尾为 ? 的链式三元表达式
+        let split = body("x = 1\nlevel = x > 2 ?\n    \"high\" : x > 0 ?\n    \"mid\" : \"low\"\nreturn level\n");
+        assert_eq!(split, body("x = 1\nlevel = x > 2 ? \"high\" : x > 0 ? \"mid\" : \"low\"\nreturn level\n"));
+        
+        // 括号内换行，缩进块中的续行不影响缩进
+        let split = body("if true:\n    a = [1,\n  2,\n        3]\n    b = max(a[0],\n a[1])\n");
+        assert_eq!(split, body("if true:\n    a = [1, 2, 3]\n    b = max(a[0], a[1])\n"));
+        
+        // 行尾的 \\ 显式续行
+        let split = body("total = 1 \\\n    * 2\nreturn total\n");
+        assert_eq!(split, body("total = 1 * 2\nreturn total\n"));
+        
+        // 括号内的花括号 when 仍以换行分隔分支
+        let split = body("x = 1\ny = max(when {\n    x > 1 -> 1\n    else -> 0\n}, 0)\n");
+        assert_eq!(split, body("x = 1\ny = max(when { x > 1 -> 1, else -> 0 }, 0)\n"));
+    }
+    
+    #[test]
+    fn test_parse_export_declaration() {
+        let source = "package util\n-- EXPORT scale, RATE --\nRATE = 0.05\nscale(x):\n    return x\n";
+        let tokens = Lexer::new(source).tokenize().unwrap();
+        let script = Parser::new(tokens).parse().unwrap();
+        if let Script::Package { exports, .. } = script {
+            assert_eq!(exports, Some(vec!["scale".to_string(), "RATE".to_string()]));
+        } else {
+            panic!("Expected Package");
+        }
+        
+        // 只能导出包中定义的成员
+        let tokens = Lexer::new("package util\n-- EXPORT missing --\nRATE = 0.05\n").tokenize().unwrap();
+        let err = Parser::new(tokens).parse().unwrap_err();
+        assert!(err.message.contains("missing"), "{}", err.message);
+    }
+    
+    #[test]
+    fn test_duplicate_output_name_is_error() {
+        let source = "-- INPUT close:number --\n-- OUTPUT ma:number, ma:number --\nreturn [close, close]\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        
+        let err = parser.parse().unwrap_err();
+        assert!(err.message.contains("参数名重复: ma"), "{}", err.message);
+    }
 }
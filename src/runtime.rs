@@ -1,13 +1,16 @@
 // DPLang 运行时 - Value 类型和基本运算
 
 use std::fmt;
-use rust_decimal::Decimal;
+use rust_decimal::{Decimal, RoundingStrategy};
+use crate::parser::RoundingMode;
 use std::str::FromStr;
 
 /// 运行时值
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
+    /// 整数（整数字面量、整数形式的 CSV 字段），溢出或与小数运算时提升为 Number/Decimal
+    Integer(i64),
     Decimal(Decimal),
     String(String),
     Bool(bool),
@@ -15,8 +18,8 @@ pub enum Value {
     Array(Vec<Value>),
     /// 数组切片（零拷贝引用）
     ArraySlice {
-        /// 底层列数据的共享引用
-        column_data: std::rc::Rc<Vec<Value>>,
+        /// 底层列数据的共享引用（Arc，Value 可以在线程间移交）
+        column_data: std::sync::Arc<Vec<Value>>,
         /// 切片起始索引
         start: usize,
         /// 切片长度
@@ -33,10 +36,85 @@ pub enum Value {
     Function(Box<crate::parser::FunctionDef>),
 }
 
+impl PartialEq for Value {
+    /// 整数与同值的 Number/Decimal 视为相等（1 == 1.0）
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::Integer(a), Value::Integer(b)) => a == b,
+            (Value::Integer(a), Value::Number(b)) | (Value::Number(b), Value::Integer(a)) => {
+                compare_integer_number(*a, *b) == Some(std::cmp::Ordering::Equal)
+            }
+            (Value::Integer(a), Value::Decimal(b)) | (Value::Decimal(b), Value::Integer(a)) => Decimal::from(*a) == *b,
+            (Value::Decimal(a), Value::Decimal(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            (Value::Array(a), Value::Array(b)) => a == b,
+            (
+                Value::ArraySlice { column_data: a, start: a_start, len: a_len },
+                Value::ArraySlice { column_data: b, start: b_start, len: b_len },
+            ) => a == b && a_start == b_start && a_len == b_len,
+            (
+                Value::Lambda { params: a_params, body: a_body, captures: a_captures },
+                Value::Lambda { params: b_params, body: b_body, captures: b_captures },
+            ) => a_params == b_params && a_body == b_body && a_captures == b_captures,
+            (Value::Function(a), Value::Function(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+/// 舍入方式对应的 Decimal 舍入策略
+fn rounding_strategy(mode: RoundingMode) -> RoundingStrategy {
+    match mode {
+        RoundingMode::HalfUp => RoundingStrategy::MidpointAwayFromZero,
+        RoundingMode::HalfEven => RoundingStrategy::MidpointNearestEven,
+        RoundingMode::Truncate => RoundingStrategy::ToZero,
+        RoundingMode::Ceil => RoundingStrategy::ToPositiveInfinity,
+        RoundingMode::Floor => RoundingStrategy::ToNegativeInfinity,
+    }
+}
+
+/// 整数与浮点数精确比较（不把整数转成 f64，避免超过 2^53 时丢精度）；NaN 返回 None
+fn compare_integer_number(i: i64, f: f64) -> Option<std::cmp::Ordering> {
+    use std::cmp::Ordering;
+    // 2^63：超出 i64 范围的浮点数直接判定大小
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if f.is_nan() {
+        return None;
+    }
+    if f >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if f < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    // 范围内向下取整后可精确转为 i64；整数部分相等时由小数部分决定
+    match i.cmp(&(f.floor() as i64)) {
+        Ordering::Equal if f.fract() != 0.0 => Some(Ordering::Less),
+        ord => Some(ord),
+    }
+}
+
+/// 整数与 Number/Decimal 混合运算时，把整数一侧提升为对方的类型
+fn promote_integer(a: &Value, b: &Value) -> (Value, Value) {
+    let lift = |i: i64, like: &Value| match like {
+        Value::Decimal(_) => Value::Decimal(Decimal::from(i)),
+        _ => Value::Number(i as f64),
+    };
+    match (a, b) {
+        (Value::Integer(i), other) => (lift(*i, other), other.clone()),
+        (other, Value::Integer(i)) => (other.clone(), lift(*i, other)),
+        _ => (a.clone(), b.clone()),
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
+            Value::Integer(i) => write!(f, "{}", i),
             Value::Decimal(d) => write!(f, "{}", d),
             Value::String(s) => write!(f, "\"{}\"", s),
             Value::Bool(b) => write!(f, "{}", b),
@@ -80,6 +158,7 @@ impl Value {
             Value::Bool(b) => *b,
             Value::Null => false,
             Value::Number(n) => *n != 0.0,
+            Value::Integer(i) => *i != 0,
             Value::Decimal(d) => !d.is_zero(),
             Value::String(s) => !s.is_empty(),
             Value::Array(arr) => !arr.is_empty(),
@@ -94,7 +173,9 @@ impl Value {
     /// 如需在计算中跳过 Null，请使用 is_null() 检查或 to_number_or_default()
     pub fn to_number(&self) -> Result<f64, RuntimeError> {
         match self {
+            Value::Number(n) if !n.is_finite() => Err(RuntimeError::non_finite()),
             Value::Number(n) => Ok(*n),
+            Value::Integer(i) => Ok(*i as f64),
             Value::Decimal(d) => Ok(d.to_string().parse().unwrap_or(0.0)),
             Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
             Value::Null => Err(RuntimeError::type_error("Null 值无法转换为数字")),
@@ -116,11 +197,33 @@ impl Value {
     pub fn is_null(&self) -> bool {
         matches!(self, Value::Null)
     }
+    
+    /// 解析数字文本：整数形式（如 "42"、"-7"）得到 Integer，其余得到 Number
+    pub fn parse_number(text: &str) -> Option<Value> {
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(i) = text.parse::<i64>() {
+                return Some(Value::Integer(i));
+            }
+        }
+        text.parse::<f64>().ok().map(Value::Number)
+    }
+    
+    /// 转换为数组下标（整数直接使用，Number 截断小数部分）
+    pub fn to_index(&self) -> Option<isize> {
+        match self {
+            Value::Integer(i) => isize::try_from(*i).ok(),
+            Value::Number(n) if n.is_finite() => Some(*n as isize),
+            _ => None,
+        }
+    }
         
     /// 转换为 Decimal
     pub fn to_decimal(&self) -> Result<Decimal, RuntimeError> {
         match self {
             Value::Decimal(d) => Ok(*d),
+            Value::Integer(i) => Ok(Decimal::from(*i)),
+            Value::Number(n) if !n.is_finite() => Err(RuntimeError::non_finite()),
             Value::Number(n) => {
                 // 将 f64 转为字符串再解析为 Decimal
                 Decimal::from_str(&n.to_string())
@@ -134,18 +237,87 @@ impl Value {
         }
     }
         
+    /// 转换为 JSON（decimal 按数字输出；NaN/Infinity、Lambda 和函数无法表示）
+    pub fn to_json(&self) -> Result<serde_json::Value, RuntimeError> {
+        match self {
+            Value::Null => Ok(serde_json::Value::Null),
+            Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
+            Value::Integer(i) => Ok(serde_json::Value::from(*i)),
+            Value::Number(n) => serde_json::Number::from_f64(*n)
+                .map(serde_json::Value::Number)
+                .ok_or_else(RuntimeError::non_finite),
+            Value::Decimal(d) => serde_json::from_str(&d.to_string())
+                .map_err(|_| RuntimeError::type_error(&format!("无法转换为 JSON 数字: {}", d))),
+            Value::String(s) => Ok(serde_json::Value::String(s.clone())),
+            Value::Array(items) => items.iter()
+                .map(Value::to_json)
+                .collect::<Result<_, _>>()
+                .map(serde_json::Value::Array),
+            Value::ArraySlice { column_data, start, len } => (0..*len)
+                .filter_map(|i| column_data.get(*start + i))
+                .map(Value::to_json)
+                .collect::<Result<_, _>>()
+                .map(serde_json::Value::Array),
+            Value::Lambda { .. } | Value::Function(_) => {
+                Err(RuntimeError::type_error("函数无法转换为 JSON"))
+            }
+        }
+    }
+    
+    /// 从 JSON 转换（整数得到 Integer，暂不支持 JSON 对象）
+    pub fn from_json(json: serde_json::Value) -> Result<Value, RuntimeError> {
+        match json {
+            serde_json::Value::Null => Ok(Value::Null),
+            serde_json::Value::Bool(b) => Ok(Value::Bool(b)),
+            serde_json::Value::Number(n) => n.as_i64()
+                .map(Value::Integer)
+                .or_else(|| n.as_f64().map(Value::Number))
+                .ok_or_else(|| RuntimeError::type_error(&format!("无法表示的数字: {}", n))),
+            serde_json::Value::String(s) => Ok(Value::String(s)),
+            serde_json::Value::Array(items) => Ok(Value::Array(
+                items.into_iter().map(Value::from_json).collect::<Result<_, _>>()?,
+            )),
+            serde_json::Value::Object(_) => Err(RuntimeError::type_error("不支持嵌套对象")),
+        }
+    }
+    
     /// 应用精度设置，转换为 Decimal 并设置小数位数
     pub fn apply_precision(&self, scale: u32) -> Result<Value, RuntimeError> {
+        self.apply_precision_with_mode(scale, RoundingMode::default())
+    }
+    
+    /// 按指定舍入方式应用精度设置
+    pub fn apply_precision_with_mode(&self, scale: u32, mode: RoundingMode) -> Result<Value, RuntimeError> {
         let decimal = self.to_decimal()?;
         // 设置小数位数
-        let rounded = decimal.round_dp(scale);
+        let rounded = decimal.round_dp_with_strategy(scale, rounding_strategy(mode));
         Ok(Value::Decimal(rounded))
     }
     
+    /// 按最小变动单位取整（如价格对齐到 0.01），tick 必须为正
+    pub fn round_to_tick(&self, tick: &Value, mode: RoundingMode) -> Result<Value, RuntimeError> {
+        let tick = tick.to_decimal()?;
+        if tick <= Decimal::ZERO {
+            return Err(RuntimeError::type_error(&format!("最小变动单位必须为正数: {}", tick)));
+        }
+        let steps = (self.to_decimal()? / tick).round_dp_with_strategy(0, rounding_strategy(mode));
+        let rounded = (steps * tick).normalize();
+        match self {
+            Value::Decimal(_) => Ok(Value::Decimal(rounded)),
+            _ => Value::Decimal(rounded).to_number().map(Value::Number),
+        }
+    }
+    
     /// 加法
     pub fn add(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a + b)),
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_add(*b)
+                .map_or(Value::Number(*a as f64 + *b as f64), Value::Integer)),
+            (Value::Integer(_), Value::Number(_) | Value::Decimal(_)) | (Value::Number(_) | Value::Decimal(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.add(&b)
+            }
             (Value::Decimal(a), Value::Decimal(b)) => Ok(Value::Decimal(a + b)),
             (Value::String(a), Value::String(b)) => Ok(Value::String(format!("{}{}", a, b))),
             (Value::Array(a), Value::Array(b)) => {
@@ -167,6 +339,8 @@ impl Value {
                 }
                 Ok(Value::Array(result))
             }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error(&format!("无法执行加法: {} + {}", self, other))),
         }
     }
@@ -175,6 +349,12 @@ impl Value {
     pub fn sub(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a - b)),
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_sub(*b)
+                .map_or(Value::Number(*a as f64 - *b as f64), Value::Integer)),
+            (Value::Integer(_), Value::Number(_) | Value::Decimal(_)) | (Value::Number(_) | Value::Decimal(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.sub(&b)
+            }
             (Value::Decimal(a), Value::Decimal(b)) => Ok(Value::Decimal(a - b)),
             (Value::Array(a), Value::Array(b)) => {
                 if a.len() != b.len() {
@@ -200,6 +380,8 @@ impl Value {
                 }
                 Ok(Value::Array(result))
             }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error(&format!("无法执行减法: {} - {}", self, other))),
         }
     }
@@ -208,6 +390,12 @@ impl Value {
     pub fn mul(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a * b)),
+            (Value::Integer(a), Value::Integer(b)) => Ok(a.checked_mul(*b)
+                .map_or(Value::Number(*a as f64 * *b as f64), Value::Integer)),
+            (Value::Integer(_), Value::Number(_) | Value::Decimal(_)) | (Value::Number(_) | Value::Decimal(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.mul(&b)
+            }
             (Value::Decimal(a), Value::Decimal(b)) => Ok(Value::Decimal(a * b)),
             (Value::Array(a), Value::Array(b)) => {
                 if a.len() != b.len() {
@@ -226,6 +414,8 @@ impl Value {
                 }
                 Ok(Value::Array(result))
             }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error(&format!("无法执行乘法: {} * {}", self, other))),
         }
     }
@@ -239,6 +429,17 @@ impl Value {
                 }
                 Ok(Value::Number(a / b))
             }
+            // 整数相除结果为 Number（7 / 2 = 3.5）
+            (Value::Integer(a), Value::Integer(b)) => {
+                if *b == 0 {
+                    return Err(RuntimeError::zero_division());
+                }
+                Ok(Value::Number(*a as f64 / *b as f64))
+            }
+            (Value::Integer(_), Value::Number(_) | Value::Decimal(_)) | (Value::Number(_) | Value::Decimal(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.div(&b)
+            }
             (Value::Decimal(a), Value::Decimal(b)) => {
                 if b.is_zero() {
                     return Err(RuntimeError::zero_division());
@@ -269,6 +470,8 @@ impl Value {
                 }
                 Ok(Value::Array(result))
             }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error(&format!("无法执行除法: {} / {}", self, other))),
         }
     }
@@ -282,6 +485,18 @@ impl Value {
                 }
                 Ok(Value::Number(a % b))
             }
+            (Value::Integer(a), Value::Integer(b)) => {
+                if *b == 0 {
+                    return Err(RuntimeError::zero_division());
+                }
+                Ok(a.checked_rem(*b).map_or(Value::Number(*a as f64 % *b as f64), Value::Integer))
+            }
+            (Value::Integer(_), Value::Number(_)) | (Value::Number(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.modulo(&b)
+            }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error("取模运算仅支持数字")),
         }
     }
@@ -290,6 +505,15 @@ impl Value {
     pub fn pow(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Number(a.powf(*b))),
+            (Value::Integer(a), Value::Integer(b)) => Ok(u32::try_from(*b).ok()
+                .and_then(|exp| a.checked_pow(exp))
+                .map_or(Value::Number((*a as f64).powf(*b as f64)), Value::Integer)),
+            (Value::Integer(_), Value::Number(_)) | (Value::Number(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.pow(&b)
+            }
+            // Null 参与算术运算时结果为 Null（空值传播，不当作 0）
+            (Value::Null, _) | (_, Value::Null) => Ok(Value::Null),
             _ => Err(RuntimeError::type_error("幂运算仅支持数字")),
         }
     }
@@ -298,6 +522,17 @@ impl Value {
     pub fn gt(&self, other: &Value) -> Result<Value, RuntimeError> {
         match (self, other) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Bool(a > b)),
+            (Value::Integer(a), Value::Integer(b)) => Ok(Value::Bool(a > b)),
+            (Value::Integer(a), Value::Number(b)) => {
+                Ok(Value::Bool(compare_integer_number(*a, *b) == Some(std::cmp::Ordering::Greater)))
+            }
+            (Value::Number(a), Value::Integer(b)) => {
+                Ok(Value::Bool(compare_integer_number(*b, *a) == Some(std::cmp::Ordering::Less)))
+            }
+            (Value::Integer(_), Value::Decimal(_)) | (Value::Decimal(_), Value::Integer(_)) => {
+                let (a, b) = promote_integer(self, other);
+                a.gt(&b)
+            }
             (Value::Decimal(a), Value::Decimal(b)) => Ok(Value::Bool(a > b)),
             (Value::String(a), Value::String(b)) => Ok(Value::Bool(a > b)),
             (Value::Array(a), Value::Array(b)) => {
@@ -393,7 +628,9 @@ impl Value {
     pub fn neg(&self) -> Result<Value, RuntimeError> {
         match self {
             Value::Number(n) => Ok(Value::Number(-n)),
+            Value::Integer(i) => Ok(i.checked_neg().map_or(Value::Number(-(*i as f64)), Value::Integer)),
             Value::Decimal(d) => Ok(Value::Decimal(-d)),
+            Value::Null => Ok(Value::Null),
             Value::Array(a) => {
                 let mut result = Vec::new();
                 for av in a.iter() {
@@ -414,6 +651,7 @@ pub struct RuntimeError {
     pub line: Option<usize>,        // 新增：错误行号
     pub column: Option<usize>,      // 新增：错误列号
     pub context: Option<String>,    // 新增：错误上下文
+    pub name: Option<String>,       // 出错的变量/函数名（未定义、参数不匹配、沙箱禁用时）
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -425,6 +663,10 @@ pub enum ErrorType {
     UndefinedVariable,
     UndefinedFunction,
     ArgumentMismatch,
+    NonFinite,
+    Cancelled,
+    PermissionDenied,
+    LimitExceeded,
 }
 
 impl RuntimeError {
@@ -435,6 +677,18 @@ impl RuntimeError {
             line: None,
             column: None,
             context: None,
+            name: None,
+        }
+    }
+    
+    pub fn non_finite() -> Self {
+        RuntimeError {
+            error_type: ErrorType::NonFinite,
+            message: "数值结果为 NaN 或 Infinity".to_string(),
+            line: None,
+            column: None,
+            context: None,
+            name: None,
         }
     }
     
@@ -445,6 +699,19 @@ impl RuntimeError {
             line: None,
             column: None,
             context: None,
+            name: None,
+        }
+    }
+    
+    /// 数组越界访问（严格索引模式下）
+    pub fn index_out_of_bounds(index: isize, len: usize) -> Self {
+        RuntimeError {
+            error_type: ErrorType::IndexOutOfBounds,
+            message: format!("数组索引越界: 索引 {}，长度 {}", index, len),
+            line: None,
+            column: None,
+            context: None,
+            name: None,
         }
     }
     
@@ -455,6 +722,7 @@ impl RuntimeError {
             line: None,
             column: None,
             context: None,
+            name: Some(name.to_string()),
         }
     }
     
@@ -465,9 +733,63 @@ impl RuntimeError {
             line: None,
             column: None,
             context: None,
+            name: Some(name.to_string()),
         }
     }
     
+    /// 参数数量不匹配，expected 如 "2"、"2-3"、"至少 1"
+    pub fn argument_mismatch(name: &str, expected: &str, actual: usize) -> Self {
+        RuntimeError {
+            error_type: ErrorType::ArgumentMismatch,
+            message: format!("{} 需要 {} 个参数，实际传入 {} 个", name, expected, actual),
+            line: None,
+            column: None,
+            context: None,
+            name: Some(name.to_string()),
+        }
+    }
+    
+    /// 执行被取消或超时
+    pub fn cancelled(message: &str) -> Self {
+        RuntimeError {
+            error_type: ErrorType::Cancelled,
+            message: message.to_string(),
+            line: None,
+            column: None,
+            context: None,
+            name: None,
+        }
+    }
+    
+    /// 沙箱禁用的内置函数被调用
+    pub fn permission_denied(name: &str) -> Self {
+        RuntimeError {
+            error_type: ErrorType::PermissionDenied,
+            message: format!("{} 不可用：当前沙箱配置禁止调用该函数", name),
+            line: None,
+            column: None,
+            context: None,
+            name: Some(name.to_string()),
+        }
+    }
+    
+    /// 超出资源限制（数组长度、输入行数）
+    pub fn limit_exceeded(message: &str) -> Self {
+        RuntimeError {
+            error_type: ErrorType::LimitExceeded,
+            message: message.to_string(),
+            line: None,
+            column: None,
+            context: None,
+            name: None,
+        }
+    }
+    
+    /// 是否为取消/超时错误（不应被 ERROR 块或 SkipRow 吞掉）
+    pub fn is_cancelled(&self) -> bool {
+        self.error_type == ErrorType::Cancelled
+    }
+    
     /// 添加位置信息
     pub fn with_location(mut self, line: usize, column: usize) -> Self {
         self.line = Some(line);
@@ -513,6 +835,20 @@ mod tests {
         assert_eq!(a.add(&b).unwrap(), Value::Number(3.0));
     }
     
+    #[test]
+    fn test_integer_number_exact_compare() {
+        let big = Value::Integer(9007199254740993);
+        let near = Value::Number(9007199254740992.0);
+        assert_ne!(big, near);
+        assert_eq!(big.gt(&near).unwrap(), Value::Bool(true));
+        assert_eq!(near.lt(&big).unwrap(), Value::Bool(true));
+        assert_eq!(Value::Integer(1), Value::Number(1.0));
+        assert_eq!(Value::Integer(3).lt(&Value::Number(3.5)).unwrap(), Value::Bool(true));
+        assert_eq!(Value::Integer(-4).lt(&Value::Number(-3.5)).unwrap(), Value::Bool(true));
+        assert_eq!(Value::Integer(i64::MAX).lt(&Value::Number(f64::INFINITY)).unwrap(), Value::Bool(true));
+        assert_ne!(Value::Integer(i64::MAX), Value::Number(9223372036854775807.0));
+    }
+
     #[test]
     fn test_array_add() {
         let a = Value::Array(vec![Value::Number(1.0), Value::Number(2.0)]);
@@ -1,20 +1,27 @@
 // 语义分析器 - 在执行前进行静态检查
 
-use crate::parser::{Script, Stmt, Expr, FunctionDef};
+use crate::parser::{Script, Stmt, Expr, FStringSegment, FunctionDef, Annotation, KNOWN_ANNOTATIONS};
+use crate::executor::SandboxConfig;
+use serde::Serialize;
 use std::collections::{HashMap, HashSet};
 
+/// 执行器提供的内置变量（不需要在脚本中定义）
+const BUILTIN_VARIABLES: &[&str] = &["_index", "_total", "_global_index", "_global_total", "_args", "_args_names"];
+
 /// 语义分析错误类型
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize)]
 pub enum SemanticErrorType {
     UndefinedVariable,
     VariableShadowing,
     UnusedVariable,
     TypeMismatch,
     InvalidOperation,
+    PermissionDenied,
+    UnknownAnnotation,
 }
 
 /// 语义分析错误
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct SemanticError {
     pub error_type: SemanticErrorType,
     pub message: String,
@@ -46,6 +53,22 @@ impl SemanticError {
         }
     }
     
+    pub fn permission_denied(name: &str) -> Self {
+        SemanticError {
+            error_type: SemanticErrorType::PermissionDenied,
+            message: format!("沙箱配置禁止调用函数: {}", name),
+            variable: Some(name.to_string()),
+        }
+    }
+    
+    pub fn unknown_annotation(name: &str) -> Self {
+        SemanticError {
+            error_type: SemanticErrorType::UnknownAnnotation,
+            message: format!("未知的注解: @{}（将被忽略）", name),
+            variable: None,
+        }
+    }
+    
     pub fn type_mismatch(expected: &str, actual: &str) -> Self {
         SemanticError {
             error_type: SemanticErrorType::TypeMismatch,
@@ -56,7 +79,7 @@ impl SemanticError {
 }
 
 /// 语义分析结果
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct SemanticAnalysisResult {
     /// 错误列表
     pub errors: Vec<SemanticError>,
@@ -83,6 +106,22 @@ impl SemanticAnalysisResult {
     pub fn add_warning(&mut self, warning: SemanticError) {
         self.warnings.push(warning);
     }
+    
+    /// 诊断信息的 JSON 数组（供编辑器等工具使用），每项带 severity: error / warning
+    pub fn diagnostics_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Diagnostic<'a> {
+            severity: &'static str,
+            #[serde(flatten)]
+            error: &'a SemanticError,
+        }
+        
+        let diagnostics: Vec<_> = self.errors.iter()
+            .map(|error| Diagnostic { severity: "error", error })
+            .chain(self.warnings.iter().map(|error| Diagnostic { severity: "warning", error }))
+            .collect();
+        serde_json::to_string(&diagnostics).unwrap_or_else(|_| "[]".to_string())
+    }
 }
 
 /// 作用域信息
@@ -160,6 +199,8 @@ pub struct SemanticAnalyzer {
     result: SemanticAnalysisResult,
     /// 内置函数集合
     builtin_functions: HashSet<String>,
+    /// 沙箱禁用的内置函数
+    disallowed_functions: HashSet<String>,
 }
 
 impl SemanticAnalyzer {
@@ -177,11 +218,57 @@ impl SemanticAnalyzer {
         builtin_functions.insert("past".to_string());
         builtin_functions.insert("offset".to_string());
         builtin_functions.insert("window".to_string());
+        builtin_functions.insert("sqrt".to_string());
+        builtin_functions.insert("sign".to_string());
+        builtin_functions.insert("clamp".to_string());
+        builtin_functions.insert("lerp".to_string());
+        builtin_functions.insert("round_to_tick".to_string());
+        builtin_functions.insert("approx_eq".to_string());
+        builtin_functions.insert("validate_ohlc".to_string());
+        builtin_functions.insert("clean_ohlc".to_string());
+        builtin_functions.insert("in_range".to_string());
+        builtin_functions.insert("mask".to_string());
+        builtin_functions.insert("chunk".to_string());
+        builtin_functions.insert("windows".to_string());
+        builtin_functions.insert("head".to_string());
+        builtin_functions.insert("tap".to_string());
+        builtin_functions.insert("tail".to_string());
+        builtin_functions.insert("cummax".to_string());
+        builtin_functions.insert("cummin".to_string());
+        builtin_functions.insert("fill_null".to_string());
+        builtin_functions.insert("coalesce".to_string());
+        builtin_functions.insert("nvl".to_string());
+        builtin_functions.insert("if_null".to_string());
+        builtin_functions.insert("ffill".to_string());
+        builtin_functions.insert("interpolate".to_string());
+        builtin_functions.insert("pct_change".to_string());
+        builtin_functions.insert("forward".to_string());
+        builtin_functions.insert("returns".to_string());
+        builtin_functions.insert("roc".to_string());
+        builtin_functions.insert("mom".to_string());
+        builtin_functions.insert("drawdown".to_string());
+        builtin_functions.insert("percentile".to_string());
+        builtin_functions.insert("quantile".to_string());
+        builtin_functions.insert("histogram".to_string());
+        builtin_functions.insert("load_csv".to_string());
+        builtin_functions.insert("lookup".to_string());
+        builtin_functions.insert("to_json".to_string());
+        builtin_functions.insert("from_json".to_string());
+        builtin_functions.insert("format_number".to_string());
         
         SemanticAnalyzer {
             scope: Scope::new(),
             result: SemanticAnalysisResult::new(),
             builtin_functions,
+            disallowed_functions: HashSet::new(),
+        }
+    }
+    
+    /// 按沙箱配置预检：调用被禁用的内置函数报错，脚本在执行前即被拒绝
+    pub fn with_sandbox(sandbox: &SandboxConfig) -> Self {
+        SemanticAnalyzer {
+            disallowed_functions: sandbox.disallowed().map(str::to_string).collect(),
+            ..Self::new()
         }
     }
     
@@ -224,12 +311,14 @@ impl SemanticAnalyzer {
             Script::Package { variables, functions, .. } => {
                 // 定义包变量
                 for var_def in variables {
+                    self.check_annotations(&var_def.annotations);
                     self.scope.define(var_def.name.clone());
                     self.analyze_expr(&var_def.value);
                 }
                 
                 // 定义包函数
                 for func_def in functions {
+                    self.check_annotations(&func_def.annotations);
                     self.analyze_function(func_def);
                 }
             }
@@ -238,6 +327,15 @@ impl SemanticAnalyzer {
         std::mem::replace(&mut self.result, SemanticAnalysisResult::new())
     }
     
+    /// 未知注解给出警告（保持向前兼容，不报错）
+    fn check_annotations(&mut self, annotations: &[Annotation]) {
+        for annotation in annotations {
+            if !KNOWN_ANNOTATIONS.contains(&annotation.name.as_str()) {
+                self.result.add_warning(SemanticError::unknown_annotation(&annotation.name));
+            }
+        }
+    }
+    
     /// 分析语句列表
     fn analyze_statements(&mut self, stmts: &[Stmt]) {
         for stmt in stmts {
@@ -305,6 +403,10 @@ impl SemanticAnalyzer {
                         crate::parser::DestructurePattern::Spread(name) => {
                             self.scope.define(name.clone());
                         }
+                        crate::parser::DestructurePattern::Default(name, default) => {
+                            self.analyze_expr(default);
+                            self.scope.define(name.clone());
+                        }
                         crate::parser::DestructurePattern::Ignore => {}
                     }
                 }
@@ -317,7 +419,10 @@ impl SemanticAnalyzer {
         match expr {
             Expr::Identifier(name) => {
                 // 检查变量是否定义
-                if !self.scope.is_defined(name) && !self.builtin_functions.contains(name) {
+                if !self.scope.is_defined(name)
+                    && !self.builtin_functions.contains(name)
+                    && !BUILTIN_VARIABLES.contains(&name.as_str())
+                {
                     self.result.add_error(SemanticError::undefined_variable(name));
                 } else {
                     self.scope.mark_used(name);
@@ -345,28 +450,19 @@ impl SemanticAnalyzer {
                 self.analyze_expr(else_expr);
             }
             
-            Expr::FString(parts) => {
-                // 分析 f-string 中的表达式
-                for part in parts {
-                    if let crate::lexer::FStringPart::Expr(expr_str) = part {
-                        // 解析并分析嵌入的表达式
-                        if let Ok(mut lexer) = std::panic::catch_unwind(|| crate::lexer::Lexer::new(expr_str)) {
-                            if let Ok(tokens) = lexer.tokenize() {
-                                let mut parser = crate::parser::Parser::new(tokens);
-                                if let Ok(script) = parser.parse() {
-                                    if let crate::parser::Script::DataScript { body, .. } = script {
-                                        for stmt in &body {
-                                            self.analyze_stmt(stmt);
-                                        }
-                                    }
-                                }
-                            }
-                        }
+            Expr::FString(segments) => {
+                // 分析 f-string 中（已编译）的表达式
+                for segment in segments {
+                    if let FStringSegment::Expr(inner) = segment {
+                        self.analyze_expr(inner);
                     }
                 }
             }
             
-            Expr::When { branches, else_expr } => {
+            Expr::When { subject, branches, else_expr } => {
+                if let Some(subject_expr) = subject {
+                    self.analyze_expr(subject_expr);
+                }
                 // 分析 when 表达式的所有分支
                 for branch in branches {
                     self.analyze_expr(&branch.condition);
@@ -379,10 +475,12 @@ impl SemanticAnalyzer {
             
             Expr::Call { callee, args } => {
                 // 检查函数是否定义
-                if !self.builtin_functions.contains(callee) && !self.scope.is_defined(callee) {
-                    self.result.add_error(SemanticError::undefined_variable(callee));
-                } else {
+                if self.scope.is_defined(callee) {
                     self.scope.mark_used(callee);
+                } else if self.disallowed_functions.contains(callee) {
+                    self.result.add_error(SemanticError::permission_denied(callee));
+                } else if !self.builtin_functions.contains(callee) {
+                    self.result.add_error(SemanticError::undefined_variable(callee));
                 }
                 
                 for arg in args {
@@ -441,7 +539,7 @@ impl SemanticAnalyzer {
             }
             
             // 字面量不需要检查
-            Expr::Number(_) | Expr::String(_) | Expr::Bool(_) | Expr::Null => {}
+            Expr::Number(_) | Expr::Integer(_) | Expr::String(_) | Expr::Bool(_) | Expr::Null => {}
         }
     }
     
@@ -487,6 +585,14 @@ mod tests {
     use crate::lexer::Lexer;
     use crate::parser::Parser;
     
+    #[test]
+    fn test_builtin_variables_are_defined() {
+        let source = "return [_index, _total, _global_index, _global_total]\n";
+        let script = Parser::new(Lexer::new(source).tokenize().unwrap()).parse().unwrap();
+        let result = SemanticAnalyzer::new().analyze(&script);
+        assert!(!result.has_errors(), "{:?}", result.errors);
+    }
+    
     #[test]
     fn test_undefined_variable() {
         let source = r#"
@@ -507,6 +613,47 @@ return [result]
         assert!(result.has_errors());
         assert_eq!(result.errors.len(), 1);
         assert_eq!(result.errors[0].error_type, SemanticErrorType::UndefinedVariable);
+        
+        let json: serde_json::Value = serde_json::from_str(&result.diagnostics_json()).unwrap();
+        assert_eq!(json[0]["severity"], "error");
+        assert_eq!(json[0]["error_type"], "UndefinedVariable");
+        assert_eq!(json[0]["variable"], "y");
+    }
+    
+    #[test]
+    fn test_sandbox_rejects_disallowed_builtin() {
+        let source = r#"
+-- OUTPUT total:number --
+
+table = load_csv("prices.csv")
+return [sum([1, 2])]
+"#;
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let result = SemanticAnalyzer::with_sandbox(&SandboxConfig::safe()).analyze(&script);
+        assert_eq!(result.errors.len(), 1);
+        assert_eq!(result.errors[0].error_type, SemanticErrorType::PermissionDenied);
+        assert_eq!(result.errors[0].variable.as_deref(), Some("load_csv"));
+        
+        let result = SemanticAnalyzer::new().analyze(&script);
+        assert!(!result.has_errors());
+    }
+    
+    #[test]
+    fn test_unknown_annotation_is_warning() {
+        let source = "package util\n@memo\n@cache(100)\ndouble(x):\n    return x * 2\n";
+        let mut lexer = Lexer::new(source);
+        let tokens = lexer.tokenize().unwrap();
+        let mut parser = Parser::new(tokens);
+        let script = parser.parse().unwrap();
+        
+        let result = SemanticAnalyzer::new().analyze(&script);
+        assert!(!result.has_errors());
+        assert_eq!(result.warnings.len(), 1);
+        assert_eq!(result.warnings[0].error_type, SemanticErrorType::UnknownAnnotation);
     }
     
     #[test]